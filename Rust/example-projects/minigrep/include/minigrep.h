@@ -0,0 +1,48 @@
+/* minigrep's search for C and C++ - see src/ffi.rs for how to build the
+ * library (with the ffi feature) to link against. */
+#ifndef MINIGREP_H
+#define MINIGREP_H
+
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* ran fine, even if nothing matched */
+#define MINIGREP_OK 0
+/* a pointer that can't be null was */
+#define MINIGREP_NULL 1
+/* the query or the contents weren't UTF-8 */
+#define MINIGREP_NOT_UTF8 2
+
+/* a line that matched, as where it is in the contents searched */
+typedef struct {
+    /* 1-based */
+    size_t line_number;
+    /* where the line starts in the contents, and how long it is (without its
+     * line break) */
+    size_t offset;
+    size_t len;
+} MinigrepLine;
+
+typedef struct {
+    MinigrepLine *lines;
+    size_t len;
+} MinigrepResults;
+
+/* Searches the NUL-terminated contents for lines containing the query, case
+ * sensitively. On MINIGREP_OK the results belong to the caller until they're
+ * given to minigrep_results_free; on anything else they're left empty. The
+ * lines point into contents, so it has to outlive them. */
+int minigrep_search(const char *query, const char *contents, MinigrepResults *out_results);
+
+/* Frees the lines found, leaving the results empty (so freeing them twice is
+ * harmless). */
+void minigrep_results_free(MinigrepResults *results);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,195 @@
+// The flags as the options help lists them - which there are, and which take
+// a value - for reading the command line, the config file and the MINIGREP_
+// environment variables the same way.
+
+use crate::MinigrepError;
+
+// flags that take a value but aren't in the help
+const HIDDEN_WITH_VALUES: [&str; 1] = ["--generate-completions"];
+
+// whether the options help lists the flag
+pub(crate) fn lists(help: &str, flag: &str) -> bool {
+    help.lines()
+        .any(|line| flag_spec(line).split([',', ' ']).any(|word| word == flag))
+}
+
+// the name of the value the flag is listed with, like "num" for <num>
+pub(crate) fn value_name<'a>(help: &'a str, flag: &str) -> Option<&'a str> {
+    let spec = help
+        .lines()
+        .map(flag_spec)
+        .find(|spec| spec.split([',', ' ']).any(|word| word == flag))?;
+    let (_, value) = spec.split_once('<')?;
+    value.split_once('>').map(|(name, _)| name)
+}
+
+fn takes_value(help: &str, flag: &str) -> bool {
+    value_name(help, flag).is_some() || HIDDEN_WITH_VALUES.contains(&flag)
+}
+
+// the flags at the start of a line of the options help, before their description
+fn flag_spec(line: &str) -> &str {
+    let line = line.trim_start();
+    if !line.starts_with('-') {
+        return "";
+    }
+    line.split_once("  ").map_or(line, |(spec, _)| spec)
+}
+
+// Split the command line into one flag or value per argument, the way
+// Config::build reads it: "--max-depth=3" becomes "--max-depth" "3", "-in"
+// becomes "-i" "-n" and "-A3" (or "-nA3") ends with "-A" "3". Values that
+// follow their flag, and everything after "--", are left as they are.
+pub fn normalize(
+    mut args: impl Iterator<Item = String>,
+    help: &str,
+) -> Result<Vec<String>, MinigrepError> {
+    let mut normal = Vec::new();
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            normal.push(arg);
+            normal.extend(args.by_ref());
+            break;
+        }
+
+        // whether the next argument is the value of this one's (last) flag
+        let mut value_follows = false;
+        if let Some(long) = arg.strip_prefix("--") {
+            match long.split_once('=') {
+                Some((name, value)) => {
+                    let flag = format!("--{name}");
+                    if lists(help, &flag) && !takes_value(help, &flag) {
+                        return Err(MinigrepError::InvalidArg(format!(
+                            "{flag} doesn't take a value"
+                        )));
+                    }
+                    normal.extend([flag, value.to_string()]);
+                }
+                None => {
+                    value_follows = takes_value(help, &arg);
+                    normal.push(arg);
+                }
+            }
+        } else if arg.len() > 2 && arg.starts_with('-') {
+            let group = &arg[1..];
+            for (index, c) in group.char_indices() {
+                let flag = format!("-{c}");
+                if takes_value(help, &flag) {
+                    let value = &group[index + c.len_utf8()..];
+                    value_follows = value.is_empty();
+                    normal.push(flag);
+                    if !value.is_empty() {
+                        normal.push(value.to_string());
+                    }
+                    break;
+                }
+                normal.push(flag);
+            }
+        } else {
+            value_follows = takes_value(help, &arg);
+            normal.push(arg);
+        }
+
+        if value_follows {
+            match args.next() {
+                Some(value) => normal.push(value),
+                None => {
+                    let flag = normal.last().cloned().unwrap_or_default();
+                    return Err(MinigrepError::MissingArg(format!(
+                        "{flag} requires a value"
+                    )));
+                }
+            }
+        }
+    }
+    Ok(normal)
+}
+
+// the error for a flag that isn't one, suggesting the long flag it was
+// probably meant to be
+pub fn unknown_flag(flag: &str, help: &str) -> String {
+    let closest = help
+        .lines()
+        .flat_map(|line| flag_spec(line).split([',', ' ']))
+        .filter(|word| word.starts_with("--"))
+        .map(|known| (distance(flag, known), known))
+        .min();
+    match closest {
+        Some((distance, known)) if distance <= 2 => {
+            format!("unknown flag '{flag}', did you mean '{known}'?")
+        }
+        _ => format!("unknown flag '{flag}'"),
+    }
+}
+
+// the Levenshtein distance between the two
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, &b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HELP: &str = "\
+Options:
+  -e, --regexp <pattern>     search for <pattern>
+  -i, --ignore-case          ignore case
+  -n, --line-number          prefix each matching line with its line number
+  -d, --max-depth <num>      descend at most <num> directories below each path
+  -A <num>                   print <num> lines of context after each match";
+
+    fn normalize(args: &[&str]) -> Result<Vec<String>, String> {
+        super::normalize(args.iter().map(|arg| arg.to_string()), HELP)
+            .map_err(|err| err.to_string())
+    }
+
+    #[test]
+    fn flags_are_split_one_per_argument() {
+        assert_eq!(
+            Ok(["-i", "-n", "-A", "3", "--max-depth", "2", "to", "-"]
+                .map(String::from)
+                .to_vec()),
+            normalize(&["-inA3", "--max-depth=2", "to", "-"])
+        );
+        // values are left alone, even ones that look like flags
+        assert_eq!(
+            Ok(["-e", "-in", "-n", "--", "-in"].map(String::from).to_vec()),
+            normalize(&["-e", "-in", "-n", "--", "-in"])
+        );
+        assert_eq!(
+            Ok(["-e", "=x"].map(String::from).to_vec()),
+            normalize(&["-e=x"])
+        );
+        assert_eq!(
+            Err("--ignore-case doesn't take a value".to_string()),
+            normalize(&["--ignore-case=yes"])
+        );
+        assert_eq!(
+            Err("-A requires a value".to_string()),
+            normalize(&["-in", "-A"])
+        );
+    }
+
+    #[test]
+    fn unknown_flags_suggest_the_closest_one() {
+        assert_eq!(
+            "unknown flag '--ignore-cse', did you mean '--ignore-case'?",
+            unknown_flag("--ignore-cse", HELP)
+        );
+        assert_eq!(
+            "unknown flag '--frobnicate'",
+            unknown_flag("--frobnicate", HELP)
+        );
+    }
+}
@@ -0,0 +1,161 @@
+use std::fmt;
+
+// Style is how one kind of output is colored - the parts of an ANSI escape
+// sequence, written before the text (with a reset after it)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Style {
+    // the SGR codes for the colors, like "31" or "38;5;208"
+    fg: Option<String>,
+    bg: Option<String>,
+    bold: bool,
+    underline: bool,
+    // the bright variant of a named foreground color
+    intense: bool,
+}
+
+impl Style {
+    pub fn is_plain(&self) -> bool {
+        *self == Style::default()
+    }
+
+    fn set(&mut self, attribute: &str, value: &str) -> Result<(), String> {
+        match attribute {
+            "fg" => self.fg = Some(color_code(value, 30)?),
+            "bg" => self.bg = Some(color_code(value, 40)?),
+            "style" => match value {
+                "bold" => self.bold = true,
+                "nobold" => self.bold = false,
+                "underline" => self.underline = true,
+                "nounderline" => self.underline = false,
+                "intense" => self.intense = true,
+                "nointense" => self.intense = false,
+                _ => return Err(format!("unknown style '{value}'")),
+            },
+            _ => return Err(format!("expected fg, bg or style, got '{attribute}'")),
+        }
+        Ok(())
+    }
+}
+
+// the escape sequence that starts the style (nothing for a plain one)
+impl fmt::Display for Style {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut codes = Vec::new();
+        if self.bold {
+            codes.push("1".to_string());
+        }
+        if self.underline {
+            codes.push("4".to_string());
+        }
+        if let Some(fg) = &self.fg {
+            // 30-37 become 90-97
+            match fg.parse::<u8>() {
+                Ok(code) if self.intense => codes.push((code + 60).to_string()),
+                _ => codes.push(fg.clone()),
+            }
+        }
+        if let Some(bg) = &self.bg {
+            codes.push(bg.clone());
+        }
+        if codes.is_empty() {
+            return Ok(());
+        }
+        write!(f, "\x1b[{}m", codes.join(";"))
+    }
+}
+
+pub const RESET: &str = "\x1b[0m";
+
+// Colors are the styles of each part of a printed line, set with
+// --colors type:attribute:value (or type:none to turn one off)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Colors {
+    pub path: Style,
+    pub line: Style,
+    pub column: Style,
+    pub matched: Style,
+}
+
+// only the matched text stands out, in bold red
+impl Default for Colors {
+    fn default() -> Colors {
+        Colors {
+            path: Style::default(),
+            line: Style::default(),
+            column: Style::default(),
+            matched: Style {
+                fg: Some("31".to_string()),
+                bold: true,
+                ..Style::default()
+            },
+        }
+    }
+}
+
+impl Colors {
+    // change one style, from a spec like "match:fg:yellow" or "path:none"
+    pub fn apply(&mut self, spec: &str) -> Result<(), String> {
+        let mut parts = spec.splitn(3, ':');
+        let style = match parts.next().unwrap_or_default() {
+            "path" => &mut self.path,
+            "line" => &mut self.line,
+            "column" => &mut self.column,
+            "match" => &mut self.matched,
+            other => {
+                return Err(format!(
+                    "expected path, line, column or match, got '{other}'"
+                ))
+            }
+        };
+        match (parts.next(), parts.next()) {
+            (Some("none"), None) => *style = Style::default(),
+            (Some(attribute), Some(value)) => style.set(attribute, value)?,
+            _ => return Err(format!("expected type:attribute:value, got '{spec}'")),
+        }
+        Ok(())
+    }
+}
+
+// a color name, or a number from 0 to 255 for the 256 color palette
+// `base` is 30 for foreground colors and 40 for background ones
+fn color_code(value: &str, base: u8) -> Result<String, String> {
+    const NAMES: [&str; 8] = [
+        "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+    ];
+    if let Some(index) = NAMES.iter().position(|&name| name == value) {
+        return Ok((base + index as u8).to_string());
+    }
+    match value.parse::<u8>() {
+        Ok(number) => Ok(format!("{};5;{number}", base + 8)),
+        Err(_) => Err(format!("unknown color '{value}'")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn specs_change_the_escape_sequences() {
+        let mut colors = Colors::default();
+        assert_eq!("\x1b[1;31m", colors.matched.to_string());
+        assert!(colors.path.is_plain());
+
+        colors.apply("match:fg:yellow").unwrap();
+        colors.apply("match:style:nobold").unwrap();
+        colors.apply("match:bg:20").unwrap();
+        colors.apply("path:fg:blue").unwrap();
+        colors.apply("path:style:intense").unwrap();
+        colors.apply("line:style:underline").unwrap();
+        assert_eq!("\x1b[33;48;5;20m", colors.matched.to_string());
+        assert_eq!("\x1b[94m", colors.path.to_string());
+        assert_eq!("\x1b[4m", colors.line.to_string());
+
+        colors.apply("match:none").unwrap();
+        assert!(colors.matched.is_plain());
+
+        assert!(colors.apply("match:fg:mauve").is_err());
+        assert!(colors.apply("heading:fg:red").is_err());
+        assert!(colors.apply("match:fg").is_err());
+    }
+}
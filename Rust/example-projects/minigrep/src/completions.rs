@@ -0,0 +1,257 @@
+// Completion scripts for --generate-completions. The flags come from the
+// options help text, so a flag shows up in the completions once it's listed
+// there, with its description.
+
+// the shells there are scripts for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    PowerShell,
+}
+
+impl Shell {
+    pub fn from_name(name: &str) -> Option<Shell> {
+        match name.to_ascii_lowercase().as_str() {
+            "bash" => Some(Shell::Bash),
+            "zsh" => Some(Shell::Zsh),
+            "fish" => Some(Shell::Fish),
+            "powershell" | "pwsh" => Some(Shell::PowerShell),
+            _ => None,
+        }
+    }
+}
+
+// one line of the options help, like "-e, --regexp <pattern>  search for..."
+#[derive(Debug, PartialEq)]
+struct Flag<'a> {
+    short: Option<&'a str>,
+    long: Option<&'a str>,
+    // the name in angle brackets, for flags that take a value
+    value: Option<&'a str>,
+    help: &'a str,
+}
+
+impl Flag<'_> {
+    // both spellings, as typed
+    fn names(&self) -> impl Iterator<Item = String> + '_ {
+        let short = self.short.map(|short| format!("-{short}"));
+        let long = self.long.map(|long| format!("--{long}"));
+        short.into_iter().chain(long)
+    }
+
+    // the values a flag can take, for the ones with a fixed set
+    fn choices(&self) -> Option<&'static [&'static str]> {
+        match self.long? {
+            "color" => Some(&["auto", "always", "never"]),
+            "sort" | "sortr" => Some(&["path", "modified", "size", "none"]),
+            "format" => Some(&["text", "json", "csv", "tsv", "sarif"]),
+            "encoding" => Some(&["auto", "utf-8", "utf-16le", "utf-16be", "latin1"]),
+            _ => None,
+        }
+    }
+
+    fn takes_path(&self) -> bool {
+        self.value == Some("path")
+    }
+}
+
+// the flags listed in the help text - the lines that start with a dash
+fn flags(help: &str) -> Vec<Flag<'_>> {
+    let mut flags = Vec::new();
+    for line in help.lines() {
+        let line = line.trim_start();
+        if !line.starts_with('-') {
+            continue;
+        }
+        // two spaces separate the flags from their description
+        let (spec, help) = line.split_once("  ").unwrap_or((line, ""));
+        let mut flag = Flag {
+            short: None,
+            long: None,
+            value: None,
+            help: help.trim(),
+        };
+        for word in spec.split([',', ' ']).filter(|word| !word.is_empty()) {
+            if let Some(long) = word.strip_prefix("--") {
+                flag.long = Some(long);
+            } else if let Some(short) = word.strip_prefix('-') {
+                flag.short = Some(short);
+            } else {
+                flag.value = Some(word.trim_start_matches('<').trim_end_matches('>'));
+            }
+        }
+        flags.push(flag);
+    }
+    flags
+}
+
+// the completion script for minigrep's flags, as listed in `help`
+pub fn script(shell: Shell, help: &str) -> String {
+    let flags = flags(help);
+    match shell {
+        Shell::Bash => bash(&flags),
+        Shell::Zsh => zsh(&flags),
+        Shell::Fish => fish(&flags),
+        Shell::PowerShell => powershell(&flags),
+    }
+}
+
+// flags after a dash, the fixed values after the flags that have them, and
+// bash's own file name completion everywhere else
+fn bash(flags: &[Flag]) -> String {
+    let names: Vec<String> = flags.iter().flat_map(Flag::names).collect();
+    let mut script = String::from(
+        "_minigrep() {\n    local cur=\"${COMP_WORDS[COMP_CWORD]}\"\n    local prev=\"${COMP_WORDS[COMP_CWORD-1]}\"\n    case \"$prev\" in\n",
+    );
+    for flag in flags {
+        if let Some(choices) = flag.choices() {
+            let names: Vec<String> = flag.names().collect();
+            script.push_str(&format!(
+                "        {})\n            COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))\n            return\n            ;;\n",
+                names.join("|"),
+                choices.join(" ")
+            ));
+        }
+    }
+    script.push_str(&format!(
+        "    esac\n    if [[ \"$cur\" == -* ]]; then\n        COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))\n    fi\n}}\ncomplete -o bashdefault -o default -F _minigrep minigrep\n",
+        names.join(" ")
+    ));
+    script
+}
+
+fn zsh(flags: &[Flag]) -> String {
+    let mut script = String::from("#compdef minigrep\n\n_arguments -s -S \\\n");
+    for flag in flags {
+        let names: Vec<String> = flag.names().collect();
+        let names = match names.as_slice() {
+            [name] => name.clone(),
+            _ => format!("'({})'{{{}}}", names.join(" "), names.join(",")),
+        };
+        // [ and ] would end the description early
+        let help = flag
+            .help
+            .replace('\'', "'\\''")
+            .replace('[', "\\[")
+            .replace(']', "\\]");
+        let value = match flag.value {
+            Some(value) => {
+                let action = match flag.choices() {
+                    Some(choices) => format!("({})", choices.join(" ")),
+                    None if flag.takes_path() => "_files".to_string(),
+                    None => " ".to_string(),
+                };
+                format!(":{value}:{action}")
+            }
+            None => String::new(),
+        };
+        script.push_str(&format!("  {names}'[{help}]{value}' \\\n"));
+    }
+    script.push_str("  '*:file:_files'\n");
+    script
+}
+
+fn fish(flags: &[Flag]) -> String {
+    let mut script = String::new();
+    for flag in flags {
+        script.push_str("complete -c minigrep");
+        if let Some(short) = flag.short {
+            // fish only has single letter short options
+            if short.len() == 1 {
+                script.push_str(&format!(" -s {short}"));
+            } else {
+                script.push_str(&format!(" -o {short}"));
+            }
+        }
+        if let Some(long) = flag.long {
+            script.push_str(&format!(" -l {long}"));
+        }
+        if flag.value.is_some() {
+            match flag.choices() {
+                Some(choices) => script.push_str(&format!(" -x -a '{}'", choices.join(" "))),
+                None if flag.takes_path() => script.push_str(" -r -F"),
+                None => script.push_str(" -x"),
+            }
+        }
+        let help = flag.help.replace('\\', "\\\\").replace('\'', "\\'");
+        script.push_str(&format!(" -d '{help}'\n"));
+    }
+    script
+}
+
+fn powershell(flags: &[Flag]) -> String {
+    let mut script = String::from(
+        "using namespace System.Management.Automation\n\nRegister-ArgumentCompleter -Native -CommandName minigrep -ScriptBlock {\n    param($wordToComplete, $commandAst, $cursorPosition)\n    $flags = @(\n",
+    );
+    let mut entries = Vec::new();
+    for flag in flags {
+        let help = flag.help.replace('\'', "''");
+        for name in flag.names() {
+            entries.push(format!(
+                "        [CompletionResult]::new('{name}', '{name}', [CompletionResultType]::ParameterName, '{help}')"
+            ));
+        }
+    }
+    script.push_str(&entries.join(",\n"));
+    script.push_str(
+        "\n    )\n    $flags | Where-Object { $_.CompletionText -like \"$wordToComplete*\" }\n}\n",
+    );
+    script
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HELP: &str = "\
+Options:
+  -e, --regexp <pattern>     search for <pattern>
+      --color <when>         highlight the matched text
+  -O, --output <path>        write the results to <path>
+  -A <num>                   print <num> lines of context after each match
+      --hidden               search hidden files (names starting with '.')
+Set environment variable IGNORE_CASE=1 to do case insesitive searching";
+
+    #[test]
+    fn flags_come_from_the_help_text() {
+        let flags = flags(HELP);
+        assert_eq!(5, flags.len());
+        assert_eq!(
+            Flag {
+                short: Some("e"),
+                long: Some("regexp"),
+                value: Some("pattern"),
+                help: "search for <pattern>",
+            },
+            flags[0]
+        );
+        assert_eq!(
+            (Some("A"), None, Some("num")),
+            (flags[3].short, flags[3].long, flags[3].value)
+        );
+    }
+
+    #[test]
+    fn scripts_list_every_flag() {
+        let bash = script(Shell::Bash, HELP);
+        assert!(bash.contains("\"-e --regexp --color -O --output -A --hidden\""));
+        assert!(bash.contains(
+            "        --color)\n            COMPREPLY=($(compgen -W \"auto always never\""
+        ));
+
+        let zsh = script(Shell::Zsh, HELP);
+        assert!(zsh.contains(
+            "  '(-O --output)'{-O,--output}'[write the results to <path>]:path:_files' \\\n"
+        ));
+        assert!(zsh
+            .contains("  --hidden'[search hidden files (names starting with '\\''.'\\'')]' \\\n"));
+
+        let fish = script(Shell::Fish, HELP);
+        assert!(fish.contains("complete -c minigrep -l color -x -a 'auto always never' -d 'highlight the matched text'\n"));
+
+        let powershell = script(Shell::PowerShell, HELP);
+        assert_eq!(7, powershell.matches("[CompletionResult]::new(").count());
+    }
+}
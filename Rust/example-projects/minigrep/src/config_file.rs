@@ -0,0 +1,303 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use crate::args::lists;
+use crate::log::debug;
+
+// The config file holds defaults for the flags, one per key: `hidden = true`
+// is --hidden, `max_depth = 3` is --max-depth 3, `glob = ["!target"]` is a
+// --glob for each string, and `heading = false` is --no-heading. They go in
+// front of the flags on the command line, so those win where the two disagree.
+//
+// It's read from $MINIGREP_CONFIG_PATH, or else
+// $XDG_CONFIG_HOME/minigrep/config.toml (~/.config by default). Only a subset
+// of TOML is understood: key = value lines with booleans, integers, strings and
+// arrays of those, and # comments.
+
+// the flags the config file's settings stand for (none without a file)
+pub fn flags() -> Result<Vec<String>, String> {
+    let Some((path, named)) = path() else {
+        return Ok(Vec::new());
+    };
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        // the default file is optional, one named by MINIGREP_CONFIG_PATH isn't
+        Err(err) if err.kind() == io::ErrorKind::NotFound && !named => {
+            debug!("{}: no config file", path.display());
+            return Ok(Vec::new());
+        }
+        Err(err) => return Err(format!("{}: {err}", path.display())),
+    };
+    let flags = settings_to_flags(&text, crate::OPTIONS_HELP)
+        .map_err(|err| format!("{}: {err}", path.display()))?;
+    debug!("{}: config file flags {flags:?}", path.display());
+    Ok(flags)
+}
+
+// where the config file is, and whether MINIGREP_CONFIG_PATH named it (an
+// empty MINIGREP_CONFIG_PATH means there's none)
+fn path() -> Option<(PathBuf, bool)> {
+    if let Some(path) = env::var_os("MINIGREP_CONFIG_PATH") {
+        return (!path.is_empty()).then(|| (PathBuf::from(path), true));
+    }
+    let config_dir = match env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some((config_dir.join("minigrep").join("config.toml"), false))
+}
+
+#[derive(Debug, PartialEq)]
+enum Value {
+    Bool(bool),
+    Integer(i64),
+    String(String),
+    Array(Vec<Value>),
+}
+
+// the flags the settings stand for, checked against the flags in `help`
+fn settings_to_flags(text: &str, help: &str) -> Result<Vec<String>, String> {
+    let mut flags = Vec::new();
+    let mut lines = text.lines().enumerate();
+    while let Some((index, line)) = lines.next() {
+        let line_number = index + 1;
+        let mut line = strip_comment(line).trim().to_string();
+        if line.is_empty() {
+            continue;
+        }
+        if line.starts_with('[') {
+            return Err(format!(
+                "line {line_number}: tables aren't supported, only key = value"
+            ));
+        }
+        // an array can carry on over several lines
+        while !balanced(&line) {
+            match lines.next() {
+                Some((_, more)) => {
+                    line.push(' ');
+                    line.push_str(strip_comment(more).trim());
+                }
+                None => return Err(format!("line {line_number}: unclosed array")),
+            }
+        }
+
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(format!("line {line_number}: expected key = value"));
+        };
+        let key = key.trim();
+        let value =
+            parse_value(value.trim()).map_err(|err| format!("line {line_number}: {err}"))?;
+        let flag = format!("--{}", key.replace('_', "-"));
+        if !lists(help, &flag) {
+            return Err(format!("line {line_number}: unknown setting '{key}'"));
+        }
+        push_flags(&mut flags, &flag, value, help)
+            .map_err(|err| format!("line {line_number}: {key}: {err}"))?;
+    }
+    Ok(flags)
+}
+
+fn push_flags(flags: &mut Vec<String>, flag: &str, value: Value, help: &str) -> Result<(), String> {
+    match value {
+        Value::Bool(true) => flags.push(flag.to_string()),
+        // false is the default, unless there's a --no- flag to turn it off
+        Value::Bool(false) => {
+            let negated = flag.replacen("--", "--no-", 1);
+            if lists(help, &negated) {
+                flags.push(negated);
+            }
+        }
+        Value::Integer(number) => flags.extend([flag.to_string(), number.to_string()]),
+        Value::String(text) => flags.extend([flag.to_string(), text]),
+        Value::Array(values) => {
+            for value in values {
+                if matches!(value, Value::Array(_)) {
+                    return Err("arrays can't hold arrays".to_string());
+                }
+                push_flags(flags, flag, value, help)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// the line without a # comment, leaving #s inside strings alone
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (index, c) in line.char_indices() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(open), _) if c == open && !escaped => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '#') => return &line[..index],
+            _ => {}
+        }
+        escaped = false;
+    }
+    line
+}
+
+// whether every [ outside a string has its ]
+fn balanced(line: &str) -> bool {
+    let mut depth = 0;
+    let mut quote = None;
+    let mut escaped = false;
+    for c in line.chars() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(open), _) if c == open && !escaped => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '[') => depth += 1,
+            (None, ']') => depth -= 1,
+            _ => {}
+        }
+        escaped = false;
+    }
+    depth <= 0
+}
+
+fn parse_value(text: &str) -> Result<Value, String> {
+    let (value, rest) = parse_next(text)?;
+    if !rest.trim().is_empty() {
+        return Err(format!("unexpected '{}' after the value", rest.trim()));
+    }
+    Ok(value)
+}
+
+// the value at the start of `text`, and what comes after it
+fn parse_next(text: &str) -> Result<(Value, &str), String> {
+    let text = text.trim_start();
+    if let Some(rest) = text.strip_prefix('"') {
+        let mut value = String::new();
+        let mut chars = rest.char_indices();
+        while let Some((index, c)) = chars.next() {
+            match c {
+                '"' => return Ok((Value::String(value), &rest[index + 1..])),
+                '\\' => match chars.next().map(|(_, c)| c) {
+                    Some('n') => value.push('\n'),
+                    Some('t') => value.push('\t'),
+                    Some('"') => value.push('"'),
+                    Some('\\') => value.push('\\'),
+                    Some(other) => return Err(format!("unknown escape '\\{other}'")),
+                    None => break,
+                },
+                _ => value.push(c),
+            }
+        }
+        return Err("unclosed string".to_string());
+    }
+    // literal strings have no escapes, which suits regular expressions
+    if let Some(rest) = text.strip_prefix('\'') {
+        return match rest.split_once('\'') {
+            Some((value, rest)) => Ok((Value::String(value.to_string()), rest)),
+            None => Err("unclosed string".to_string()),
+        };
+    }
+    if let Some(mut rest) = text.strip_prefix('[') {
+        let mut values = Vec::new();
+        loop {
+            rest = rest.trim_start();
+            if let Some(after) = rest.strip_prefix(']') {
+                return Ok((Value::Array(values), after));
+            }
+            let (value, after) = parse_next(rest)?;
+            values.push(value);
+            rest = after.trim_start();
+            // a trailing comma is allowed
+            if let Some(after) = rest.strip_prefix(',') {
+                rest = after;
+            } else if !rest.starts_with(']') {
+                return Err("expected ',' or ']' in the array".to_string());
+            }
+        }
+    }
+    let end = text
+        .find(|c: char| c == ',' || c == ']' || c.is_whitespace())
+        .unwrap_or(text.len());
+    let (word, rest) = text.split_at(end);
+    match word {
+        "true" => Ok((Value::Bool(true), rest)),
+        "false" => Ok((Value::Bool(false), rest)),
+        _ => match word.replace('_', "").parse() {
+            Ok(number) => Ok((Value::Integer(number), rest)),
+            Err(_) => Err(format!(
+                "expected a boolean, number, string or array, got '{word}'"
+            )),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HELP: &str = "\
+Options:
+  -S, --smart-case           ignore case unless the query has capitals
+      --heading              print each file name once above its lines
+      --no-heading           print the file name in front of every line
+  -d, --max-depth <num>      descend at most <num> directories below each path
+  -g, --glob <glob>          only search files matching <glob>
+      --colors <spec>        change a color";
+
+    #[test]
+    fn settings_become_flags() {
+        let text = "\
+# my defaults
+smart_case = true
+heading = false   # like grep
+max_depth = 3
+glob = [
+    '!target',   # build output
+    \"!*.min.js\",
+]
+colors = [\"match:fg:yellow\", 'path:style:bold']
+";
+        assert_eq!(
+            vec![
+                "--smart-case",
+                "--no-heading",
+                "--max-depth",
+                "3",
+                "--glob",
+                "!target",
+                "--glob",
+                "!*.min.js",
+                "--colors",
+                "match:fg:yellow",
+                "--colors",
+                "path:style:bold",
+            ],
+            settings_to_flags(text, HELP).unwrap()
+        );
+    }
+
+    #[test]
+    fn mistakes_say_which_line() {
+        let error = |text| settings_to_flags(text, HELP).unwrap_err();
+        assert_eq!(
+            "line 2: unknown setting 'colour'",
+            error("heading = true\ncolour = 'red'")
+        );
+        assert_eq!("line 1: unclosed string", error("glob = \"*.rs"));
+        assert_eq!("line 1: unclosed array", error("glob = ['*.rs',"));
+        assert_eq!(
+            "line 1: tables aren't supported, only key = value",
+            error("[search]")
+        );
+        assert_eq!(
+            "line 1: expected a boolean, number, string or array, got 'yes'",
+            error("heading = yes")
+        );
+    }
+}
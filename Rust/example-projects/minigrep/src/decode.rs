@@ -0,0 +1,290 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::process::{Command as Process, Stdio};
+
+use crate::decompress;
+
+// A Decoder turns a file that isn't text into text to search, like a PDF
+// through pdftotext. Each one says which files are its own, by their
+// extensions or the bytes they start with, and minigrep searches what it
+// produces (as bytes, to be decoded like any file's). The ones minigrep has:
+// - the pdf and sqlite features' decoders, always on when built in
+// - -z's decompressors, by extension
+// - --pre's command, the fallback for every file the others don't take
+// A new format goes in built_in, behind a feature of its own.
+pub trait Decoder: Send + Sync {
+    // what to call it in --debug output and warnings
+    fn name(&self) -> &str;
+
+    // the extensions of the files it decodes, like "pdf"
+    fn extensions(&self) -> &[&str] {
+        &[]
+    }
+
+    // what the files it decodes start with, like b"%PDF-"
+    fn magic(&self) -> &[&[u8]] {
+        &[]
+    }
+
+    // whether the file is one to decode, given up to MAGIC_LEN of its first
+    // bytes (or none, when no decoder has any magic to look for)
+    fn detects(&self, path: &Path, head: &[u8]) -> bool {
+        has_extension_or_magic(path, head, self.extensions(), self.magic())
+    }
+
+    fn decode(&self, path: &Path) -> io::Result<Vec<u8>>;
+}
+
+// as much of the start of a file as decoders can look at
+pub const MAGIC_LEN: usize = 64;
+
+fn has_extension_or_magic(path: &Path, head: &[u8], extensions: &[&str], magic: &[&[u8]]) -> bool {
+    let extension = path.extension().and_then(|extension| extension.to_str());
+    extension.is_some_and(|extension| {
+        extensions
+            .iter()
+            .any(|known| known.eq_ignore_ascii_case(extension))
+    }) || magic.iter().any(|magic| head.starts_with(magic))
+}
+
+// a decoder that runs a command on the file and searches what it prints -
+// unless it's given extensions or magic, for every file
+pub struct Command {
+    // the program and the arguments that go before the file's path
+    command: Vec<String>,
+    // the ones that go after it
+    after: Vec<String>,
+    extensions: &'static [&'static str],
+    magic: &'static [&'static [u8]],
+}
+
+impl Command {
+    pub fn new(command: &[&str]) -> Command {
+        Command {
+            command: command.iter().map(|arg| arg.to_string()).collect(),
+            after: Vec::new(),
+            extensions: &[],
+            magic: &[],
+        }
+    }
+
+    pub fn after(mut self, args: &[&str]) -> Command {
+        self.after = args.iter().map(|arg| arg.to_string()).collect();
+        self
+    }
+
+    pub fn detect(
+        mut self,
+        extensions: &'static [&'static str],
+        magic: &'static [&'static [u8]],
+    ) -> Command {
+        self.extensions = extensions;
+        self.magic = magic;
+        self
+    }
+}
+
+impl Decoder for Command {
+    fn name(&self) -> &str {
+        &self.command[0]
+    }
+
+    fn extensions(&self) -> &[&str] {
+        self.extensions
+    }
+
+    fn magic(&self) -> &[&[u8]] {
+        self.magic
+    }
+
+    fn detects(&self, path: &Path, head: &[u8]) -> bool {
+        // with nothing to go by, it's for every file
+        self.extensions.is_empty() && self.magic.is_empty()
+            || has_extension_or_magic(path, head, self.extensions, self.magic)
+    }
+
+    // the command's output, read straight from its stdout
+    fn decode(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let program = &self.command[0];
+        let mut child = Process::new(program)
+            .args(&self.command[1..])
+            .arg(path)
+            .args(&self.after)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|err| io::Error::new(err.kind(), format!("unable to run {program}: {err}")))?;
+
+        let mut contents = Vec::new();
+        if let Some(mut stdout) = child.stdout.take() {
+            stdout.read_to_end(&mut contents)?;
+        }
+        let mut errors = String::new();
+        if let Some(mut stderr) = child.stderr.take() {
+            stderr.read_to_string(&mut errors)?;
+        }
+
+        if !child.wait()?.success() {
+            return Err(io::Error::other(format!(
+                "{program} failed: {}",
+                errors.trim()
+            )));
+        }
+        Ok(contents)
+    }
+}
+
+// the decoders to try on each file, first to last
+#[derive(Default)]
+pub struct Decoders {
+    decoders: Vec<Box<dyn Decoder>>,
+}
+
+impl Decoders {
+    // the ones minigrep was built with, then with -z the decompressors, and
+    // last --pre's command - which takes any file they don't, so it has to
+    // come after them
+    pub fn new(pre: Option<&str>, search_zip: bool) -> Decoders {
+        let mut decoders = Decoders::default();
+        for decoder in built_in() {
+            decoders.push(decoder);
+        }
+        if search_zip {
+            for decoder in decompress::decoders() {
+                decoders.push(decoder);
+            }
+        }
+        if let Some(pre) = pre {
+            decoders.push(Command::new(&[pre]));
+        }
+        decoders
+    }
+
+    pub fn push(&mut self, decoder: impl Decoder + 'static) {
+        self.decoders.push(Box::new(decoder));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.decoders.is_empty()
+    }
+
+    // the first decoder that takes the file, reading its start if any of
+    // them goes by magic - a file that can't be read has none, and fails
+    // when it's read for searching instead
+    pub fn find(&self, path: &Path) -> Option<&dyn Decoder> {
+        if self.decoders.is_empty() {
+            return None;
+        }
+        let mut head = Vec::new();
+        if self
+            .decoders
+            .iter()
+            .any(|decoder| !decoder.magic().is_empty())
+        {
+            if let Ok(file) = File::open(path) {
+                let _ = file.take(MAGIC_LEN as u64).read_to_end(&mut head);
+            }
+        }
+        self.decoders
+            .iter()
+            .find(|decoder| decoder.detects(path, &head))
+            .map(|decoder| decoder.as_ref())
+    }
+}
+
+// the decoders for formats that features turn on
+fn built_in() -> Vec<Command> {
+    vec![
+        #[cfg(feature = "pdf")]
+        Command::new(&["pdftotext", "-q", "-layout"])
+            .after(&["-"])
+            .detect(&["pdf"], &[b"%PDF-"]),
+        #[cfg(feature = "sqlite")]
+        Command::new(&["sqlite3", "-readonly"])
+            .after(&[".dump"])
+            .detect(&[], &[b"SQLite format 3\0"]),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TempDir;
+    use std::fs;
+
+    #[test]
+    fn decoders_are_found_by_extension_or_magic() {
+        let mut decoders = Decoders::default();
+        decoders.push(Command::new(&["pdftotext"]).detect(&["pdf"], &[b"%PDF-"]));
+        decoders.push(Command::new(&["gzip", "-d", "-c"]).detect(&["gz"], &[]));
+
+        let found = |decoders: &Decoders, path: &str| {
+            decoders
+                .find(Path::new(path))
+                .map(|decoder| decoder.name().to_string())
+        };
+        assert_eq!(
+            Some("pdftotext".to_string()),
+            found(&decoders, "report.PDF")
+        );
+        assert_eq!(
+            Some("gzip".to_string()),
+            found(&decoders, "logs/app.log.gz")
+        );
+        assert_eq!(None, found(&decoders, "poem.txt"));
+
+        let pdf = &decoders.decoders[0];
+        assert!(pdf.detects(Path::new("report"), b"%PDF-1.7\n"));
+        assert!(!pdf.detects(Path::new("report"), b"I'm nobody!"));
+
+        // with a fallback for every other file
+        decoders.push(Command::new(&["pre"]));
+        assert_eq!(Some("pre".to_string()), found(&decoders, "poem.txt"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn commands_are_run_on_the_file() {
+        let poem = Command::new(&["cat"])
+            .decode(Path::new("poem.txt"))
+            .unwrap();
+        assert!(poem.starts_with(b"I'm nobody! Who are you?"));
+
+        // with arguments after the path
+        let echoed = Command::new(&["sh", "-c", "echo $0 $1"])
+            .after(&["after"])
+            .decode(Path::new("poem.txt"))
+            .unwrap();
+        assert_eq!(b"poem.txt after\n", echoed.as_slice());
+
+        assert!(Command::new(&["false"])
+            .decode(Path::new("poem.txt"))
+            .is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn pre_only_takes_what_the_decompressors_dont() {
+        let dir = TempDir::new("decode");
+        let log = dir.join("app.log");
+        fs::write(&log, "compressed\n").unwrap();
+        let gzipped = Process::new("gzip").arg(&log).status().unwrap();
+        assert!(gzipped.success());
+
+        let decoders = Decoders::new(Some("cat"), true);
+        let gz = decoders.find(&dir.join("app.log.gz")).unwrap();
+        assert_eq!("gzip", gz.name());
+        assert_eq!(
+            b"compressed\n",
+            gz.decode(&dir.join("app.log.gz")).unwrap().as_slice()
+        );
+        assert_eq!(
+            Some("cat"),
+            decoders
+                .find(Path::new("poem.txt"))
+                .map(|decoder| decoder.name())
+        );
+    }
+}
@@ -0,0 +1,38 @@
+use crate::decode::Command;
+
+// the command that decompresses each kind of file to stdout, by extension
+// the tools are run as separate processes (like ripgrep does) rather than
+// linking a decoder for every format
+const DECOMPRESSORS: &[(&str, &[&str])] = &[
+    ("gz", &["gzip", "-d", "-c"]),
+    ("tgz", &["gzip", "-d", "-c"]),
+    ("bz2", &["bzip2", "-d", "-c"]),
+    ("tbz2", &["bzip2", "-d", "-c"]),
+    ("xz", &["xz", "-d", "-c"]),
+    ("txz", &["xz", "-d", "-c"]),
+    ("lzma", &["xz", "--format=lzma", "-d", "-c"]),
+    ("zst", &["zstd", "-q", "-d", "-c"]),
+];
+
+// a decoder for each kind of compressed file, for -z
+pub fn decoders() -> impl Iterator<Item = Command> {
+    DECOMPRESSORS.iter().map(|(extension, command)| {
+        Command::new(command).detect(std::slice::from_ref(extension), &[])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::decode::Decoders;
+    use std::path::Path;
+
+    #[test]
+    fn picks_the_command_by_extension() {
+        let decoders = Decoders::new(None, true);
+        let command_for = |path: &str| decoders.find(Path::new(path)).map(|c| c.name());
+        assert_eq!(Some("gzip"), command_for("logs/app.log.gz"));
+        assert_eq!(Some("xz"), command_for("dump.XZ"));
+        assert_eq!(None, command_for("poem.txt"));
+        assert_eq!(None, command_for("gz"));
+    }
+}
@@ -0,0 +1,301 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::Matcher;
+
+// apply the replacement template to every match in the contents
+// returns the new contents and how many substitutions were made
+// line endings (\n or \r\n) are kept exactly as they were
+pub fn replace_contents(matcher: &Matcher, contents: &str, template: &str) -> (String, usize) {
+    let mut replaced = String::with_capacity(contents.len());
+    let mut count = 0;
+
+    for line in contents.split_inclusive('\n') {
+        let text = line.strip_suffix('\n').unwrap_or(line);
+        let text = text.strip_suffix('\r').unwrap_or(text);
+        let ending = &line[text.len()..];
+
+        let spans: Vec<_> = matcher
+            .find_spans(text)
+            .into_iter()
+            .filter(|span| !span.is_empty())
+            .collect();
+        if spans.is_empty() {
+            replaced.push_str(line);
+            continue;
+        }
+
+        count += spans.len();
+        let (new_text, _) = matcher.replace(text, &spans, template);
+        replaced.push_str(&new_text);
+        replaced.push_str(ending);
+    }
+
+    (replaced, count)
+}
+
+// what to do with one proposed change in interactive mode
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Answer {
+    // make this change
+    Yes,
+    // skip this change
+    No,
+    // make this change and every one after it without asking
+    All,
+    // skip this change and stop asking - changes already accepted are kept
+    Quit,
+}
+
+impl Answer {
+    pub fn parse(input: &str) -> Option<Answer> {
+        match input.trim() {
+            "y" | "Y" => Some(Answer::Yes),
+            "n" | "N" => Some(Answer::No),
+            "a" | "A" => Some(Answer::All),
+            "q" | "Q" => Some(Answer::Quit),
+            _ => None,
+        }
+    }
+}
+
+// a changed line, shown to the user before asking
+pub struct Change<'a> {
+    pub line_number: usize,
+    pub old: &'a str,
+    pub new: &'a str,
+}
+
+// like replace_contents, but `ask` decides about each changed line
+// returns the new contents, the number of substitutions made and the last
+// answer given (All or Quit carry on to the next file)
+pub fn replace_interactive(
+    matcher: &Matcher,
+    contents: &str,
+    template: &str,
+    mut ask: impl FnMut(&Change) -> Answer,
+) -> (String, usize, Option<Answer>) {
+    let mut replaced = String::with_capacity(contents.len());
+    let mut count = 0;
+    let mut last_answer = None;
+
+    for (index, line) in contents.split_inclusive('\n').enumerate() {
+        let text = line.strip_suffix('\n').unwrap_or(line);
+        let text = text.strip_suffix('\r').unwrap_or(text);
+        let ending = &line[text.len()..];
+
+        let spans: Vec<_> = matcher
+            .find_spans(text)
+            .into_iter()
+            .filter(|span| !span.is_empty())
+            .collect();
+        if spans.is_empty() || last_answer == Some(Answer::Quit) {
+            replaced.push_str(line);
+            continue;
+        }
+
+        let (new_text, _) = matcher.replace(text, &spans, template);
+        let answer = match last_answer {
+            Some(Answer::All) => Answer::All,
+            _ => ask(&Change {
+                line_number: index + 1,
+                old: text,
+                new: &new_text,
+            }),
+        };
+        last_answer = Some(answer);
+
+        if answer == Answer::Yes || answer == Answer::All {
+            count += spans.len();
+            replaced.push_str(&new_text);
+            replaced.push_str(ending);
+        } else {
+            replaced.push_str(line);
+        }
+    }
+
+    (replaced, count, last_answer)
+}
+
+// lines of unchanged context around each change in a diff
+const DIFF_CONTEXT: usize = 3;
+
+// a unified diff between the original and replaced contents
+// replacements are made line by line, so line i of one is line i of the other
+// (unless the replacement text itself has newlines - then the whole file is one hunk)
+pub fn unified_diff(path: &str, old: &str, new: &str) -> String {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    if old_lines.len() != new_lines.len() {
+        let mut diff = format!(
+            "--- a/{path}\n+++ b/{path}\n@@ -1,{} +1,{} @@\n",
+            old_lines.len(),
+            new_lines.len()
+        );
+        for line in &old_lines {
+            diff.push_str(&format!("-{line}\n"));
+        }
+        for line in &new_lines {
+            diff.push_str(&format!("+{line}\n"));
+        }
+        return diff;
+    }
+    let changed: Vec<usize> = (0..old_lines.len())
+        .filter(|&i| old_lines[i] != new_lines[i])
+        .collect();
+
+    let mut diff = String::new();
+    if changed.is_empty() {
+        return diff;
+    }
+    diff.push_str(&format!("--- a/{path}\n+++ b/{path}\n"));
+
+    // merge changes whose context windows touch into one hunk
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for &i in &changed {
+        let start = i.saturating_sub(DIFF_CONTEXT);
+        let end = (i + DIFF_CONTEXT + 1).min(old_lines.len());
+        match hunks.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    for (start, end) in hunks {
+        let len = end - start;
+        diff.push_str(&format!(
+            "@@ -{},{len} +{},{len} @@\n",
+            start + 1,
+            start + 1
+        ));
+
+        let mut i = start;
+        while i < end {
+            if old_lines[i] == new_lines[i] {
+                diff.push_str(&format!(" {}\n", old_lines[i]));
+                i += 1;
+                continue;
+            }
+            // a run of changed lines: all the removals, then all the additions
+            let run_end = (i..end)
+                .find(|&j| old_lines[j] == new_lines[j])
+                .unwrap_or(end);
+            for line in &old_lines[i..run_end] {
+                diff.push_str(&format!("-{line}\n"));
+            }
+            for line in &new_lines[i..run_end] {
+                diff.push_str(&format!("+{line}\n"));
+            }
+            i = run_end;
+        }
+    }
+
+    diff
+}
+
+// write the file by writing a temp file next to it and renaming it over the
+// original - a crash part way through never leaves a half written file
+pub fn write_atomic(path: &Path, contents: &str) -> io::Result<()> {
+    let file_name = match path.file_name() {
+        Some(name) => name.to_string_lossy(),
+        None => return Err(io::Error::new(io::ErrorKind::InvalidInput, "not a file")),
+    };
+    let temp_path = path.with_file_name(format!(".{file_name}.minigrep-tmp"));
+
+    let result = (|| {
+        let mut temp = fs::File::create(&temp_path)?;
+        temp.write_all(contents.as_bytes())?;
+        temp.sync_all()?;
+        // keep the original file's permissions
+        fs::set_permissions(&temp_path, fs::metadata(path)?.permissions())?;
+        fs::rename(&temp_path, path)
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TempDir;
+
+    #[test]
+    fn replace_keeps_line_endings() {
+        let matcher = Matcher::new("(o)ld", false, true).unwrap();
+        let contents = "old\r\nkeep\nold and old\n";
+
+        assert_eq!(
+            ("new\r\nkeep\nnew and new\n".to_string(), 3),
+            replace_contents(&matcher, contents, "new")
+        );
+    }
+
+    #[test]
+    fn diff_groups_changes_into_hunks() {
+        let old = "1\n2\nold\n4\n5\n6\n7\n8\n9\n10\nold\n";
+        let new = "1\n2\nnew\n4\n5\n6\n7\n8\n9\n10\nnew\n";
+
+        let expected = "\
+--- a/f.txt
++++ b/f.txt
+@@ -1,6 +1,6 @@
+ 1
+ 2
+-old
++new
+ 4
+ 5
+ 6
+@@ -8,4 +8,4 @@
+ 8
+ 9
+ 10
+-old
++new
+";
+        assert_eq!(expected, unified_diff("f.txt", old, new));
+    }
+
+    #[test]
+    fn interactive_applies_only_accepted_changes() {
+        let matcher = Matcher::new("old", false, false).unwrap();
+        let contents = "old 1\nold 2\nold 3\nold 4\n";
+        let mut answers = vec![Answer::No, Answer::Yes, Answer::Quit].into_iter();
+
+        let (replaced, count, last) =
+            replace_interactive(&matcher, contents, "new", |_| answers.next().unwrap());
+
+        assert_eq!("old 1\nnew 2\nold 3\nold 4\n", replaced);
+        assert_eq!(1, count);
+        assert_eq!(Some(Answer::Quit), last);
+    }
+
+    #[test]
+    fn interactive_all_stops_asking() {
+        let matcher = Matcher::new("old", false, false).unwrap();
+        let mut asked = 0;
+
+        let (replaced, count, _) = replace_interactive(&matcher, "old\nold\nold\n", "new", |_| {
+            asked += 1;
+            Answer::All
+        });
+
+        assert_eq!(("new\nnew\nnew\n", 3, 1), (replaced.as_str(), count, asked));
+    }
+
+    #[test]
+    fn write_atomic_replaces_contents() {
+        let dir = TempDir::new("edit");
+        let path = dir.join("poem.txt");
+        fs::write(&path, "before").unwrap();
+
+        write_atomic(&path, "after").unwrap();
+
+        assert_eq!("after", fs::read_to_string(&path).unwrap());
+        assert_eq!(1, fs::read_dir(&dir).unwrap().count());
+    }
+}
@@ -0,0 +1,99 @@
+// Text encodings other than UTF-8, so files like logs exported from Windows
+// tools (often UTF-16) can be searched - they're transcoded to UTF-8 first.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    // ISO-8859-1: every byte is the code point of the same value
+    Latin1,
+}
+
+impl Encoding {
+    // the encoding for a name given to --encoding
+    pub fn from_name(name: &str) -> Option<Encoding> {
+        match name.to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" => Some(Encoding::Utf8),
+            "utf-16le" | "utf16le" => Some(Encoding::Utf16Le),
+            "utf-16be" | "utf16be" => Some(Encoding::Utf16Be),
+            "latin1" | "latin-1" | "iso-8859-1" => Some(Encoding::Latin1),
+            _ => None,
+        }
+    }
+
+    // the byte order mark this encoding starts a file with, if it has one
+    fn bom(self) -> &'static [u8] {
+        match self {
+            Encoding::Utf8 => b"\xEF\xBB\xBF",
+            Encoding::Utf16Le => b"\xFF\xFE",
+            Encoding::Utf16Be => b"\xFE\xFF",
+            Encoding::Latin1 => b"",
+        }
+    }
+}
+
+// the encoding announced by a byte order mark at the start of the file
+pub fn sniff(bytes: &[u8]) -> Option<Encoding> {
+    [Encoding::Utf8, Encoding::Utf16Le, Encoding::Utf16Be]
+        .into_iter()
+        .find(|encoding| bytes.starts_with(encoding.bom()))
+}
+
+// transcode to UTF-8, dropping the byte order mark
+// anything that can't be decoded becomes U+FFFD rather than an error
+pub fn decode(bytes: &[u8], encoding: Encoding) -> String {
+    let bytes = bytes.strip_prefix(encoding.bom()).unwrap_or(bytes);
+    match encoding {
+        Encoding::Utf8 => String::from_utf8_lossy(bytes).into_owned(),
+        Encoding::Utf16Le => decode_utf16(bytes, u16::from_le_bytes),
+        Encoding::Utf16Be => decode_utf16(bytes, u16::from_be_bytes),
+        Encoding::Latin1 => bytes.iter().map(|&byte| char::from(byte)).collect(),
+    }
+}
+
+fn decode_utf16(bytes: &[u8], to_unit: fn([u8; 2]) -> u16) -> String {
+    let units = bytes
+        .chunks_exact(2)
+        .map(|pair| to_unit([pair[0], pair[1]]));
+    let mut text: String = char::decode_utf16(units)
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect();
+    // an odd byte left over at the end
+    if bytes.len() % 2 == 1 {
+        text.push(char::REPLACEMENT_CHARACTER);
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sniffs_byte_order_marks() {
+        assert_eq!(Some(Encoding::Utf16Le), sniff(b"\xFF\xFEa\x00"));
+        assert_eq!(Some(Encoding::Utf16Be), sniff(b"\xFE\xFF\x00a"));
+        assert_eq!(Some(Encoding::Utf8), sniff(b"\xEF\xBB\xBFabc"));
+        assert_eq!(None, sniff(b"abc"));
+    }
+
+    #[test]
+    fn decodes_utf16_and_latin1() {
+        // "frog é" in UTF-16LE, after the BOM
+        let utf16 = b"\xFF\xFEf\x00r\x00o\x00g\x00 \x00\xE9\x00";
+        assert_eq!("frog é", decode(utf16, Encoding::Utf16Le));
+        assert_eq!(
+            "frog é",
+            decode(b"\x00f\x00r\x00o\x00g\x00 \x00\xE9", Encoding::Utf16Be)
+        );
+        assert_eq!("café", decode(b"caf\xE9", Encoding::Latin1));
+    }
+
+    #[test]
+    fn names_are_case_insensitive() {
+        assert_eq!(Some(Encoding::Utf16Le), Encoding::from_name("UTF-16LE"));
+        assert_eq!(Some(Encoding::Latin1), Encoding::from_name("iso-8859-1"));
+        assert_eq!(None, Encoding::from_name("ebcdic"));
+    }
+}
@@ -0,0 +1,131 @@
+use std::ffi::OsString;
+
+use crate::args::{lists, value_name};
+
+// MINIGREP_<FLAG> environment variables are defaults for the flags, the same
+// way the config file's settings are: MINIGREP_HIDDEN=1 is --hidden,
+// MINIGREP_MAX_DEPTH=3 is --max-depth 3 and MINIGREP_HEADING=no is
+// --no-heading. MINIGREP_CONFIG_PATH says where the config file is instead.
+// IGNORE_CASE, set to anything, is still --ignore-case as it always was.
+
+const PREFIX: &str = "MINIGREP_";
+
+// the flags the variables stand for, checked against the flags in `help`
+// - in order of name, so they come out the same whichever way they were set
+pub fn flags(
+    vars: impl Iterator<Item = (OsString, OsString)>,
+    help: &str,
+) -> Result<Vec<String>, String> {
+    let mut vars: Vec<(String, OsString)> = vars
+        .filter_map(|(name, value)| Some((name.into_string().ok()?, value)))
+        .filter(|(name, _)| {
+            name == "IGNORE_CASE" || name.starts_with(PREFIX) && name != "MINIGREP_CONFIG_PATH"
+        })
+        .collect();
+    vars.sort();
+
+    let mut flags = Vec::new();
+    for (name, value) in vars {
+        if name == "IGNORE_CASE" {
+            flags.push("--ignore-case".to_string());
+            continue;
+        }
+        let value = value
+            .into_string()
+            .map_err(|_| format!("{name}: not valid UTF-8"))?;
+        let flag = format!(
+            "--{}",
+            name[PREFIX.len()..].to_ascii_lowercase().replace('_', "-")
+        );
+        if !lists(help, &flag) {
+            return Err(format!("{name}: there's no {flag} flag to set"));
+        }
+        let Some(value_name) = value_name(help, &flag) else {
+            if parse_bool(&value).map_err(|err| format!("{name}: {err}"))? {
+                flags.push(flag);
+            } else {
+                let negated = flag.replacen("--", "--no-", 1);
+                if lists(help, &negated) {
+                    flags.push(negated);
+                }
+            }
+            continue;
+        };
+        if value.is_empty() {
+            return Err(format!("{name}: expected a value for {flag}"));
+        }
+        if value_name == "num" && value.parse::<usize>().is_err() {
+            return Err(format!("{name}: expected a number, got '{value}'"));
+        }
+        flags.extend([flag, value]);
+    }
+    Ok(flags)
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" | "" => Ok(false),
+        _ => Err(format!(
+            "expected 1, true, yes or on (or 0, false, no or off), got '{value}'"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HELP: &str = "\
+Options:
+  -i, --ignore-case          ignore case
+      --heading              print each file name once above its lines
+      --no-heading           print the file name in front of every line
+  -d, --max-depth <num>      descend at most <num> directories below each path";
+
+    fn vars(vars: &[(&str, &str)]) -> impl Iterator<Item = (OsString, OsString)> {
+        vars.iter()
+            .map(|(name, value)| (OsString::from(name), OsString::from(value)))
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    #[test]
+    fn variables_become_flags() {
+        let flags = flags(
+            vars(&[
+                ("PATH", "/usr/bin"),
+                ("MINIGREP_MAX_DEPTH", "3"),
+                ("MINIGREP_IGNORE_CASE", "yes"),
+                ("MINIGREP_HEADING", "0"),
+                ("MINIGREP_CONFIG_PATH", "/etc/minigrep.toml"),
+            ]),
+            HELP,
+        );
+        assert_eq!(
+            Ok(vec![
+                "--no-heading".to_string(),
+                "--ignore-case".to_string(),
+                "--max-depth".to_string(),
+                "3".to_string(),
+            ]),
+            flags
+        );
+    }
+
+    #[test]
+    fn mistakes_name_the_variable() {
+        assert_eq!(
+            Err("MINIGREP_COLOUR: there's no --colour flag to set".to_string()),
+            flags(vars(&[("MINIGREP_COLOUR", "never")]), HELP)
+        );
+        assert_eq!(
+            Err("MINIGREP_MAX_DEPTH: expected a number, got 'deep'".to_string()),
+            flags(vars(&[("MINIGREP_MAX_DEPTH", "deep")]), HELP)
+        );
+        assert_eq!(
+            Err("MINIGREP_HEADING: expected 1, true, yes or on (or 0, false, no or off), got 'maybe'".to_string()),
+            flags(vars(&[("MINIGREP_HEADING", "maybe")]), HELP)
+        );
+    }
+}
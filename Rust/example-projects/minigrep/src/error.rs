@@ -0,0 +1,83 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+// what went wrong - Config::build, with_defaults and run return these so
+// callers can tell a mistake on the command line from a file that couldn't
+// be read, and main can pick an exit code
+#[derive(Debug)]
+pub enum MinigrepError {
+    // no query, or a flag without its value
+    MissingArg(String),
+    // a flag that isn't one, a value it doesn't take, or flags that don't go together
+    InvalidArg(String),
+    // a mistake in the config file or a MINIGREP_ environment variable
+    Config(String),
+    // a pattern that isn't a valid regular expression
+    InvalidPattern(regex::Error),
+    // a file (or URL, or git revision) that couldn't be read or written
+    Io { path: String, source: io::Error },
+    // the results couldn't be written out
+    Output(io::Error),
+}
+
+impl MinigrepError {
+    pub fn io(path: &str, source: io::Error) -> MinigrepError {
+        MinigrepError::Io {
+            path: path.to_string(),
+            source,
+        }
+    }
+
+    // whether the command line (or the defaults put in front of it) was at fault
+    pub fn is_usage(&self) -> bool {
+        matches!(
+            self,
+            MinigrepError::MissingArg(_) | MinigrepError::InvalidArg(_) | MinigrepError::Config(_)
+        )
+    }
+}
+
+impl fmt::Display for MinigrepError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MinigrepError::MissingArg(message)
+            | MinigrepError::InvalidArg(message)
+            | MinigrepError::Config(message) => write!(f, "{message}"),
+            MinigrepError::InvalidPattern(err) => write!(f, "{err}"),
+            MinigrepError::Io { path, source } => write!(f, "{path}: {source}"),
+            MinigrepError::Output(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl Error for MinigrepError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MinigrepError::InvalidPattern(err) => Some(err),
+            MinigrepError::Io { source, .. } => Some(source),
+            MinigrepError::Output(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+// the parse_ helpers describe a bad value in a string
+impl From<String> for MinigrepError {
+    fn from(message: String) -> MinigrepError {
+        MinigrepError::InvalidArg(message)
+    }
+}
+
+impl From<regex::Error> for MinigrepError {
+    fn from(err: regex::Error) -> MinigrepError {
+        MinigrepError::InvalidPattern(err)
+    }
+}
+
+// an I/O error without a path to go with it is one writing the results
+impl From<io::Error> for MinigrepError {
+    fn from(err: io::Error) -> MinigrepError {
+        MinigrepError::Output(err)
+    }
+}
@@ -0,0 +1,180 @@
+use std::ffi::{c_char, c_int, CStr};
+use std::ptr;
+
+use crate::search::search_matches;
+use crate::Matcher;
+
+// The search for C and C++ (with the ffi feature), declared in
+// include/minigrep.h. Build the library to link against with
+//
+//     cargo rustc --release --lib --features ffi --crate-type staticlib
+//
+// (or cdylib). Each line found is given as where it is in the contents that
+// were searched, so nothing is copied and the caller's buffer is the only
+// text - what minigrep allocates is the array of them, which goes back through
+// minigrep_results_free.
+
+// ran fine, even if nothing matched
+pub const MINIGREP_OK: c_int = 0;
+// a pointer that can't be null was
+pub const MINIGREP_NULL: c_int = 1;
+// the query or the contents weren't UTF-8
+pub const MINIGREP_NOT_UTF8: c_int = 2;
+
+// a line that matched
+#[repr(C)]
+pub struct MinigrepLine {
+    // 1-based
+    pub line_number: usize,
+    // where the line starts in the contents, and how long it is (without its
+    // line break)
+    pub offset: usize,
+    pub len: usize,
+}
+
+#[repr(C)]
+pub struct MinigrepResults {
+    pub lines: *mut MinigrepLine,
+    pub len: usize,
+}
+
+/// Searches the NUL-terminated `contents` for lines containing the
+/// NUL-terminated `query`, case sensitively, filling in `out_results`.
+///
+/// # Safety
+///
+/// `query` and `contents` must be null or point to NUL-terminated strings,
+/// and `out_results` must be null or point to a `MinigrepResults` to write.
+/// When this returns `MINIGREP_OK` the results have to be given to
+/// `minigrep_results_free`; otherwise they're left empty.
+#[no_mangle]
+pub unsafe extern "C" fn minigrep_search(
+    query: *const c_char,
+    contents: *const c_char,
+    out_results: *mut MinigrepResults,
+) -> c_int {
+    if out_results.is_null() {
+        return MINIGREP_NULL;
+    }
+    *out_results = MinigrepResults {
+        lines: ptr::null_mut(),
+        len: 0,
+    };
+    if query.is_null() || contents.is_null() {
+        return MINIGREP_NULL;
+    }
+    let (Ok(query), Ok(contents)) = (
+        CStr::from_ptr(query).to_str(),
+        CStr::from_ptr(contents).to_str(),
+    ) else {
+        return MINIGREP_NOT_UTF8;
+    };
+
+    let matcher = Matcher::new(query, false, false).expect("a literal never fails to build");
+    let lines: Box<[MinigrepLine]> = search_matches(&matcher, contents)
+        .into_iter()
+        .map(|m| MinigrepLine {
+            line_number: m.line_number,
+            offset: m.byte_offset,
+            len: m.line.len(),
+        })
+        .collect();
+    let len = lines.len();
+    *out_results = MinigrepResults {
+        lines: Box::into_raw(lines).cast(),
+        len,
+    };
+    MINIGREP_OK
+}
+
+/// Frees the lines `minigrep_search` found, leaving `results` empty, so
+/// freeing them twice is harmless.
+///
+/// # Safety
+///
+/// `results` must be null or point to results filled in by
+/// `minigrep_search` (or emptied by this).
+#[no_mangle]
+pub unsafe extern "C" fn minigrep_results_free(results: *mut MinigrepResults) {
+    if results.is_null() || (*results).lines.is_null() {
+        return;
+    }
+    let lines = ptr::slice_from_raw_parts_mut((*results).lines, (*results).len);
+    drop(Box::from_raw(lines));
+    *results = MinigrepResults {
+        lines: ptr::null_mut(),
+        len: 0,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+
+    fn empty() -> MinigrepResults {
+        MinigrepResults {
+            lines: ptr::null_mut(),
+            len: 0,
+        }
+    }
+
+    #[test]
+    fn lines_are_found_where_they_are_in_the_contents() {
+        let query = CString::new("duct").unwrap();
+        let text = "Rust:\nsafe, fast, productive.\nPick three.\nDuct tape, productive.";
+        let contents = CString::new(text).unwrap();
+        let mut results = empty();
+
+        let status = unsafe { minigrep_search(query.as_ptr(), contents.as_ptr(), &mut results) };
+        assert_eq!(MINIGREP_OK, status);
+        let lines = unsafe { std::slice::from_raw_parts(results.lines, results.len) };
+        let found: Vec<_> = lines
+            .iter()
+            .map(|line| (line.line_number, &text[line.offset..line.offset + line.len]))
+            .collect();
+        assert_eq!(
+            vec![
+                (2, "safe, fast, productive."),
+                (4, "Duct tape, productive.")
+            ],
+            found
+        );
+
+        unsafe {
+            minigrep_results_free(&mut results);
+            minigrep_results_free(&mut results);
+        }
+        assert!(results.lines.is_null());
+        assert_eq!(0, results.len);
+    }
+
+    #[test]
+    fn bad_arguments_leave_the_results_empty() {
+        let query = CString::new("duct").unwrap();
+        let bad = CString::new(vec![b'a', 0xff]).unwrap();
+        let mut results = empty();
+
+        unsafe {
+            assert_eq!(
+                MINIGREP_NULL,
+                minigrep_search(ptr::null(), query.as_ptr(), &mut results)
+            );
+            assert_eq!(
+                MINIGREP_NOT_UTF8,
+                minigrep_search(query.as_ptr(), bad.as_ptr(), &mut results)
+            );
+            assert_eq!(
+                MINIGREP_NULL,
+                minigrep_search(query.as_ptr(), query.as_ptr(), ptr::null_mut())
+            );
+        }
+        assert!(results.lines.is_null());
+
+        // nothing matching is still a search that ran
+        let contents = CString::new("nothing here").unwrap();
+        let status = unsafe { minigrep_search(query.as_ptr(), contents.as_ptr(), &mut results) };
+        assert_eq!((MINIGREP_OK, 0), (status, results.len));
+        unsafe { minigrep_results_free(&mut results) };
+    }
+}
@@ -0,0 +1,672 @@
+use std::io::{self, Write};
+use std::time::Duration;
+
+use crate::{Line, Match};
+
+// Machine-readable output, picked with --format. Text is the grep-style output
+// the Printer writes - the other formats are written here, for tools that
+// would rather not parse that.
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Format {
+    #[default]
+    Text,
+    // one JSON document with the matches of every file
+    Json,
+    // a JSON message per line, the way rg --json writes them (--json)
+    JsonLines,
+    // a row per match, for spreadsheets and data frames
+    Csv,
+    Tsv,
+    // SARIF 2.1.0, with a result per match, for code scanning in CI
+    Sarif,
+}
+
+impl Format {
+    // the format for a name given to --format
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name.to_ascii_lowercase().as_str() {
+            "text" => Some(Format::Text),
+            "json" => Some(Format::Json),
+            "csv" => Some(Format::Csv),
+            "tsv" => Some(Format::Tsv),
+            "sarif" => Some(Format::Sarif),
+            _ => None,
+        }
+    }
+}
+
+// the results of one searched file, as a report needs them
+pub struct Searched<'a> {
+    pub path: &'a str,
+    pub contents: &'a str,
+    pub groups: &'a [Vec<Line<'a>>],
+    // what the lines end with ('\n', or NUL with --null-data)
+    pub terminator: char,
+    // how long searching the file took
+    pub elapsed: Duration,
+}
+
+impl Searched<'_> {
+    fn matches(&self) -> impl Iterator<Item = &Match<'_>> {
+        self.groups.iter().flatten().filter_map(|line| match line {
+            Line::Matched(m) => Some(m),
+            Line::Context(..) => None,
+        })
+    }
+}
+
+// Report writes the results in a format other than text, a file at a time as
+// the files are searched, so nothing is held back until the end
+pub enum Report {
+    Json(JsonDocument),
+    JsonLines(JsonLines),
+    Delimited(Delimited),
+    Sarif(Sarif),
+}
+
+impl Report {
+    // start a report - there's none for text
+    pub fn begin(format: Format, out: &mut impl Write) -> io::Result<Option<Report>> {
+        Ok(match format {
+            Format::Text => None,
+            Format::Json => Some(Report::Json(JsonDocument::begin(out)?)),
+            Format::JsonLines => Some(Report::JsonLines(JsonLines::default())),
+            Format::Csv => Some(Report::Delimited(Delimited::begin(out, ',')?)),
+            Format::Tsv => Some(Report::Delimited(Delimited::begin(out, '\t')?)),
+            Format::Sarif => Some(Report::Sarif(Sarif::begin(out)?)),
+        })
+    }
+
+    pub fn file(&mut self, out: &mut impl Write, searched: &Searched) -> io::Result<()> {
+        match self {
+            Report::Json(document) => document.file(out, searched),
+            Report::JsonLines(lines) => lines.file(out, searched),
+            Report::Delimited(rows) => rows.file(out, searched),
+            Report::Sarif(log) => log.file(out, searched),
+        }
+    }
+
+    // finish the report, given how long the whole search took
+    pub fn end(self, out: &mut impl Write, elapsed: Duration) -> io::Result<()> {
+        match self {
+            Report::Json(document) => document.end(out),
+            Report::JsonLines(lines) => lines.end(out, elapsed),
+            Report::Delimited(_) => Ok(()),
+            Report::Sarif(log) => log.end(out),
+        }
+    }
+}
+
+// JsonDocument writes {"files": [{"path": ..., "matches": [...]}, ...]}.
+// Each match is {"line_number", "column", "text", "spans"}, with the 1-based
+// (byte) column of the first match and the byte range of every match, and
+// only files with matches are listed - context lines are left out.
+pub struct JsonDocument {
+    files: usize,
+}
+
+impl JsonDocument {
+    fn begin(out: &mut impl Write) -> io::Result<JsonDocument> {
+        write!(out, "{{\"files\":[")?;
+        Ok(JsonDocument { files: 0 })
+    }
+
+    fn file(&mut self, out: &mut impl Write, searched: &Searched) -> io::Result<()> {
+        let matches: Vec<String> = searched
+            .matches()
+            .map(|m| {
+                let spans: Vec<String> = m
+                    .spans
+                    .iter()
+                    .map(|span| format!("{{\"start\":{},\"end\":{}}}", span.start, span.end))
+                    .collect();
+                format!(
+                    "{{\"line_number\":{},\"column\":{},\"text\":{},\"spans\":[{}]}}",
+                    m.line_number,
+                    m.spans.first().map_or(0, |span| span.start) + 1,
+                    json_string(m.line),
+                    spans.join(",")
+                )
+            })
+            .collect();
+        if matches.is_empty() {
+            return Ok(());
+        }
+        if self.files > 0 {
+            write!(out, ",")?;
+        }
+        self.files += 1;
+        write!(
+            out,
+            "{{\"path\":{},\"matches\":[{}]}}",
+            json_string(searched.path),
+            matches.join(",")
+        )
+    }
+
+    fn end(self, out: &mut impl Write) -> io::Result<()> {
+        writeln!(out, "]}}")
+    }
+}
+
+// Delimited writes CSV (or TSV): a path,line,column,match_text header, then a
+// row for every match - or for every line, when the lines have nothing
+// highlighted (with -v). CSV fields are quoted as RFC 4180 says; TSV can't
+// quote, so tabs, newlines and backslashes are escaped like \t instead.
+pub struct Delimited {
+    separator: char,
+}
+
+impl Delimited {
+    fn begin(out: &mut impl Write, separator: char) -> io::Result<Delimited> {
+        let rows = Delimited { separator };
+        rows.row(out, ["path", "line", "column", "match_text"])?;
+        Ok(rows)
+    }
+
+    fn file(&mut self, out: &mut impl Write, searched: &Searched) -> io::Result<()> {
+        for m in searched.matches() {
+            let line_number = m.line_number.to_string();
+            let spans: Vec<_> = m.spans.iter().filter(|span| !span.is_empty()).collect();
+            if spans.is_empty() {
+                self.row(out, [searched.path, &line_number, "1", m.line])?;
+            }
+            for span in spans {
+                let column = (span.start + 1).to_string();
+                let text = &m.line[span.clone()];
+                self.row(out, [searched.path, &line_number, &column, text])?;
+            }
+        }
+        Ok(())
+    }
+
+    fn row(&self, out: &mut impl Write, fields: [&str; 4]) -> io::Result<()> {
+        let fields: Vec<String> = fields
+            .into_iter()
+            .map(|field| match self.separator {
+                ',' => csv_field(field),
+                _ => tsv_field(field),
+            })
+            .collect();
+        writeln!(out, "{}", fields.join(&self.separator.to_string()))
+    }
+}
+
+// a CSV field, quoted when it has to be, with quotes doubled inside
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn tsv_field(field: &str) -> String {
+    field
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+// Sarif writes a SARIF 2.1.0 log with one run, whose results are the matches,
+// so CI systems can show them as code scanning annotations. Every result is for
+// the one "match" rule, and columns count characters (unicodeCodePoints), not
+// the UTF-16 units SARIF assumes otherwise.
+pub struct Sarif {
+    results: usize,
+}
+
+impl Sarif {
+    fn begin(out: &mut impl Write) -> io::Result<Sarif> {
+        write!(
+            out,
+            concat!(
+                r#"{{"$schema":"https://json.schemastore.org/sarif-2.1.0.json","version":"2.1.0","runs":[{{"#,
+                r#""tool":{{"driver":{{"name":"minigrep","version":"{}","rules":[{{"id":"match","#,
+                r#""shortDescription":{{"text":"A line matched the search pattern"}},"defaultConfiguration":{{"level":"warning"}}}}]}}}},"#,
+                r#""columnKind":"unicodeCodePoints","results":["#
+            ),
+            env!("CARGO_PKG_VERSION")
+        )?;
+        Ok(Sarif { results: 0 })
+    }
+
+    fn file(&mut self, out: &mut impl Write, searched: &Searched) -> io::Result<()> {
+        let uri = json_string(&file_uri(searched.path));
+        for m in searched.matches() {
+            let spans: Vec<_> = m.spans.iter().filter(|span| !span.is_empty()).collect();
+            // a line without spans (with -v) is a result of its own
+            let regions = if spans.is_empty() {
+                vec![(format!("\"startLine\":{}", m.line_number), m.line)]
+            } else {
+                spans
+                    .into_iter()
+                    .map(|span| {
+                        let start = m.line[..span.start].chars().count() + 1;
+                        let end = start + m.line[span.clone()].chars().count();
+                        let region = format!(
+                            "\"startLine\":{},\"startColumn\":{start},\"endColumn\":{end}",
+                            m.line_number
+                        );
+                        (region, &m.line[span.clone()])
+                    })
+                    .collect()
+            };
+
+            for (region, text) in regions {
+                if self.results > 0 {
+                    write!(out, ",")?;
+                }
+                self.results += 1;
+                write!(
+                    out,
+                    concat!(
+                        r#"{{"ruleId":"match","level":"warning","message":{{"text":{}}},"#,
+                        r#""locations":[{{"physicalLocation":{{"artifactLocation":{{"uri":{}}},"#,
+                        r#""region":{{{},"snippet":{{"text":{}}}}}}}}}]}}"#
+                    ),
+                    json_string(&format!("Matched: {text}")),
+                    uri,
+                    region,
+                    json_string(m.line)
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    fn end(self, out: &mut impl Write) -> io::Result<()> {
+        writeln!(out, "]}}]}}")
+    }
+}
+
+// a path as a URI reference - relative paths stay relative (to where minigrep
+// ran), absolute ones become file:// URIs
+fn file_uri(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    let mut uri = String::new();
+    if path.starts_with('/') {
+        uri.push_str("file://");
+    }
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                uri.push(char::from(byte))
+            }
+            _ => uri.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    uri
+}
+
+// the numbers rg --json reports for each file, and in total at the end
+#[derive(Debug, Default, Clone, PartialEq)]
+struct SearchStats {
+    elapsed: Duration,
+    searches: usize,
+    searches_with_match: usize,
+    bytes_searched: usize,
+    bytes_printed: usize,
+    matched_lines: usize,
+    matches: usize,
+}
+
+impl SearchStats {
+    fn add(&mut self, other: &SearchStats) {
+        self.elapsed += other.elapsed;
+        self.searches += other.searches;
+        self.searches_with_match += other.searches_with_match;
+        self.bytes_searched += other.bytes_searched;
+        self.bytes_printed += other.bytes_printed;
+        self.matched_lines += other.matched_lines;
+        self.matches += other.matches;
+    }
+
+    // in the key order rg writes the summary's (sorted, as serde_json does)
+    fn to_json(&self) -> String {
+        format!(
+            "{{\"bytes_printed\":{},\"bytes_searched\":{},\"elapsed\":{},\"matched_lines\":{},\"matches\":{},\"searches\":{},\"searches_with_match\":{}}}",
+            self.bytes_printed,
+            self.bytes_searched,
+            sorted_duration(self.elapsed),
+            self.matched_lines,
+            self.matches,
+            self.searches,
+            self.searches_with_match
+        )
+    }
+}
+
+// JsonLines writes the messages of rg --json, one per line: "begin" and "end"
+// around the "match" and "context" lines of each file with matches, and a
+// "summary" of the whole search at the end - so tools written for rg can read it.
+#[derive(Default)]
+pub struct JsonLines {
+    totals: SearchStats,
+}
+
+impl JsonLines {
+    fn file(&mut self, out: &mut impl Write, searched: &Searched) -> io::Result<()> {
+        let mut stats = SearchStats {
+            elapsed: searched.elapsed,
+            searches: 1,
+            bytes_searched: searched.contents.len(),
+            ..SearchStats::default()
+        };
+        for m in searched.matches() {
+            stats.matched_lines += 1;
+            stats.matches += m.spans.iter().filter(|span| !span.is_empty()).count();
+        }
+        if stats.matched_lines == 0 {
+            self.totals.add(&stats);
+            return Ok(());
+        }
+        stats.searches_with_match = 1;
+
+        let path = format!("{{\"text\":{}}}", json_string(searched.path));
+        let mut messages = format!("{{\"type\":\"begin\",\"data\":{{\"path\":{path}}}}}\n");
+        for line in searched.groups.iter().flatten() {
+            let (kind, line_number, offset, text, submatches) = match line {
+                Line::Matched(m) => {
+                    let submatches: Vec<String> = m
+                        .spans
+                        .iter()
+                        .map(|span| {
+                            format!(
+                                "{{\"match\":{{\"text\":{}}},\"start\":{},\"end\":{}}}",
+                                json_string(&m.line[span.clone()]),
+                                span.start,
+                                span.end
+                            )
+                        })
+                        .collect();
+                    ("match", m.line_number, m.byte_offset, m.line, submatches)
+                }
+                Line::Context(line_number, offset, text) => {
+                    ("context", *line_number, *offset, *text, Vec::new())
+                }
+            };
+            // the line goes out as it was in the file, line ending and all
+            let rest = &searched.contents[offset + text.len()..];
+            let ending = ["\r\n", "\n", "\0"]
+                .into_iter()
+                .find(|ending| rest.starts_with(ending))
+                .unwrap_or_default();
+            messages.push_str(&format!(
+                "{{\"type\":\"{kind}\",\"data\":{{\"path\":{path},\"lines\":{{\"text\":{}}},\"line_number\":{line_number},\"absolute_offset\":{offset},\"submatches\":[{}]}}}}\n",
+                json_string(&format!("{text}{ending}")),
+                submatches.join(",")
+            ));
+        }
+        stats.bytes_printed = messages.len();
+        messages.push_str(&format!(
+            "{{\"type\":\"end\",\"data\":{{\"path\":{path},\"binary_offset\":null,\"stats\":{}}}}}\n",
+            file_stats(&stats)
+        ));
+
+        self.totals.add(&stats);
+        out.write_all(messages.as_bytes())
+    }
+
+    fn end(self, out: &mut impl Write, elapsed: Duration) -> io::Result<()> {
+        writeln!(
+            out,
+            "{{\"data\":{{\"elapsed_total\":{},\"stats\":{}}},\"type\":\"summary\"}}",
+            sorted_duration(elapsed),
+            self.totals.to_json()
+        )
+    }
+}
+
+// an end message's stats, which rg writes in the order the fields are declared
+fn file_stats(stats: &SearchStats) -> String {
+    format!(
+        "{{\"elapsed\":{},\"searches\":{},\"searches_with_match\":{},\"bytes_searched\":{},\"bytes_printed\":{},\"matched_lines\":{},\"matches\":{}}}",
+        duration(stats.elapsed),
+        stats.searches,
+        stats.searches_with_match,
+        stats.bytes_searched,
+        stats.bytes_printed,
+        stats.matched_lines,
+        stats.matches
+    )
+}
+
+// a duration as rg writes one
+fn duration(elapsed: Duration) -> String {
+    format!(
+        "{{\"secs\":{},\"nanos\":{},\"human\":\"{:.6}s\"}}",
+        elapsed.as_secs(),
+        elapsed.subsec_nanos(),
+        elapsed.as_secs_f64()
+    )
+}
+
+// the same, with the keys sorted for the summary
+fn sorted_duration(elapsed: Duration) -> String {
+    format!(
+        "{{\"human\":\"{:.6}s\",\"nanos\":{},\"secs\":{}}}",
+        elapsed.as_secs_f64(),
+        elapsed.subsec_nanos(),
+        elapsed.as_secs()
+    )
+}
+
+// a JSON string literal, quotes included
+pub(crate) fn json_string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nobody(line_number: usize, byte_offset: usize, line: &str) -> Line<'_> {
+        let start = line.find("nobody").unwrap();
+        let span = start..start + "nobody".len();
+        Line::Matched(Match {
+            line_number,
+            byte_offset,
+            line,
+            spans: vec![span],
+            score: None,
+        })
+    }
+
+    fn report(format: Format, files: &[Searched]) -> String {
+        let mut out = Vec::new();
+        let mut report = Report::begin(format, &mut out).unwrap().unwrap();
+        for searched in files {
+            report.file(&mut out, searched).unwrap();
+        }
+        report.end(&mut out, Duration::from_millis(2)).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn json_document_lists_the_files_with_matches() {
+        let poem = [vec![
+            nobody(1, 0, "I'm nobody! Who are you?"),
+            nobody(2, 25, "Are you \"nobody\", too?"),
+        ]];
+        let notes = [vec![nobody(7, 0, "\tnobody")]];
+        let files = [
+            Searched {
+                path: "poem.txt",
+                contents: "",
+                groups: &poem,
+                terminator: '\n',
+                elapsed: Duration::ZERO,
+            },
+            Searched {
+                path: "empty.txt",
+                contents: "",
+                groups: &[],
+                terminator: '\n',
+                elapsed: Duration::ZERO,
+            },
+            Searched {
+                path: "C:\\notes.txt",
+                contents: "",
+                groups: &notes,
+                terminator: '\n',
+                elapsed: Duration::ZERO,
+            },
+        ];
+
+        assert_eq!(
+            concat!(
+                r#"{"files":[{"path":"poem.txt","matches":["#,
+                r#"{"line_number":1,"column":5,"text":"I'm nobody! Who are you?","spans":[{"start":4,"end":10}]},"#,
+                r#"{"line_number":2,"column":10,"text":"Are you \"nobody\", too?","spans":[{"start":9,"end":15}]}]},"#,
+                r#"{"path":"C:\\notes.txt","matches":["#,
+                r#"{"line_number":7,"column":2,"text":"\tnobody","spans":[{"start":1,"end":7}]}]}]}"#,
+                "\n"
+            ),
+            report(Format::Json, &files)
+        );
+    }
+
+    #[test]
+    fn csv_and_tsv_have_a_row_per_match() {
+        let matched = |line_number, line, spans| {
+            Line::Matched(Match {
+                line_number,
+                byte_offset: 0,
+                line,
+                spans,
+                score: None,
+            })
+        };
+        let groups = [vec![
+            matched(1, "nobody, \"nobody\"", vec![0..6, 9..15]),
+            Line::Context(2, 0, "context isn't listed"),
+            // an inverted line has no spans
+            matched(3, "a\tb", Vec::new()),
+        ]];
+        let files = [Searched {
+            path: "my notes.txt",
+            contents: "",
+            groups: &groups,
+            terminator: '\n',
+            elapsed: Duration::ZERO,
+        }];
+
+        assert_eq!(
+            "\
+path,line,column,match_text
+my notes.txt,1,1,nobody
+my notes.txt,1,10,nobody
+my notes.txt,3,1,a\tb
+",
+            report(Format::Csv, &files)
+        );
+        assert_eq!(
+            "\
+path\tline\tcolumn\tmatch_text
+my notes.txt\t1\t1\tnobody
+my notes.txt\t1\t10\tnobody
+my notes.txt\t3\t1\ta\\tb
+",
+            report(Format::Tsv, &files)
+        );
+        assert_eq!(
+            "\"say \"\"hi\"\", then go\"",
+            csv_field("say \"hi\", then go")
+        );
+    }
+
+    #[test]
+    fn sarif_has_a_result_per_match() {
+        let groups = [vec![nobody(3, 0, "¿nobody?")]];
+        let files = [Searched {
+            path: "docs/read me.txt",
+            contents: "",
+            groups: &groups,
+            terminator: '\n',
+            elapsed: Duration::ZERO,
+        }];
+
+        let log = report(Format::Sarif, &files);
+        assert!(log.starts_with(r#"{"$schema":"https://json.schemastore.org/sarif-2.1.0.json","version":"2.1.0","runs":[{"tool":{"driver":{"name":"minigrep","#));
+        assert!(log.ends_with(
+            concat!(
+                r#""columnKind":"unicodeCodePoints","results":[{"ruleId":"match","level":"warning","message":{"text":"Matched: nobody"},"#,
+                r#""locations":[{"physicalLocation":{"artifactLocation":{"uri":"docs/read%20me.txt"},"#,
+                r#""region":{"startLine":3,"startColumn":2,"endColumn":8,"snippet":{"text":"¿nobody?"}}}}]}]}]}"#,
+                "\n"
+            )
+        ));
+        assert_eq!("file:///tmp/a%23b.rs", file_uri("/tmp/a#b.rs"));
+    }
+
+    #[test]
+    fn json_lines_follow_the_rg_schema() {
+        let contents = "I'm nobody! Who are you?\r\nAre you nobody, too?\n";
+        let groups = [vec![
+            nobody(1, 0, "I'm nobody! Who are you?"),
+            Line::Context(2, 26, "Are you nobody, too?"),
+        ]];
+        let files = [
+            Searched {
+                path: "poem.txt",
+                contents,
+                groups: &groups,
+                terminator: '\n',
+                elapsed: Duration::from_nanos(1500),
+            },
+            Searched {
+                path: "other.txt",
+                contents: "nothing here\n",
+                groups: &[],
+                terminator: '\n',
+                elapsed: Duration::from_nanos(500),
+            },
+        ];
+
+        let report = report(Format::JsonLines, &files);
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(5, lines.len());
+        assert_eq!(
+            r#"{"type":"begin","data":{"path":{"text":"poem.txt"}}}"#,
+            lines[0]
+        );
+        assert_eq!(
+            r#"{"type":"match","data":{"path":{"text":"poem.txt"},"lines":{"text":"I'm nobody! Who are you?\r\n"},"line_number":1,"absolute_offset":0,"submatches":[{"match":{"text":"nobody"},"start":4,"end":10}]}}"#,
+            lines[1]
+        );
+        assert_eq!(
+            r#"{"type":"context","data":{"path":{"text":"poem.txt"},"lines":{"text":"Are you nobody, too?\n"},"line_number":2,"absolute_offset":26,"submatches":[]}}"#,
+            lines[2]
+        );
+        let printed = lines[0].len() + lines[1].len() + lines[2].len() + 3;
+        assert_eq!(
+            format!(
+                r#"{{"type":"end","data":{{"path":{{"text":"poem.txt"}},"binary_offset":null,"stats":{{"elapsed":{{"secs":0,"nanos":1500,"human":"0.000002s"}},"searches":1,"searches_with_match":1,"bytes_searched":47,"bytes_printed":{printed},"matched_lines":1,"matches":1}}}}}}"#
+            ),
+            lines[3]
+        );
+        assert_eq!(
+            format!(
+                r#"{{"data":{{"elapsed_total":{{"human":"0.002000s","nanos":2000000,"secs":0}},"stats":{{"bytes_printed":{printed},"bytes_searched":60,"elapsed":{{"human":"0.000002s","nanos":2000,"secs":0}},"matched_lines":1,"matches":1,"searches":2,"searches_with_match":1}}}},"type":"summary"}}"#
+            ),
+            lines[4]
+        );
+    }
+}
@@ -0,0 +1,133 @@
+use std::ops::Range;
+
+// Approximate matching: finds the part of a line with the smallest
+// edit distance (insertions, deletions and substitutions) to a query,
+// so typos like "confgi" still find "config".
+pub struct Fuzzy {
+    queries: Vec<Vec<char>>,
+    max_distance: usize,
+    ignore_case: bool,
+}
+
+impl Fuzzy {
+    pub fn new(queries: &[String], max_distance: usize, ignore_case: bool) -> Fuzzy {
+        let queries = queries
+            .iter()
+            .map(|query| query.chars().map(|c| fold(c, ignore_case)).collect())
+            .collect();
+        Fuzzy {
+            queries,
+            max_distance,
+            ignore_case,
+        }
+    }
+
+    // the best match starting at or after byte offset `start`, as (span, distance)
+    // the closest query wins; ties go to the match that ends first
+    pub fn find_at(&self, line: &str, start: usize) -> Option<(Range<usize>, usize)> {
+        let mut best: Option<(Range<usize>, usize)> = None;
+        for query in &self.queries {
+            if let Some((span, distance)) = self.find_query(query, &line[start..]) {
+                let span = start + span.start..start + span.end;
+                let closer = match &best {
+                    Some((best_span, best_distance)) => {
+                        distance < *best_distance
+                            || (distance == *best_distance && span.end < best_span.end)
+                    }
+                    None => true,
+                };
+                if closer {
+                    best = Some((span, distance));
+                }
+            }
+        }
+        best
+    }
+
+    // Sellers' algorithm: edit distance where the match may start anywhere in the text
+    // each cell keeps (distance, byte offset where that alignment started)
+    fn find_query(&self, query: &[char], text: &str) -> Option<(Range<usize>, usize)> {
+        let m = query.len();
+        let mut previous: Vec<(usize, usize)> = (0..=m).map(|i| (i, 0)).collect();
+        let mut current = previous.clone();
+
+        // a query short enough to be deleted entirely matches at the very start
+        let mut best = if m <= self.max_distance {
+            Some((0..0, m))
+        } else {
+            None
+        };
+
+        for (offset, c) in text.char_indices() {
+            let end = offset + c.len_utf8();
+            let c = fold(c, self.ignore_case);
+
+            // a match can always start fresh after this char
+            current[0] = (0, end);
+            for i in 1..=m {
+                let cost = usize::from(query[i - 1] != c);
+                let substitute = (previous[i - 1].0 + cost, previous[i - 1].1);
+                let skip_query = (current[i - 1].0 + 1, current[i - 1].1);
+                let skip_text = (previous[i].0 + 1, previous[i].1);
+                current[i] = [substitute, skip_query, skip_text]
+                    .into_iter()
+                    .min_by_key(|&(distance, _)| distance)
+                    .unwrap();
+            }
+
+            let (distance, start) = current[m];
+            let improves = match &best {
+                Some((_, best_distance)) => distance < *best_distance,
+                None => distance <= self.max_distance,
+            };
+            if improves {
+                best = Some((start..end, distance));
+                if distance == 0 {
+                    break;
+                }
+            }
+
+            std::mem::swap(&mut previous, &mut current);
+        }
+
+        best
+    }
+}
+
+fn fold(c: char, ignore_case: bool) -> char {
+    if ignore_case {
+        c.to_lowercase().next().unwrap_or(c)
+    } else {
+        c
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fuzzy(query: &str, max_distance: usize) -> Fuzzy {
+        Fuzzy::new(&[query.to_string()], max_distance, false)
+    }
+
+    #[test]
+    fn finds_typos_within_distance() {
+        // dropping the stray 'g' turns "confgi" into "confi"
+        let line = "let config = load();";
+
+        assert_eq!(Some((4..9, 1)), fuzzy("confgi", 1).find_at(line, 0));
+        assert_eq!(None, fuzzy("confgi", 0).find_at(line, 0));
+    }
+
+    #[test]
+    fn exact_match_has_distance_zero() {
+        assert_eq!(Some((7..11, 0)), fuzzy("frog", 1).find_at("like a frog", 0));
+    }
+
+    #[test]
+    fn ignore_case_folds_both_sides() {
+        let fuzzy = Fuzzy::new(&["Frog".to_string()], 0, true);
+
+        assert_eq!(Some((2..6, 0)), fuzzy.find_at("a FROG", 0));
+    }
+}
@@ -0,0 +1,232 @@
+use std::cell::RefCell;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+// Files as they were at a git revision, read straight from the repository's
+// objects with gix rather than by running git - so a revision is only ever
+// parsed as one, and never taken for one of git's options.
+
+// the files tracked under `path` at the revision, relative to the current directory
+pub fn list_files(rev: &str, path: &str) -> io::Result<Vec<String>> {
+    list_files_in(Path::new("."), rev, path)
+}
+
+// the contents of a file (relative to the current directory) at the revision
+pub fn read_file(rev: &str, path: &str) -> io::Result<Vec<u8>> {
+    read_file_in(Path::new("."), rev, path)
+}
+
+// a path from the config of the repository at `repo` (and the user's) like
+// core.excludesFile, with its ~ expanded - None when it isn't set
+pub fn config_path(repo: &Path, key: &str) -> Option<PathBuf> {
+    let (repo, _) = open(repo).ok()?;
+    repo.config_snapshot().trusted_path(key).ok()?
+}
+
+fn list_files_in(dir: &Path, rev: &str, path: &str) -> io::Result<Vec<String>> {
+    let (repo, prefix) = open(dir)?;
+    let tree = tree_at(&repo, rev)?;
+    let Some(within) = in_repo(&prefix, path) else {
+        return Err(outside(path));
+    };
+    let tree = if within.as_os_str().is_empty() {
+        tree
+    } else {
+        match tree.lookup_entry_by_path(&within).map_err(other)? {
+            Some(entry) if entry.mode().is_tree() => entry.object().map_err(other)?.into_tree(),
+            Some(entry) if entry.mode().is_blob_or_symlink() => {
+                return Ok(vec![relative(&prefix, &within)])
+            }
+            // like git ls-tree, a path that isn't there has no files
+            _ => return Ok(Vec::new()),
+        }
+    };
+
+    // in the tree's order, as git lists them
+    let mut recorder = gix::traverse::tree::Recorder::default();
+    tree.traverse().depthfirst(&mut recorder).map_err(other)?;
+    Ok(recorder
+        .records
+        .into_iter()
+        .filter(|entry| entry.mode.is_blob_or_symlink())
+        .map(|entry| relative(&prefix, &within.join(entry.filepath.to_string())))
+        .collect())
+}
+
+fn read_file_in(dir: &Path, rev: &str, path: &str) -> io::Result<Vec<u8>> {
+    let (repo, prefix) = open(dir)?;
+    let tree = tree_at(&repo, rev)?;
+    let within = in_repo(&prefix, path).ok_or_else(|| outside(path))?;
+    match tree.lookup_entry_by_path(&within).map_err(other)? {
+        Some(entry) if entry.mode().is_blob_or_symlink() => {
+            Ok(entry.object().map_err(other)?.detach().data)
+        }
+        _ => Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("not a file at {rev}"),
+        )),
+    }
+}
+
+// the repository `dir` is in, and where `dir` is in it - opened once on
+// each thread, as every file read at a revision needs it
+fn open(dir: &Path) -> io::Result<(gix::Repository, PathBuf)> {
+    thread_local! {
+        static OPENED: RefCell<Option<(PathBuf, gix::Repository, PathBuf)>> =
+            const { RefCell::new(None) };
+    }
+    let dir = dir.canonicalize()?;
+    let cached = OPENED.with_borrow(|opened| match opened {
+        Some((opened_dir, repo, prefix)) if *opened_dir == dir => {
+            Some((repo.clone(), prefix.clone()))
+        }
+        _ => None,
+    });
+    if let Some(opened) = cached {
+        return Ok(opened);
+    }
+    let repo = gix::discover(&dir).map_err(other)?;
+    let workdir = repo
+        .workdir()
+        .ok_or_else(|| io::Error::other("not in a git working tree"))?
+        .canonicalize()?;
+    let prefix = dir.strip_prefix(&workdir).map_err(other)?.to_path_buf();
+    OPENED.set(Some((dir, repo.clone(), prefix.clone())));
+    Ok((repo, prefix))
+}
+
+fn tree_at<'repo>(repo: &'repo gix::Repository, rev: &str) -> io::Result<gix::Tree<'repo>> {
+    let id = repo
+        .rev_parse_single(rev)
+        .map_err(|err| io::Error::new(io::ErrorKind::NotFound, err))?;
+    id.object().map_err(other)?.peel_to_tree().map_err(other)
+}
+
+// `path` (relative to `prefix`, where the current directory is) relative to
+// the top of the repository - None if it's outside it
+fn in_repo(prefix: &Path, path: &str) -> Option<PathBuf> {
+    let mut within = PathBuf::new();
+    for component in prefix.join(path).components() {
+        match component {
+            Component::Normal(name) => within.push(name),
+            Component::ParentDir => {
+                if !within.pop() {
+                    return None;
+                }
+            }
+            Component::CurDir => {}
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(within)
+}
+
+// a path from the top of the repository as it is from `prefix`, the way
+// git prints paths from a directory inside it
+fn relative(prefix: &Path, path: &Path) -> String {
+    let common = prefix
+        .components()
+        .zip(path.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut relative = PathBuf::new();
+    for _ in prefix.components().skip(common) {
+        relative.push("..");
+    }
+    relative.push(path.components().skip(common).collect::<PathBuf>());
+    relative.to_string_lossy().into_owned()
+}
+
+fn outside(path: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("{path}: outside the repository"),
+    )
+}
+
+fn other(err: impl std::error::Error + Send + Sync + 'static) -> io::Error {
+    io::Error::other(err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TempDir;
+    use std::fs;
+    use std::process::Command;
+
+    // a repository with a commit of the files, each holding its own name,
+    // then changed so the working tree isn't what's read
+    fn repository(files: &[&str]) -> TempDir {
+        let dir = TempDir::new("git");
+        let git = |args: &[&str]| {
+            let status = Command::new("git")
+                .args(["-c", "user.name=a", "-c", "user.email=a@b", "-C"])
+                .arg(&*dir)
+                .args(args)
+                .output()
+                .unwrap()
+                .status;
+            assert!(status.success(), "git {args:?}");
+        };
+        git(&["init", "-q"]);
+        for file in files {
+            let path = dir.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, file).unwrap();
+        }
+        git(&["add", "."]);
+        git(&["commit", "-q", "-m", "files"]);
+        for file in files {
+            fs::write(dir.join(file), "changed").unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn files_are_listed_and_read_at_the_revision() {
+        let repo = repository(&["a.txt", "src/b.rs", "src/c/d.rs"]);
+
+        assert_eq!(
+            vec!["a.txt", "src/b.rs", "src/c/d.rs"],
+            list_files_in(&repo, "HEAD", ".").unwrap()
+        );
+        assert_eq!(
+            vec!["src/b.rs", "src/c/d.rs"],
+            list_files_in(&repo, "HEAD", "src").unwrap()
+        );
+        assert_eq!(
+            b"src/b.rs",
+            read_file_in(&repo, "HEAD", "src/b.rs").unwrap().as_slice()
+        );
+        assert!(list_files_in(&repo, "HEAD", "missing").unwrap().is_empty());
+        assert!(read_file_in(&repo, "HEAD", "src").is_err());
+
+        // from a directory further down, paths are relative to it
+        let src = repo.join("src");
+        assert_eq!(
+            vec!["../a.txt"],
+            list_files_in(&src, "HEAD", "../a.txt").unwrap()
+        );
+        assert_eq!(
+            vec!["b.rs", "c/d.rs"],
+            list_files_in(&src, "HEAD", ".").unwrap()
+        );
+        assert_eq!(
+            b"src/c/d.rs",
+            read_file_in(&src, "HEAD", "c/d.rs").unwrap().as_slice()
+        );
+        assert!(list_files_in(&src, "HEAD", "../..").is_err());
+    }
+
+    #[test]
+    fn a_revision_is_never_an_option() {
+        let repo = repository(&["a.txt"]);
+        let output = repo.join("out.txt");
+        let rev = format!("--output={}", output.display());
+
+        assert!(list_files_in(&repo, &rev, ".").is_err());
+        assert!(read_file_in(&repo, &rev, "a.txt").is_err());
+        assert!(!output.exists());
+    }
+}
@@ -0,0 +1,138 @@
+use regex::Regex;
+
+// A shell style glob matched against '/' separated paths:
+//   *      any run of characters within one path component
+//   ?      any one character except '/'
+//   **     any number of whole components ("**/a", "a/**", "a/**/b")
+//   [abc]  one of the characters, [a-z] ranges and [!abc] negation
+//   \x     a literal x
+// The glob is translated to a regex once, so matching is a single regex search.
+#[derive(Debug, Clone)]
+pub struct Glob {
+    regex: Regex,
+}
+
+impl Glob {
+    pub fn new(glob: &str) -> Result<Glob, regex::Error> {
+        let regex = Regex::new(&format!("^{}$", to_regex(glob)))?;
+        Ok(Glob { regex })
+    }
+
+    pub fn is_match(&self, path: &str) -> bool {
+        self.regex.is_match(path)
+    }
+}
+
+fn to_regex(glob: &str) -> String {
+    let chars: Vec<char> = glob.chars().collect();
+    let mut regex = String::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                let starts_component = i == 0 || chars[i - 1] == '/';
+                let ends_component = i + 2 == chars.len() || chars[i + 2] == '/';
+                if starts_component && i + 2 < chars.len() && ends_component {
+                    // "**/" - zero or more directories
+                    regex.push_str("(?:.*/)?");
+                    i += 3;
+                } else if starts_component && ends_component {
+                    // a trailing "**" - everything below
+                    regex.push_str(".*");
+                    i += 2;
+                } else {
+                    // "a**b" is just a '*' written twice
+                    regex.push_str("[^/]*");
+                    i += 2;
+                }
+                continue;
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            '[' => match class_to_regex(&chars[i..]) {
+                Some((class, len)) => {
+                    regex.push_str(&class);
+                    i += len;
+                    continue;
+                }
+                // no closing ']' - the '[' is literal
+                None => regex.push_str(r"\["),
+            },
+            '\\' if i + 1 < chars.len() => {
+                i += 1;
+                regex.push_str(&regex::escape(&chars[i].to_string()));
+            }
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+        i += 1;
+    }
+
+    regex
+}
+
+// "[...]" at the start of the chars, as a regex class and how many chars it used
+fn class_to_regex(chars: &[char]) -> Option<(String, usize)> {
+    let mut i = 1;
+    let negated = matches!(chars.get(i), Some('!') | Some('^'));
+    let mut class = String::from(if negated { "[^/" } else { "[" });
+    if negated {
+        i += 1;
+    }
+    // a ']' straight after the opening is part of the set
+    let first = i;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == ']' && i > first {
+            class.push(']');
+            return Some((class, i + 1));
+        }
+        let is_range = c == '-' && i > first && chars.get(i + 1).is_some_and(|&next| next != ']');
+        if is_range {
+            class.push('-');
+        } else {
+            class.push_str(&regex::escape(&c.to_string()));
+        }
+        i += 1;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(glob: &str, path: &str) -> bool {
+        Glob::new(glob).unwrap().is_match(path)
+    }
+
+    #[test]
+    fn star_stays_within_a_component() {
+        assert!(matches("*.rs", "main.rs"));
+        assert!(!matches("*.rs", "src/main.rs"));
+        assert!(matches("src/*.rs", "src/main.rs"));
+        assert!(matches("?.txt", "a.txt"));
+        assert!(!matches("?.txt", "ab.txt"));
+    }
+
+    #[test]
+    fn double_star_spans_directories() {
+        assert!(matches("**/*.rs", "main.rs"));
+        assert!(matches("**/*.rs", "src/bin/main.rs"));
+        assert!(matches("target/**", "target/debug/minigrep"));
+        assert!(matches("a/**/b", "a/b"));
+        assert!(matches("a/**/b", "a/x/y/b"));
+        assert!(!matches("a/**/b", "ab"));
+    }
+
+    #[test]
+    fn character_classes() {
+        assert!(matches("[abc].txt", "b.txt"));
+        assert!(matches("[a-c].txt", "b.txt"));
+        assert!(!matches("[!a-c].txt", "b.txt"));
+        assert!(matches("[!a-c].txt", "d.txt"));
+        assert!(matches("[].txt", "[].txt"));
+        assert!(matches(r"\*.txt", "*.txt"));
+        assert!(!matches(r"\*.txt", "a.txt"));
+    }
+}
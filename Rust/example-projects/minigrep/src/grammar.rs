@@ -0,0 +1,226 @@
+use std::ops::Range;
+use std::path::Path;
+
+use tree_sitter::{Language, Parser};
+
+use crate::syntax::Kind;
+
+// The languages --only-in parses with tree-sitter (with the tree-sitter
+// feature). A node's kind says what it is, so comments and strings are the
+// ones the language's own grammar finds, and function names can be told from
+// every other identifier. Files in other languages are left to the lexer in
+// syntax.rs, which can't find function names.
+
+pub struct Grammar {
+    language: fn() -> Language,
+    comments: &'static [&'static str],
+    strings: &'static [&'static str],
+    // the nodes that declare a function, with the field of each that names it
+    functions: &'static [(&'static str, &'static str)],
+}
+
+const RUST: Grammar = Grammar {
+    language: || tree_sitter_rust::LANGUAGE.into(),
+    comments: &["line_comment", "block_comment"],
+    // a 'x' is code, as the lexer has it
+    strings: &["string_literal", "raw_string_literal"],
+    functions: &[
+        ("function_item", "name"),
+        ("function_signature_item", "name"),
+    ],
+};
+
+const PYTHON: Grammar = Grammar {
+    language: || tree_sitter_python::LANGUAGE.into(),
+    comments: &["comment"],
+    strings: &["string"],
+    functions: &[("function_definition", "name")],
+};
+
+const JAVASCRIPT: Grammar = Grammar {
+    language: || tree_sitter_javascript::LANGUAGE.into(),
+    comments: &["comment"],
+    strings: &["string", "template_string"],
+    functions: &[
+        ("function_declaration", "name"),
+        ("generator_function_declaration", "name"),
+        ("function_expression", "name"),
+        ("method_definition", "name"),
+    ],
+};
+
+const C: Grammar = Grammar {
+    language: || tree_sitter_c::LANGUAGE.into(),
+    comments: &["comment"],
+    strings: &["string_literal", "char_literal", "system_lib_string"],
+    // a definition's or a prototype's
+    functions: &[("function_declarator", "declarator")],
+};
+
+const GO: Grammar = Grammar {
+    language: || tree_sitter_go::LANGUAGE.into(),
+    comments: &["comment"],
+    strings: &[
+        "interpreted_string_literal",
+        "raw_string_literal",
+        "rune_literal",
+    ],
+    functions: &[
+        ("function_declaration", "name"),
+        ("method_declaration", "name"),
+    ],
+};
+
+pub fn for_path(path: &Path) -> Option<&'static Grammar> {
+    let grammar = match path.extension()?.to_str()? {
+        "rs" => &RUST,
+        "py" | "pyi" => &PYTHON,
+        "js" | "jsx" | "mjs" | "cjs" => &JAVASCRIPT,
+        "c" | "h" => &C,
+        "go" => &GO,
+        _ => return None,
+    };
+    Some(grammar)
+}
+
+// the byte ranges of `contents` that are of one of `kinds`, in order, like
+// syntax::regions
+pub fn regions(grammar: &Grammar, contents: &str, kinds: &[Kind]) -> Vec<Range<usize>> {
+    let mut parser = Parser::new();
+    parser
+        .set_language(&(grammar.language)())
+        .expect("a grammar minigrep is built with");
+    let Some(tree) = parser.parse(contents, None) else {
+        return Vec::new();
+    };
+
+    let mut found = Vec::new();
+    // the comments and strings, whatever's asked for - code is what's between them
+    let mut literals: Vec<Range<usize>> = Vec::new();
+    let mut cursor = tree.walk();
+    'walk: loop {
+        let node = cursor.node();
+        let kind = if grammar.comments.contains(&node.kind()) {
+            Some(Kind::Comment)
+        } else if grammar.strings.contains(&node.kind()) {
+            Some(Kind::String)
+        } else {
+            None
+        };
+        match kind {
+            Some(kind) => {
+                literals.push(node.byte_range());
+                if kinds.contains(&kind) {
+                    found.push(node.byte_range());
+                }
+            }
+            None => {
+                if kinds.contains(&Kind::Function) {
+                    let names = grammar
+                        .functions
+                        .iter()
+                        .filter(|(declaration, _)| *declaration == node.kind())
+                        .filter_map(|(_, field)| node.child_by_field_name(field));
+                    found.extend(names.map(|name| name.byte_range()));
+                }
+                // nothing in a comment or a string is anything else
+                if cursor.goto_first_child() {
+                    continue;
+                }
+            }
+        }
+        while !cursor.goto_next_sibling() {
+            if !cursor.goto_parent() {
+                break 'walk;
+            }
+        }
+    }
+
+    if kinds.contains(&Kind::Code) {
+        let mut code_start = 0;
+        for literal in &literals {
+            found.push(code_start..literal.start);
+            code_start = literal.end;
+        }
+        found.push(code_start..contents.len());
+    }
+    found.sort_by_key(|range| range.start);
+    let mut regions: Vec<Range<usize>> = Vec::new();
+    for range in found.into_iter().filter(|range| !range.is_empty()) {
+        match regions.last_mut() {
+            Some(last) if last.end >= range.start => last.end = last.end.max(range.end),
+            _ => regions.push(range),
+        }
+    }
+    regions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts<'a>(path: &str, contents: &'a str, kind: Kind) -> Vec<&'a str> {
+        let grammar = for_path(Path::new(path)).unwrap();
+        regions(grammar, contents, &[kind])
+            .into_iter()
+            .map(|range| &contents[range])
+            .collect()
+    }
+
+    #[test]
+    fn function_names_are_told_from_other_identifiers() {
+        let rust = "\
+fn parse(x: u8) -> u8 { x }
+// fn commented() {}
+impl A {
+    fn method(&self) { let s = \"fn quoted() {}\"; parse(1); }
+}
+trait B {
+    fn required(&self);
+}
+";
+        assert_eq!(
+            vec!["parse", "method", "required"],
+            texts("a.rs", rust, Kind::Function)
+        );
+
+        let python = "def run():\n    return run  # def not_this()\n";
+        assert_eq!(vec!["run"], texts("a.py", python, Kind::Function));
+
+        let js = "function go() {}\nclass A { stop() {} }\nconst f = function named() {};\n";
+        assert_eq!(
+            vec!["go", "stop", "named"],
+            texts("a.js", js, Kind::Function)
+        );
+
+        let c = "int add(int a, int b);\nint *make(void) { return add(1, 2) ? 0 : 0; }\n";
+        assert_eq!(vec!["add", "make"], texts("a.c", c, Kind::Function));
+
+        let go = "func Run() {}\nfunc (s *S) Stop() { Run() }\n";
+        assert_eq!(vec!["Run", "Stop"], texts("a.go", go, Kind::Function));
+    }
+
+    #[test]
+    fn comments_and_strings_are_the_grammars() {
+        let rust = "/* a /* nested */ b */ let c = '\"'; let s = r#\"say \"hi\"\"#;\n";
+        assert_eq!(
+            vec!["/* a /* nested */ b */"],
+            texts("a.rs", rust, Kind::Comment)
+        );
+        assert_eq!(vec!["r#\"say \"hi\"\"#"], texts("a.rs", rust, Kind::String));
+        let code = texts("a.rs", rust, Kind::Code).concat();
+        assert_eq!(" let c = '\"'; let s = ;\n", code);
+
+        let python = "x = \"\"\"a # not a comment\n\"\"\"  # but this is\n";
+        assert_eq!(vec!["# but this is"], texts("a.py", python, Kind::Comment));
+
+        let go = "s := `raw // not a comment` // this is\n";
+        assert_eq!(vec!["// this is"], texts("a.go", go, Kind::Comment));
+    }
+
+    #[test]
+    fn other_languages_have_no_grammar() {
+        assert!(for_path(Path::new("a.sql")).is_none());
+        assert!(for_path(Path::new("Makefile")).is_none());
+    }
+}
@@ -0,0 +1,66 @@
+use std::io::{self, Read};
+use std::process::{Command, Stdio};
+
+// Fetching http(s) URLs given in place of a file path. The body is streamed
+// from curl (run like the decompressors, so there's no TLS stack to link) and
+// reading stops once it gets bigger than the size cap.
+
+// how much of a response is read, unless --max-filesize says otherwise
+pub const MAX_BODY: u64 = 64 << 20;
+
+pub fn is_url(path: &str) -> bool {
+    path.starts_with("http://") || path.starts_with("https://")
+}
+
+// the response body, or an error for a failed request or one over `max_size` bytes
+pub fn fetch(url: &str, max_size: u64) -> io::Result<Vec<u8>> {
+    let mut child = Command::new("curl")
+        .args([
+            "--silent",
+            "--show-error",
+            "--fail",
+            "--location",
+            "--",
+            url,
+        ])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| io::Error::new(err.kind(), format!("unable to run curl: {err}")))?;
+
+    let mut body = Vec::new();
+    if let Some(stdout) = child.stdout.take() {
+        // one byte past the cap is enough to know it's too big
+        stdout.take(max_size + 1).read_to_end(&mut body)?;
+    }
+    if body.len() as u64 > max_size {
+        let _ = child.kill();
+        let _ = child.wait();
+        return Err(io::Error::other(format!(
+            "response is bigger than {max_size} bytes"
+        )));
+    }
+
+    let mut errors = String::new();
+    if let Some(mut stderr) = child.stderr.take() {
+        stderr.read_to_string(&mut errors)?;
+    }
+    if !child.wait()?.success() {
+        return Err(io::Error::other(errors.trim().to_string()));
+    }
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_http_and_https_are_urls() {
+        assert!(is_url("https://example.com/app.log"));
+        assert!(is_url("http://localhost:8080/config"));
+        assert!(!is_url("ftp://example.com/file"));
+        assert!(!is_url("poem.txt"));
+    }
+}
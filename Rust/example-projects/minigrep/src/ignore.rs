@@ -0,0 +1,267 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::git;
+use crate::glob::Glob;
+
+// the ignore files read in each directory, lowest precedence first - a rule
+// in .minigrepignore overrides one in .ignore, which overrides .gitignore
+// (.ignore is shared with other tools like ripgrep, for projects not using git)
+// .gitignore is only git's inside a repository, so it's only read there
+pub const IGNORE_FILES: [&str; 3] = [".gitignore", ".ignore", ".minigrepignore"];
+
+// the ignore rules of one directory, which apply to everything below it
+pub struct Ignore {
+    dir: PathBuf,
+    // where dir is below the directory the rules are relative to, for a
+    // repository's excludes when the walk starts further down
+    prefix: PathBuf,
+    rules: Vec<Rule>,
+}
+
+struct Rule {
+    glob: Glob,
+    // "!pattern" - a path matching it is searched after all
+    negated: bool,
+    // "pattern/" - only matches directories
+    dir_only: bool,
+}
+
+impl Ignore {
+    // the rules of every ignore file in `dir`, or None if it has none
+    // the files are read in order, so later rules win as within one file -
+    // after git's excludes, when `dir` is a repository (`global` says
+    // whether they include the user's own), and leaving out .gitignore when
+    // it isn't `in_repo`
+    pub fn load(dir: &Path, global: bool, in_repo: bool) -> Option<Ignore> {
+        let mut contents = String::new();
+        let mut found = false;
+        if let Some(excludes) = git_excludes(dir, global) {
+            contents = excludes;
+            found = true;
+        }
+        for file_name in IGNORE_FILES {
+            if file_name == ".gitignore" && !in_repo {
+                continue;
+            }
+            if let Ok(file) = fs::read_to_string(dir.join(file_name)) {
+                contents.push_str(&file);
+                contents.push('\n');
+                found = true;
+            }
+        }
+        found.then(|| Ignore::parse(dir, &contents))
+    }
+
+    // git's excludes for the repository `dir` is somewhere inside, when
+    // that's further up - those in `dir` itself are load's
+    pub fn above(dir: &Path, global: bool) -> Option<Ignore> {
+        let dir = dir.to_path_buf();
+        let canonical = fs::canonicalize(&dir).ok()?;
+        let (repo, excludes) = canonical
+            .ancestors()
+            .skip(1)
+            .find_map(|repo| Some((repo, git_excludes(repo, global)?)))?;
+        let mut ignore = Ignore::parse(&dir, &excludes);
+        ignore.prefix = canonical.strip_prefix(repo).ok()?.to_path_buf();
+        Some(ignore)
+    }
+
+    // whether `dir` is in a git repository, itself or somewhere above
+    pub fn in_repo(dir: &Path) -> bool {
+        let canonical = fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf());
+        canonical.ancestors().any(|dir| git_dir(dir).is_some())
+    }
+
+    pub fn parse(dir: &Path, contents: &str) -> Ignore {
+        let rules = contents.lines().filter_map(Rule::parse).collect();
+        Ignore {
+            dir: dir.to_path_buf(),
+            prefix: PathBuf::new(),
+            rules,
+        }
+    }
+
+    // Some(true) if the path is ignored, Some(false) if a negated rule
+    // brings it back, None if no rule mentions it
+    // later rules override earlier ones, like git
+    pub fn matched(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        let relative = self.prefix.join(path.strip_prefix(&self.dir).ok()?);
+        let relative = relative.to_string_lossy().replace('\\', "/");
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| (is_dir || !rule.dir_only) && rule.glob.is_match(&relative))
+            .map(|rule| !rule.negated)
+    }
+}
+
+// what git ignores in the repository at `repo` beyond its .gitignore files,
+// lowest precedence first: the user's global excludes file (core.excludesFile,
+// or else ~/.config/git/ignore) unless `global` is false, then the
+// repository's .git/info/exclude - None if `repo` isn't one
+fn git_excludes(repo: &Path, global: bool) -> Option<String> {
+    let git_dir = git_dir(repo)?;
+    let mut contents = String::new();
+    if global {
+        contents.push_str(global_excludes(repo));
+    }
+    if let Ok(exclude) = fs::read_to_string(git_dir.join("info").join("exclude")) {
+        contents.push_str(&exclude);
+        contents.push('\n');
+    }
+    Some(contents)
+}
+
+// the repository's .git directory - or for a submodule or worktree, where
+// its .git file points
+fn git_dir(repo: &Path) -> Option<PathBuf> {
+    let dot_git = repo.join(".git");
+    if dot_git.is_dir() {
+        return Some(dot_git);
+    }
+    let file = fs::read_to_string(&dot_git).ok()?;
+    let git_dir = file.trim_end().strip_prefix("gitdir: ")?;
+    Some(repo.join(git_dir))
+}
+
+// the user's global excludes, read the first time a repository needs them
+// (`repo`, whose config could say where they are)
+fn global_excludes(repo: &Path) -> &'static str {
+    static EXCLUDES: OnceLock<String> = OnceLock::new();
+    EXCLUDES.get_or_init(|| {
+        let path = git::config_path(repo, "core.excludesFile").or_else(|| {
+            let config_dir = match env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+                Some(dir) => PathBuf::from(dir),
+                None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+            };
+            Some(config_dir.join("git").join("ignore"))
+        });
+        let mut contents = path
+            .and_then(|path| fs::read_to_string(path).ok())
+            .unwrap_or_default();
+        contents.push('\n');
+        contents
+    })
+}
+
+impl Rule {
+    fn parse(line: &str) -> Option<Rule> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+
+        let (negated, pattern) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        // "\!" and "\#" start a pattern with a literal character
+        let pattern = pattern.strip_prefix('\\').unwrap_or(pattern);
+        let (dir_only, pattern) = match pattern.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, pattern),
+        };
+
+        // a pattern with a '/' in it is relative to the .gitignore's directory,
+        // one without matches a name at any depth
+        let glob = if let Some(rooted) = pattern.strip_prefix('/') {
+            Glob::new(rooted)
+        } else if pattern.contains('/') {
+            Glob::new(pattern)
+        } else {
+            Glob::new(&format!("**/{pattern}"))
+        };
+
+        Some(Rule {
+            glob: glob.ok()?,
+            negated,
+            dir_only,
+        })
+    }
+}
+
+// --glob filters, relative to the root being walked: a file has to match one
+// of the plain globs (if there are any), and files matching a "!glob" are left
+// out - as with ignore rules the last glob to match decides
+pub struct Globs {
+    rules: Ignore,
+    has_includes: bool,
+}
+
+impl Globs {
+    pub fn new(root: &Path, globs: &[String]) -> Globs {
+        Globs {
+            rules: Ignore::parse(root, &globs.join("\n")),
+            has_includes: globs.iter().any(|glob| !glob.starts_with('!')),
+        }
+    }
+
+    // Some(true) to search the path whatever the ignore files say, Some(false)
+    // to skip it, None to leave it to the ignore files
+    // include globs pick out files, so they never stop a directory being entered
+    pub fn selects(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        match self.rules.matched(path, is_dir) {
+            Some(selected) => Some(selected),
+            None if self.has_includes && !is_dir => Some(false),
+            None => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GITIGNORE: &str = "\
+# build output
+/target
+*.log
+!keep.log
+docs/*.html
+node_modules/
+";
+
+    fn ignored(path: &str, is_dir: bool) -> Option<bool> {
+        let ignore = Ignore::parse(Path::new("repo"), GITIGNORE);
+        ignore.matched(&Path::new("repo").join(path), is_dir)
+    }
+
+    #[test]
+    fn patterns_without_a_slash_match_at_any_depth() {
+        assert_eq!(Some(true), ignored("debug.log", false));
+        assert_eq!(Some(true), ignored("src/debug.log", false));
+        assert_eq!(Some(true), ignored("web/node_modules", true));
+        assert_eq!(None, ignored("web/node_modules", false));
+    }
+
+    #[test]
+    fn patterns_with_a_slash_are_rooted() {
+        assert_eq!(Some(true), ignored("target", true));
+        assert_eq!(None, ignored("src/target", true));
+        assert_eq!(Some(true), ignored("docs/index.html", false));
+        assert_eq!(None, ignored("src/docs/index.html", false));
+    }
+
+    #[test]
+    fn globs_select_and_exclude_in_order() {
+        let globs = ["*.rs", "!build.rs", "!target"].map(String::from);
+        let globs = Globs::new(Path::new("root"), &globs);
+        let selects = |path: &str, is_dir| globs.selects(&Path::new("root").join(path), is_dir);
+
+        assert_eq!(Some(true), selects("src/main.rs", false));
+        assert_eq!(Some(false), selects("build.rs", false));
+        assert_eq!(Some(false), selects("README.md", false));
+        assert_eq!(Some(false), selects("target", true));
+        assert_eq!(None, selects("src", true));
+    }
+
+    #[test]
+    fn negation_overrides_earlier_rules() {
+        assert_eq!(Some(false), ignored("keep.log", false));
+        assert_eq!(Some(false), ignored("logs/keep.log", false));
+        assert_eq!(None, ignored("src/main.rs", false));
+    }
+}
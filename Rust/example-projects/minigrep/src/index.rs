@@ -0,0 +1,485 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use regex_syntax::hir::literal::{ExtractKind, Extractor};
+
+use crate::{pool, MatcherOptions};
+
+// minigrep index and minigrep query: a trigram index of the files under a
+// directory, so a search only has to read the files that could match. Each
+// run of three bytes in a file (with ASCII letters folded to lowercase) is
+// listed with the files it's in; a query works out which trigrams any match
+// has to contain and searches just the files having all of them, the usual
+// way - so the index only ever narrows the files down, and what's printed is
+// the same as searching all of them.
+//
+// Files changed since the index was built are searched whatever they contain,
+// and files added since aren't known about until it's built again.
+
+// kept in the indexed directory
+pub const FILE_NAME: &str = ".minigrep-index";
+
+const MAGIC: &[u8] = b"minigrep index 1\n";
+
+// what was indexed, for saying so
+pub struct Built {
+    pub files: usize,
+    pub trigrams: usize,
+}
+
+// index `files` (the walk of `dir`) into dir/.minigrep-index, reading them on
+// `threads` threads
+pub fn build(dir: &str, files: &[(String, bool)], threads: usize) -> io::Result<Built> {
+    let mut entries = Vec::with_capacity(files.len());
+    let mut postings: HashMap<u32, Vec<u32>> = HashMap::new();
+    let mut failed = None;
+    pool::for_each(
+        files,
+        threads,
+        true,
+        |(path, _)| -> io::Result<_> {
+            let metadata = fs::metadata(path)?;
+            let contents = fs::read(path)?;
+            // text that isn't UTF-8 could be decoded into anything, so it's
+            // searched every time
+            let trigrams = std::str::from_utf8(&contents)
+                .is_ok()
+                .then(|| trigrams_of(&contents));
+            Ok((Stamp::of(&metadata), trigrams))
+        },
+        |index, read| {
+            let name = &files[index].0;
+            match read {
+                Ok((stamp, trigrams)) => {
+                    let id = entries.len() as u32;
+                    let relative = Path::new(name).strip_prefix(dir).unwrap_or(Path::new(name));
+                    entries.push(Entry {
+                        path: relative.to_string_lossy().into_owned(),
+                        stamp,
+                        indexed: trigrams.is_some(),
+                    });
+                    for trigram in trigrams.into_iter().flatten() {
+                        postings.entry(trigram).or_default().push(id);
+                    }
+                    true
+                }
+                // a file that's gone since the walk found it isn't worth indexing
+                Err(err) if err.kind() == io::ErrorKind::NotFound => true,
+                Err(err) => {
+                    failed = Some(err);
+                    false
+                }
+            }
+        },
+    );
+    if let Some(err) = failed {
+        return Err(err);
+    }
+
+    let mut trigrams: Vec<(u32, Vec<u32>)> = postings.into_iter().collect();
+    trigrams.sort_unstable_by_key(|(trigram, _)| *trigram);
+    let mut out = Vec::new();
+    out.extend_from_slice(MAGIC);
+    put(&mut out, entries.len() as u32);
+    for entry in &entries {
+        put(&mut out, entry.path.len() as u32);
+        out.extend_from_slice(entry.path.as_bytes());
+        out.extend_from_slice(&entry.stamp.modified.to_le_bytes());
+        out.extend_from_slice(&entry.stamp.len.to_le_bytes());
+        out.push(entry.indexed as u8);
+    }
+    // a table of (trigram, where its files start, how many) sorted by
+    // trigram, so a query can look up the few it needs without reading the rest
+    put(&mut out, trigrams.len() as u32);
+    let mut start = 0;
+    for (trigram, ids) in &trigrams {
+        put(&mut out, *trigram);
+        put(&mut out, start);
+        put(&mut out, ids.len() as u32);
+        start += ids.len() as u32;
+    }
+    for (_, ids) in &trigrams {
+        for id in ids {
+            put(&mut out, *id);
+        }
+    }
+
+    // written alongside and then renamed, so a query never reads half an index
+    let path = Path::new(dir).join(FILE_NAME);
+    let partial = path.with_extension("partial");
+    fs::File::create(&partial)?.write_all(&out)?;
+    fs::rename(&partial, &path)?;
+    Ok(Built {
+        files: entries.len(),
+        trigrams: trigrams.len(),
+    })
+}
+
+// the files under `dir` the patterns could match in, as paths under it
+// (`everything` for a search that needs to see every file anyway, like -v)
+pub fn candidates(
+    dir: &str,
+    patterns: &[String],
+    options: &MatcherOptions,
+    everything: bool,
+) -> io::Result<Vec<String>> {
+    let index = Index::open(dir)?;
+    let query = if everything || options.fuzzy.is_some() {
+        None
+    } else {
+        Query::new(patterns, options)
+    };
+    let matching = query.map(|query| index.files_for(&query));
+    let mut paths = Vec::new();
+    for (id, entry) in index.entries.iter().enumerate() {
+        let path = Path::new(dir).join(&entry.path);
+        let Ok(metadata) = fs::metadata(&path) else {
+            continue;
+        };
+        let changed = Stamp::of(&metadata) != entry.stamp;
+        let found = matching
+            .as_ref()
+            .is_none_or(|ids| ids.binary_search(&(id as u32)).is_ok());
+        if found || changed || !entry.indexed {
+            paths.push(path.to_string_lossy().into_owned());
+        }
+    }
+    Ok(paths)
+}
+
+// when a file was last changed, and how big it was
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Stamp {
+    // nanoseconds since the epoch
+    modified: u64,
+    len: u64,
+}
+
+impl Stamp {
+    fn of(metadata: &fs::Metadata) -> Stamp {
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |since| since.as_nanos() as u64);
+        Stamp {
+            modified,
+            len: metadata.len(),
+        }
+    }
+}
+
+struct Entry {
+    path: String,
+    stamp: Stamp,
+    // false if the trigrams weren't taken, so it's always searched
+    indexed: bool,
+}
+
+struct Index {
+    entries: Vec<Entry>,
+    bytes: Vec<u8>,
+    // where the table of trigrams starts, how long it is and where the lists
+    // of files it points into start
+    table: usize,
+    trigrams: usize,
+    postings: usize,
+}
+
+impl Index {
+    fn open(dir: &str) -> io::Result<Index> {
+        let bytes = fs::read(Path::new(dir).join(FILE_NAME)).map_err(|err| {
+            if err.kind() == io::ErrorKind::NotFound {
+                let message = format!("no index, build one with `minigrep index {dir}`");
+                io::Error::new(io::ErrorKind::NotFound, message)
+            } else {
+                err
+            }
+        })?;
+        Index::parse(bytes).ok_or_else(|| {
+            let message =
+                format!("the index is damaged, build it again with `minigrep index {dir}`");
+            io::Error::new(io::ErrorKind::InvalidData, message)
+        })
+    }
+
+    fn parse(bytes: Vec<u8>) -> Option<Index> {
+        if !bytes.starts_with(MAGIC) {
+            return None;
+        }
+        let mut at = MAGIC.len();
+        let count = take(&bytes, &mut at)?;
+        let mut entries = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let len = take(&bytes, &mut at)? as usize;
+            let path = std::str::from_utf8(bytes.get(at..at + len)?)
+                .ok()?
+                .to_string();
+            at += len;
+            let stamp = Stamp {
+                modified: take_u64(&bytes, &mut at)?,
+                len: take_u64(&bytes, &mut at)?,
+            };
+            let indexed = *bytes.get(at)? == 1;
+            at += 1;
+            entries.push(Entry {
+                path,
+                stamp,
+                indexed,
+            });
+        }
+        let trigrams = take(&bytes, &mut at)? as usize;
+        let postings = at + trigrams * 12;
+        (postings <= bytes.len()).then_some(Index {
+            entries,
+            bytes,
+            table: at,
+            trigrams,
+            postings,
+        })
+    }
+
+    // the ids of the files (in order) with every one of the trigrams
+    fn files_with(&self, trigrams: &[u32]) -> Vec<u32> {
+        let mut files: Option<Vec<u32>> = None;
+        for trigram in trigrams {
+            let with = self.posting(*trigram);
+            files = Some(match files {
+                Some(files) => files
+                    .into_iter()
+                    .filter(|id| with.binary_search(id).is_ok())
+                    .collect(),
+                None => with,
+            });
+            if files.as_ref().is_some_and(Vec::is_empty) {
+                break;
+            }
+        }
+        files.unwrap_or_else(|| (0..self.entries.len() as u32).collect())
+    }
+
+    // the files with `trigram` in them
+    fn posting(&self, trigram: u32) -> Vec<u32> {
+        let record = |index: usize| {
+            let mut at = self.table + index * 12;
+            [(); 3].map(|_| take(&self.bytes, &mut at).unwrap_or(0))
+        };
+        let (mut low, mut high) = (0, self.trigrams);
+        while low < high {
+            let middle = (low + high) / 2;
+            let [key, start, count] = record(middle);
+            if key == trigram {
+                let mut at = self.postings + start as usize * 4;
+                return (0..count)
+                    .filter_map(|_| take(&self.bytes, &mut at))
+                    .collect();
+            }
+            if key < trigram {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+        Vec::new()
+    }
+
+    // the ids of the files (in order) the query could match in
+    fn files_for(&self, query: &Query) -> Vec<u32> {
+        let mut files = Vec::new();
+        for pattern in &query.patterns {
+            let mut found: Option<Vec<u32>> = None;
+            for any_of in pattern {
+                let mut with: Vec<u32> = any_of
+                    .iter()
+                    .flat_map(|trigrams| self.files_with(trigrams))
+                    .collect();
+                with.sort_unstable();
+                with.dedup();
+                found = Some(match found {
+                    Some(found) => found
+                        .into_iter()
+                        .filter(|id| with.binary_search(id).is_ok())
+                        .collect(),
+                    None => with,
+                });
+            }
+            files.extend(found.unwrap_or_else(|| (0..self.entries.len() as u32).collect()));
+        }
+        files.sort_unstable();
+        files.dedup();
+        files
+    }
+}
+
+// What a file has to contain for any of the patterns to match in it: for
+// each pattern, one of the literals every match starts with and one of those
+// it ends with, as the trigrams of each. None when that's nothing in
+// particular - some pattern could match without three bytes in a row known.
+struct Query {
+    // for each pattern, sets of literals the file needs one of from each
+    patterns: Vec<Vec<Vec<Vec<u32>>>>,
+}
+
+impl Query {
+    fn new(patterns: &[String], options: &MatcherOptions) -> Option<Query> {
+        let mut needs = Vec::new();
+        for pattern in patterns {
+            let pattern = if options.regex {
+                pattern.clone()
+            } else {
+                regex_syntax::escape(pattern)
+            };
+            // a pattern that doesn't parse fails with the matcher
+            let hir = regex_syntax::ParserBuilder::new()
+                .case_insensitive(options.ignore_case)
+                .multi_line(options.multiline)
+                .crlf(options.crlf)
+                .build()
+                .parse(&pattern)
+                .ok()?;
+            let mut need = Vec::new();
+            for kind in [ExtractKind::Prefix, ExtractKind::Suffix] {
+                let seq = Extractor::new().kind(kind).extract(&hir);
+                let literals = seq.literals()?;
+                let mut any_of = Vec::new();
+                for literal in literals {
+                    let trigrams = trigrams_of(literal.as_bytes());
+                    // a literal this short says nothing about the file
+                    if trigrams.is_empty() {
+                        return None;
+                    }
+                    any_of.push(trigrams);
+                }
+                need.push(any_of);
+            }
+            needs.push(need);
+        }
+        Some(Query { patterns: needs })
+    }
+}
+
+// every run of three bytes in `text`, once each, with ASCII folded to lowercase
+fn trigrams_of(text: &[u8]) -> Vec<u32> {
+    let mut trigrams: Vec<u32> = text
+        .windows(3)
+        .map(|window| {
+            let [a, b, c] = [0, 1, 2].map(|i| window[i].to_ascii_lowercase() as u32);
+            a << 16 | b << 8 | c
+        })
+        .collect();
+    trigrams.sort_unstable();
+    trigrams.dedup();
+    trigrams
+}
+
+fn put(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn take(bytes: &[u8], at: &mut usize) -> Option<u32> {
+    let value = u32::from_le_bytes(bytes.get(*at..*at + 4)?.try_into().ok()?);
+    *at += 4;
+    Some(value)
+}
+
+fn take_u64(bytes: &[u8], at: &mut usize) -> Option<u64> {
+    let value = u64::from_le_bytes(bytes.get(*at..*at + 8)?.try_into().ok()?);
+    *at += 8;
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TempDir;
+
+    fn literal() -> MatcherOptions {
+        MatcherOptions::default()
+    }
+
+    fn regex() -> MatcherOptions {
+        MatcherOptions {
+            regex: true,
+            ..Default::default()
+        }
+    }
+
+    fn found(dir: &Path, patterns: &[&str], options: &MatcherOptions) -> Vec<String> {
+        let patterns: Vec<String> = patterns.iter().map(|p| p.to_string()).collect();
+        let dir = dir.to_string_lossy();
+        let mut paths = candidates(&dir, &patterns, options, false).unwrap();
+        paths.sort();
+        paths
+            .iter()
+            .map(|path| {
+                Path::new(path)
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn only_files_that_could_match_are_searched() {
+        let dir = TempDir::new("index");
+        fs::write(dir.join("poem.txt"), "I'm nobody! Who are you?\n").unwrap();
+        fs::write(dir.join("frog.txt"), "How public, like a Frog\n").unwrap();
+        fs::write(dir.join("latin1.txt"), b"caf\xe9\n").unwrap();
+        let files: Vec<(String, bool)> = ["poem.txt", "frog.txt", "latin1.txt"]
+            .iter()
+            .map(|name| (dir.join(name).to_string_lossy().into_owned(), true))
+            .collect();
+        let built = build(&dir.to_string_lossy(), &files, 2).unwrap();
+        assert_eq!(3, built.files);
+
+        // the file that isn't UTF-8 is always searched
+        assert_eq!(
+            vec!["latin1.txt", "poem.txt"],
+            found(&dir, &["nobody"], &literal())
+        );
+        assert_eq!(vec!["latin1.txt"], found(&dir, &["toad"], &literal()));
+        // any of several patterns, regexes and two-byte literals (which can't
+        // narrow anything down)
+        assert_eq!(
+            vec!["frog.txt", "latin1.txt", "poem.txt"],
+            found(&dir, &["nobody", "public"], &literal())
+        );
+        assert_eq!(
+            vec!["frog.txt", "latin1.txt"],
+            found(&dir, &[r"pub\w+c, like"], &regex())
+        );
+        assert_eq!(
+            vec!["frog.txt", "latin1.txt", "poem.txt"],
+            found(&dir, &["a"], &literal())
+        );
+        // ASCII case is folded in the index, so "frog" could be "Frog" - it's
+        // left to the search to say whether that matches
+        let ignore_case = MatcherOptions {
+            ignore_case: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            vec!["frog.txt", "latin1.txt"],
+            found(&dir, &["frog"], &literal())
+        );
+        assert_eq!(
+            vec!["frog.txt", "latin1.txt"],
+            found(&dir, &["frog"], &ignore_case)
+        );
+
+        // a file that's changed since could have anything in it now
+        fs::write(dir.join("poem.txt"), "I'm nobody! Who are you, toad?\n").unwrap();
+        assert_eq!(
+            vec!["latin1.txt", "poem.txt"],
+            found(&dir, &["toad"], &literal())
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(candidates(&dir.to_string_lossy(), &[], &literal(), false).is_err());
+    }
+}
@@ -1,69 +1,2659 @@
+#[cfg(feature = "cli")]
+use std::collections::HashSet;
+#[cfg(feature = "cli")]
 use std::env;
-use std::error::Error;
-use std::fs;
+#[cfg(feature = "cli")]
+use std::fs::{self, File};
+#[cfg(feature = "cli")]
+use std::io::{self, IsTerminal, Read, Write};
+#[cfg(feature = "cli")]
+use std::ops::{Deref, RangeInclusive};
+#[cfg(feature = "cli")]
+use std::path::Path;
+#[cfg(feature = "cli")]
+use std::thread;
+#[cfg(feature = "cli")]
+use std::time::{Duration, Instant};
 
+pub mod color;
+pub mod encoding;
+pub mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod format;
+mod fuzzy;
+// only the command line tool turns it on, or says what it's debugging
+#[cfg_attr(not(feature = "cli"), allow(dead_code, unused_macros, unused_imports))]
+mod log;
+pub mod matcher;
+pub mod printer;
+pub mod search;
+pub mod searcher;
+pub mod sink;
+#[cfg(feature = "tokio")]
+pub mod stream;
+
+// the command line tool around the search: files and directories, the
+// environment, threads and the terminal - without it (--no-default-features)
+// the rest builds for targets that have none of those, like wasm32
+#[cfg(feature = "cli")]
+mod args;
+#[cfg(feature = "cli")]
+pub mod completions;
+#[cfg(feature = "cli")]
+mod config_file;
+#[cfg(feature = "cli")]
+pub mod decode;
+#[cfg(feature = "cli")]
+mod decompress;
+#[cfg(feature = "cli")]
+pub mod edit;
+#[cfg(feature = "cli")]
+mod env_vars;
+#[cfg(feature = "cli")]
+mod git;
+#[cfg(feature = "cli")]
+mod glob;
+#[cfg(feature = "tree-sitter")]
+mod grammar;
+#[cfg(feature = "cli")]
+mod http;
+#[cfg(feature = "cli")]
+mod ignore;
+#[cfg(feature = "cli")]
+mod index;
+#[cfg(feature = "cli")]
+mod memory;
+#[cfg(feature = "cli")]
+mod mmap;
+#[cfg(feature = "cli")]
+mod output;
+#[cfg(feature = "cli")]
+mod pool;
+#[cfg(feature = "cli")]
+mod progress;
+#[cfg(feature = "cli")]
+mod signal;
+#[cfg(feature = "cli")]
+pub mod stats;
+#[cfg(feature = "cli")]
+pub mod syntax;
+#[cfg(feature = "cli")]
+mod tail;
+// a directory of its own for each test that needs files
+#[cfg(all(test, feature = "cli"))]
+mod test_dir;
+#[cfg(feature = "cli")]
+mod tui;
+#[cfg(feature = "cli")]
+pub mod types;
+#[cfg(feature = "cli")]
+pub mod walk;
+#[cfg(feature = "cli")]
+mod watch;
+
+pub use color::Colors;
+pub use encoding::Encoding;
+pub use error::MinigrepError;
+pub use format::{Format, Report};
+pub use log::LogLevel;
+pub use matcher::{Engine, Literals, Matcher, MatcherOptions};
+pub use printer::{Delimiters, Printer};
+pub use search::{
+    has_match, search_context, search_context_within, search_invert, search_lines, search_matches,
+    search_matches_iter, search_reader, search_with, Deadline, Line, Match, SearchOptions,
+};
+pub use searcher::{CaseMode, Feed, Searcher, SearcherBuilder};
+pub use sink::Sink;
+#[cfg(feature = "tokio")]
+pub use stream::{MatchStream, OwnedLine, OwnedMatch};
+
+#[cfg(feature = "cli")]
+pub use completions::Shell;
+#[cfg(feature = "cli")]
+pub use decode::{Decoder, Decoders};
+#[cfg(feature = "cli")]
+pub use stats::{SkipReason, Stats};
+#[cfg(feature = "cli")]
+pub use types::Types;
+#[cfg(feature = "cli")]
+pub use walk::{Walk, WalkOptions};
+
+#[cfg(feature = "cli")]
+const OPTIONS_HELP: &str = "\
+Options:
+  -e, --regexp <pattern>               search for <pattern> (repeat to match any of several)
+  -f, --file <path>                    read patterns from <path>, one per line
+      --only-in <kind>                 only count matches in comments, strings, code or function names (with the tree-sitter feature), going by the file's language (repeatable)
+      --not <pattern>                  lines mustn't match <pattern> (repeat to rule out several)
+      --all-of <pattern>               lines must match <pattern> too (repeat to require several)
+  -E, --extended-regexp                treat the query as a regular expression (the default)
+  -F, --fixed-strings                  treat the query as a literal string
+  -w, --word-regexp                    only match whole words
+  -x, --line-regexp                    only match when the whole line equals the query
+  -i, --ignore-case                    match upper and lower case letters alike
+  -S, --smart-case                     ignore case when the patterns have no capital letters
+  -U, --multiline                      allow matches to span multiple lines
+      --fuzzy <num>                    approximate matching, allowing up to <num> edits
+      --dfa-size-limit <size>          let the regex engine's DFA grow to <size> (like 10M) before it falls back on a slower way
+      --regex-timeout <time>           give up on a file once searching it takes longer than <time> (like 500ms or 2s), with a warning
+      --score                          print the edit distance of each fuzzy match
+      --passthru                       print every line, highlighting the matches
+  -v, --invert-match                   select the lines that do not match
+  -m, --max-count <num>                stop searching a file after <num> matching lines
+      --line-range <range>             only search lines <range> of each file, like 100:500, 100: or :500
+      --max-total <num>                stop searching altogether after <num> matching lines in all
+  -o, --only-matching                  print only the matched parts of each line
+  -q, --quiet                          print nothing, just exit with 0 if anything matched (even after errors)
+      --unique                         print each distinct matching line once (with -o, each distinct match)
+      --unique-per-file                the same, but starting over for each file
+  -l, --files-with-matches             only print the names of files with a match
+  -L, --files-without-match            only print the names of files without a match
+  -c, --count                          print how many lines match in each file, rather than the lines
+      --count-matches                  print how many matches there are in each file (a line can have several)
+      --include-zero                   with -c or --count-matches, print the files searched without a match too, with 0
+  -n, --line-number                    prefix each matching line with its line number
+  -b, --byte-offset                    print the byte offset of each matching line (or match with -o)
+      --column                         print the column of the first match (implies -n)
+  -H, --with-filename                  print the file name with each line, even for a single file
+  -I, --no-filename                    print no file names, even when searching several files
+      --heading                        print each file name once above its lines (the default on a terminal)
+      --no-heading                     print the file name in front of every line, like grep
+  -0, --null                           end file names with a NUL byte instead of ':' or a newline
+  -r, --replace <text>                 print <text> in place of each match ($1 etc. for capture groups)
+      --write                          rewrite the files with the --replace text applied
+      --diff                           show a diff of what --write would change, without writing
+      --interactive                    ask before making each --write change (y/n/a/q)
+      --color <when>                   highlight the matched text: auto (the default, on a terminal unless NO_COLOR is set), always or never
+  -M, --max-columns <num>              leave out lines longer than <num> characters, saying how many matches they had
+      --max-columns-preview            print the start of those lines instead, up to an ellipsis
+      --trim                           print lines without the whitespace they start with
+      --context-separator <sep>        print <sep> between groups of context lines instead of '--' (\\t, \\0 and \\xHH work)
+      --no-context-separator           print nothing between groups of context lines
+      --field-match-separator <sep>    print <sep> after the file name and numbers of a matching line instead of ':' and a space
+      --field-context-separator <sep>  print <sep> after those of a context line instead of '-' and a space
+  -d, --max-depth <num>                descend at most <num> directories below each path
+      --hidden                         search hidden files and directories (names starting with '.')
+      --follow                         follow symbolic links while searching directories
+      --one-file-system                don't descend into directories on other filesystems (mount points)
+      --no-ignore                      also search files listed in .gitignore, .ignore and .minigrepignore, and git's excludes
+      --no-ignore-global               also search files listed in the global git excludes file (core.excludesFile)
+  -g, --glob <glob>                    only search files matching <glob>, skipping matches of !<glob> (repeatable)
+  -t, --type <type>                    only search files of <type>, like rust or py (repeatable)
+  -T, --type-not <type>                don't search files of <type> (repeatable)
+      --type-add <spec>                add to the file types with a name:glob spec, like web:*.html
+      --max-filesize <size>            skip files bigger than <size>, like 500K or 10M (URLs: 64M)
+  -a, --text                           search binary files as if they were text
+      --binary                         search binary files in directories too, reporting only whether they match
+  -z, --search-zip                     search inside compressed files (.gz, .bz2, .xz, .lzma and .zst)
+      --mmap                           search files straight out of memory maps rather than reading them
+      --no-mmap                        always read files into memory instead of mapping them
+      --max-memory <size>              read at most <size> of files into memory at once, like 512M (bigger ones are mapped)
+  -j, --threads <num>                  search with <num> threads (default: one per CPU, up to 12)
+      --ordered                        print the files' results in the order they're found, even on several threads
+      --unordered                      print each file's results as soon as it's searched (the default on a terminal without --sort)
+      --encoding <name>                read files as utf-8, utf-16le, utf-16be or latin1 (default: auto)
+      --crlf                           treat \\r\\n as a line break, so $ matches before the \\r in multiline mode
+      --null-data                      input lines end with NUL bytes instead of newlines, and so do output lines
+      --pre <command>                  search the output of <command> run on each file, like pdftotext
+      --rev <commit>                   search the files git has at <commit> instead of the working tree
+      --sort <key>                     search the files in order of path, modified or size (or none)
+      --sortr <key>                    the same, in reverse order
+      --watch                          keep running, and search files again whenever they change
+      --follow-appends                 keep printing matching lines as they're appended to the files, like tail -f
+      --tui                            browse the matches in a terminal UI, editing the query as you type
+  -O, --output <path>                  write the results to <path> instead of standard output
+      --line-buffered                  print each line as soon as it's found (the default on a terminal)
+      --block-buffered                 print in large blocks, which is faster (the default otherwise)
+      --colors <spec>                  change a color, like match:fg:yellow or path:style:bold (repeatable)
+      --stats                          print how many files, lines and matches were searched and found
+      --pattern-counts                 print how many matched lines each pattern matches, and which never did
+      --format <name>                  print the matches as text (the default), json, csv, tsv or sarif
+      --json                           print json lines the way rg --json does, for tools that read those
+      --debug                          say on stderr which files are searched or skipped (and why), and timings
+      --trace                          say even more than --debug, like every directory read
+      --no-config                      ignore the config file
+  -h, --help                           print this help
+  -V, --version                        print the version
+  -A <num>                             print <num> lines of context after each match
+  -B <num>                             print <num> lines of context before each match
+  -C <num>                             print <num> lines of context before and after each match
+Short flags can be combined, like -in, and values joined on, like -A3 or --max-depth=3
+Everything after -- is a query or path, even when it starts with a dash
+The exit code is 0 if anything matched, 1 if nothing did and 2 if there was an error, or 130 after Ctrl-C
+A path can also be an http(s) URL, which is fetched with curl
+Defaults for the flags can be set in ~/.config/minigrep/config.toml (or the file
+MINIGREP_CONFIG_PATH names), like hidden = true or glob = [\"!target\"], and then in
+MINIGREP_ environment variables, like MINIGREP_HIDDEN=1 or MINIGREP_MAX_DEPTH=3 - flags
+given on the command line come after both, so they win";
+
+// what to do with files that look binary
+#[cfg(feature = "cli")]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum BinaryMode {
+    // skip the ones found in directories, and only report whether a
+    // binary file named on the command line matched
+    #[default]
+    Auto,
+    // search every binary file, but only report whether it matched
+    Report,
+    // search binary files as if they were text
+    Text,
+}
+
+// when to highlight matches, for --color
+#[cfg(feature = "cli")]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ColorChoice {
+    // when printing to a terminal, unless NO_COLOR is set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+// the order to search the files in, for --sort
+#[cfg(feature = "cli")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortBy {
+    Path,
+    Modified,
+    Size,
+}
+
+// something to print instead of searching
+#[cfg(feature = "cli")]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Info {
+    // the usage and options
+    Help(String),
+    Version,
+    // a completion script for the shell
+    Completions(Shell),
+}
+
+// minigrep index and minigrep query
+#[cfg(feature = "cli")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IndexCommand {
+    // index the files under the paths
+    Build,
+    // search the paths' indexes
+    Query,
+}
+
+// what -c and --count-matches count in each file
+#[cfg(feature = "cli")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CountMode {
+    // the selected lines
+    Lines,
+    // the matches in them, every one of a line's
+    Matches,
+}
+
+// which earlier lines make a line a duplicate, for --unique
+#[cfg(feature = "cli")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Unique {
+    // the lines printed for the same file
+    PerFile,
+    // the lines printed for any file
+    Everywhere,
+}
+
+#[cfg(feature = "cli")]
+#[derive(Default)]
 pub struct Config {
-    pub query: String,
-    pub file_path: String,
+    pub patterns: Vec<String>,
+    // patterns a line has to match as well as one of `patterns`, for --all-of
+    pub all_of: Vec<String>,
+    // patterns a line mustn't match, for --not
+    pub not: Vec<String>,
+    // the parts of source files matches have to be in, for --only-in
+    pub only_in: Vec<syntax::Kind>,
+    pub file_paths: Vec<String>,
     pub ignore_case: bool,
+    pub regex: bool,
+    pub word: bool,
+    pub line_regexp: bool,
+    pub multiline: bool,
+    pub fuzzy: Option<usize>,
+    pub dfa_size_limit: Option<usize>,
+    // how long searching a file can take, for --regex-timeout
+    pub regex_timeout: Option<Duration>,
+    pub show_score: bool,
+    pub invert: bool,
+    pub max_count: Option<usize>,
+    // the lines of each file to search, for --line-range
+    pub line_range: Option<RangeInclusive<usize>>,
+    // stop the whole search after this many selected lines (from any file)
+    pub max_total: Option<usize>,
+    pub only_matching: bool,
+    pub quiet: bool,
+    pub files_with_matches: bool,
+    pub files_without_match: bool,
+    // print how many lines (or matches) each file has instead, with -c or
+    // --count-matches
+    pub count: Option<CountMode>,
+    // and print the files that have none, with --include-zero
+    pub include_zero: bool,
+    pub line_number: bool,
+    pub byte_offset: bool,
+    pub column: bool,
+    pub null: bool,
+    pub replace: Option<String>,
+    pub write: bool,
+    pub diff: bool,
+    pub interactive: bool,
+    pub color: ColorChoice,
+    pub colors: Colors,
+    pub before_context: usize,
+    pub after_context: usize,
+    pub passthru: bool,
+    pub max_depth: Option<usize>,
+    pub hidden: bool,
+    pub follow: bool,
+    pub no_ignore: bool,
+    // git's global excludes file is left out
+    pub no_ignore_global: bool,
+    pub globs: Vec<String>,
+    pub types: Vec<String>,
+    pub types_not: Vec<String>,
+    pub type_add: Vec<String>,
+    pub max_filesize: Option<u64>,
+    // don't walk into other filesystems mounted below the paths
+    pub one_file_system: bool,
+    pub binary: BinaryMode,
+    pub search_zip: bool,
+    // None sniffs a byte order mark, and otherwise reads UTF-8
+    pub encoding: Option<Encoding>,
+    pub crlf: bool,
+    pub null_data: bool,
+    // a command each file is run through, searching its output instead
+    pub pre: Option<String>,
+    // search the files as they were at this git revision
+    pub rev: Option<String>,
+    // keep running, searching files again whenever they change
+    pub watch: bool,
+    // after searching, keep printing matching lines as they're appended
+    pub follow_appends: bool,
+    // browse the matches interactively instead of printing them
+    pub tui: bool,
+    // print a summary of the search at the end
+    pub stats: bool,
+    // print how many lines each pattern matched, at the end
+    pub pattern_counts: bool,
+    pub format: Format,
+    // print each file name once above its lines - None does when printing
+    // to a terminal
+    pub heading: Option<bool>,
+    // always or never prefix lines with the file name - None does when
+    // there's more than one file
+    pub with_filename: Option<bool>,
+    // None searches the files in the order they're found
+    pub sort: Option<SortBy>,
+    pub sort_reverse: bool,
+    // leave out matched lines that were already printed
+    pub unique: Option<Unique>,
+    // lines longer than this are left out, or cut short with max_columns_preview
+    pub max_columns: Option<usize>,
+    pub max_columns_preview: bool,
+    pub trim: bool,
+    // --context-separator, --field-match-separator and --field-context-separator
+    pub delimiters: Delimiters,
+    // a file to write the results to instead of stdout
+    pub output: Option<String>,
+    // flush stdout after every line, or only once a block has built up -
+    // None does whichever suits where it's going
+    pub line_buffered: Option<bool>,
+    // the help, the version or a completion script, printed instead of searching
+    pub info: Option<Info>,
+    // build an index of the paths, or search them with theirs
+    pub index: Option<IndexCommand>,
+    // always (or never) search files straight out of a memory map - None
+    // does for big files
+    pub mmap: Option<bool>,
+    // how many threads to search files with - None is one per CPU
+    pub threads: Option<usize>,
+    // print the files' results in walk (or --sort) order when they're searched
+    // on several threads - None does with --sort, or when the results aren't
+    // going to a terminal
+    pub ordered: Option<bool>,
+    // how much of the files can be read into memory at once, in bytes
+    pub max_memory: Option<u64>,
+    // how much to say on stderr about what's searched, skipped and how long it took
+    pub log_level: LogLevel,
 }
 
+#[cfg(feature = "cli")]
 impl Config {
-    pub fn build(mut args: impl Iterator<Item = String>) -> Result<Config, String> {
+    pub fn build(mut args: impl Iterator<Item = String>) -> Result<Config, MinigrepError> {
         // arg 1 - name of the program
         let program_name = match args.next() {
             Some(arg) => arg,
-            None => return Err(format!("unable to find name of program...")),
+            None => {
+                return Err(MinigrepError::MissingArg(
+                    "unable to find name of program...".to_string(),
+                ))
+            }
         };
 
-        let usage_message = 
-            format!("Usage: {} <query> <file_path>\nSet environment variable IGNORE_CASE=1 to do case insesitive searching",
-            program_name);
+        let usage_message =
+            format!("Usage: {program_name} [OPTIONS] <query> [path]...\n       {program_name} [OPTIONS] -e <pattern>... [path]...\n       {program_name} index [OPTIONS] [dir]...\n       {program_name} query [OPTIONS] <query> [dir]...\n{OPTIONS_HELP}");
 
-        // arg 2 - query
-        let query = match args.next() {
-            Some(arg) => arg,
-            None => return Err(format!("query argument not found\n{}", &usage_message)),
+        // flags may appear anywhere - split them out from the positional arguments
+        // (with combined short flags and --flag=value taken apart first)
+        let mut args = args::normalize(args, OPTIONS_HELP)?.into_iter();
+        let mut patterns = Vec::new();
+        let mut pattern_files = Vec::new();
+        let mut all_of = Vec::new();
+        let mut not = Vec::new();
+        let mut only_in = Vec::new();
+        let mut regex = true;
+        let mut word = false;
+        let mut line_regexp = false;
+        let mut ignore_case = false;
+        let mut smart_case = false;
+        let mut multiline = false;
+        let mut fuzzy = None;
+        let mut dfa_size_limit = None;
+        let mut regex_timeout = None;
+        let mut show_score = false;
+        let mut invert = false;
+        let mut max_count = None;
+        let mut line_range = None;
+        let mut max_total = None;
+        let mut only_matching = false;
+        let mut quiet = false;
+        let mut files_with_matches = false;
+        let mut files_without_match = false;
+        let mut count = None;
+        let mut include_zero = false;
+        let mut line_number = false;
+        let mut byte_offset = false;
+        let mut column = false;
+        let mut null = false;
+        let mut replace = None;
+        let mut write = false;
+        let mut diff = false;
+        let mut interactive = false;
+        let mut color = ColorChoice::Auto;
+        let mut colors = Colors::default();
+        let mut before_context = 0;
+        let mut after_context = 0;
+        let mut passthru = false;
+        let mut max_depth = None;
+        let mut hidden = false;
+        let mut follow = false;
+        let mut no_ignore = false;
+        let mut no_ignore_global = false;
+        let mut globs = Vec::new();
+        let mut types = Vec::new();
+        let mut types_not = Vec::new();
+        let mut type_add = Vec::new();
+        let mut max_filesize = None;
+        let mut one_file_system = false;
+        let mut binary = BinaryMode::Auto;
+        let mut search_zip = false;
+        let mut encoding = None;
+        let mut crlf = false;
+        let mut null_data = false;
+        let mut pre = None;
+        let mut rev = None;
+        let mut watch = false;
+        let mut follow_appends = false;
+        let mut tui = false;
+        let mut stats = false;
+        let mut pattern_counts = false;
+        let mut log_level = LogLevel::Off;
+        let mut mmap = None;
+        let mut threads = None;
+        let mut ordered = None;
+        let mut max_memory = None;
+        let mut format = Format::Text;
+        let mut heading = None;
+        let mut with_filename = None;
+        let mut sort = None;
+        let mut sort_reverse = false;
+        let mut unique = None;
+        let mut max_columns = None;
+        let mut max_columns_preview = false;
+        let mut trim = false;
+        let mut delimiters = Delimiters::default();
+        let mut output = None;
+        let mut line_buffered = None;
+        let mut info = None;
+        let mut positional = Vec::new();
+        // whether -e or -f came before the first positional argument
+        let mut patterns_first = false;
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-e" | "--regexp" => patterns.push(parse_value(&arg, args.next())?),
+                "-f" | "--file" => pattern_files.push(parse_value(&arg, args.next())?),
+                "--all-of" => all_of.push(parse_value(&arg, args.next())?),
+                "--not" => not.push(parse_value(&arg, args.next())?),
+                "--only-in" => only_in.push(parse_syntax_kind(&arg, args.next())?),
+                "-E" | "--extended-regexp" => regex = true,
+                "-F" | "--fixed-strings" => regex = false,
+                "-w" | "--word-regexp" => word = true,
+                "-x" | "--line-regexp" => line_regexp = true,
+                "-i" | "--ignore-case" => ignore_case = true,
+                "-S" | "--smart-case" => smart_case = true,
+                // already dealt with by with_defaults
+                "--no-config" => {}
+                "-U" | "--multiline" => multiline = true,
+                "--fuzzy" => fuzzy = Some(parse_number(&arg, args.next())?),
+                "--dfa-size-limit" => {
+                    dfa_size_limit = Some(parse_size(&arg, args.next())? as usize)
+                }
+                "--regex-timeout" => regex_timeout = Some(parse_duration(&arg, args.next())?),
+                "--score" => show_score = true,
+                "--passthru" => passthru = true,
+                "-v" | "--invert-match" => invert = true,
+                "-m" | "--max-count" => max_count = Some(parse_number(&arg, args.next())?),
+                "--line-range" => line_range = Some(parse_line_range(&arg, args.next())?),
+                "--max-total" => max_total = Some(parse_number(&arg, args.next())?),
+                "-o" | "--only-matching" => only_matching = true,
+                "-q" | "--quiet" => quiet = true,
+                "-l" | "--files-with-matches" => files_with_matches = true,
+                "-L" | "--files-without-match" => files_without_match = true,
+                "-c" | "--count" => count = Some(CountMode::Lines),
+                "--count-matches" => count = Some(CountMode::Matches),
+                "--include-zero" => include_zero = true,
+                "-n" | "--line-number" => line_number = true,
+                "-b" | "--byte-offset" => byte_offset = true,
+                "--column" => column = true,
+                "-0" | "--null" => null = true,
+                "-r" | "--replace" => replace = Some(parse_value(&arg, args.next())?),
+                "--write" => write = true,
+                "--diff" => diff = true,
+                "--interactive" => {
+                    interactive = true;
+                    write = true;
+                }
+                "--color" => color = parse_color(&arg, args.next())?,
+                "--colors" => colors
+                    .apply(&parse_value(&arg, args.next())?)
+                    .map_err(|err| format!("{arg}: {err}"))?,
+                "-d" | "--max-depth" => max_depth = Some(parse_number(&arg, args.next())?),
+                "--hidden" => hidden = true,
+                "--follow" => follow = true,
+                "--no-ignore" => no_ignore = true,
+                "--no-ignore-global" => no_ignore_global = true,
+                "-g" | "--glob" => globs.push(parse_value(&arg, args.next())?),
+                "-t" | "--type" => types.push(parse_value(&arg, args.next())?),
+                "-T" | "--type-not" => types_not.push(parse_value(&arg, args.next())?),
+                "--type-add" => type_add.push(parse_value(&arg, args.next())?),
+                "--max-filesize" => max_filesize = Some(parse_size(&arg, args.next())?),
+                "--one-file-system" => one_file_system = true,
+                "-a" | "--text" => binary = BinaryMode::Text,
+                "--binary" => binary = BinaryMode::Report,
+                "-z" | "--search-zip" => search_zip = true,
+                "--encoding" => encoding = parse_encoding(&arg, args.next())?,
+                "--crlf" => crlf = true,
+                "--null-data" => null_data = true,
+                "--pre" => pre = Some(parse_value(&arg, args.next())?),
+                "--rev" => rev = Some(parse_value(&arg, args.next())?),
+                "--watch" => watch = true,
+                "--follow-appends" => follow_appends = true,
+                "--tui" => tui = true,
+                "--stats" => stats = true,
+                "--pattern-counts" => pattern_counts = true,
+                "--debug" => log_level = log_level.max(LogLevel::Debug),
+                "--trace" => log_level = LogLevel::Trace,
+                "--mmap" => mmap = Some(true),
+                "--no-mmap" => mmap = Some(false),
+                "--max-memory" => max_memory = Some(parse_size(&arg, args.next())?),
+                "-j" | "--threads" => match parse_number(&arg, args.next())? {
+                    0 => {
+                        return Err(MinigrepError::InvalidArg(format!(
+                            "{arg} needs at least 1 thread"
+                        )))
+                    }
+                    count => threads = Some(count),
+                },
+                "--format" => format = parse_format(&arg, args.next())?,
+                "--json" => format = Format::JsonLines,
+                "--heading" => heading = Some(true),
+                "--no-heading" => heading = Some(false),
+                "-H" | "--with-filename" => with_filename = Some(true),
+                "-I" | "--no-filename" => with_filename = Some(false),
+                "-M" | "--max-columns" => max_columns = Some(parse_number(&arg, args.next())?),
+                "--max-columns-preview" => max_columns_preview = true,
+                "--trim" => trim = true,
+                "--context-separator" => {
+                    delimiters.context = Some(parse_separator(&arg, args.next())?)
+                }
+                "--no-context-separator" => delimiters.context = None,
+                "--field-match-separator" => {
+                    delimiters.field_match = parse_separator(&arg, args.next())?;
+                    delimiters.before_text = String::new();
+                }
+                "--field-context-separator" => {
+                    delimiters.field_context = parse_separator(&arg, args.next())?;
+                    delimiters.before_text = String::new();
+                }
+                "--ordered" => ordered = Some(true),
+                "--unordered" => ordered = Some(false),
+                "--line-buffered" => line_buffered = Some(true),
+                "--block-buffered" => line_buffered = Some(false),
+                // left out of the help, since it's for packaging more than searching
+                "--generate-completions" => {
+                    let value = parse_value(&arg, args.next())?;
+                    let shell = Shell::from_name(&value).ok_or_else(|| {
+                        format!("{arg} expects bash, zsh, fish or powershell, got '{value}'")
+                    })?;
+                    info = Some(Info::Completions(shell));
+                }
+                "-h" | "--help" => info = Some(Info::Help(usage_message.clone())),
+                "-V" | "--version" => info = Some(Info::Version),
+                // the rest are queries and paths, dashes and all
+                "--" => positional.extend(args.by_ref()),
+                "-O" | "--output" => output = Some(parse_value(&arg, args.next())?),
+                "--unique" => unique = Some(Unique::Everywhere),
+                "--unique-per-file" => unique = Some(Unique::PerFile),
+                "--sort" | "--sortr" => {
+                    sort = parse_sort(&arg, args.next())?;
+                    sort_reverse = arg == "--sortr";
+                }
+                "-A" => after_context = parse_number(&arg, args.next())?,
+                "-B" => before_context = parse_number(&arg, args.next())?,
+                "-C" => {
+                    let context = parse_number(&arg, args.next())?;
+                    before_context = context;
+                    after_context = context;
+                }
+                _ if arg.starts_with('-') && arg != "-" => {
+                    return Err(MinigrepError::InvalidArg(format!(
+                        "{}\n{usage_message}",
+                        args::unknown_flag(&arg, OPTIONS_HELP)
+                    )));
+                }
+                _ => {
+                    if positional.is_empty() {
+                        patterns_first =
+                            !patterns.is_empty() || !pattern_files.is_empty() || !all_of.is_empty();
+                    }
+                    positional.push(arg)
+                }
+            }
+        }
+        if let Some(info) = info {
+            return Ok(Config {
+                info: Some(info),
+                ..Config::default()
+            });
+        }
+        // minigrep index and minigrep query - unless the patterns came first,
+        // so -e index still searches a file called index
+        let index = match positional.first().map(String::as_str) {
+            _ if patterns_first => None,
+            Some("index") => Some(IndexCommand::Build),
+            Some("query") => Some(IndexCommand::Query),
+            _ => None,
         };
+        if index.is_some() {
+            positional.remove(0);
+        }
+        let mut args = positional.into_iter();
 
-        // arg 3 - file path
-        let file_path = match args.next() {
-            Some(arg) => arg,
-            None => return Err(format!("file path argument not found\n{}", &usage_message)),
-        };
+        for pattern_file in &pattern_files {
+            let contents = fs::read_to_string(pattern_file)
+                .map_err(|err| MinigrepError::io(pattern_file, err))?;
+            patterns.extend(parse_pattern_file(&contents));
+        }
+
+        // with only --all-of patterns, the first of them is the one the
+        // others are matched along with
+        if patterns.is_empty() && pattern_files.is_empty() && !all_of.is_empty() {
+            patterns.push(all_of.remove(0));
+        }
 
-        // Environment variables
-        // ignore case
-        let ignore_case = env::var("IGNORE_CASE").is_ok();
+        // arg 2 - query, unless the patterns were given with -e, -f or --all-of
+        if patterns.is_empty() && pattern_files.is_empty() && index != Some(IndexCommand::Build) {
+            match args.next() {
+                Some(arg) => patterns.push(arg),
+                None => {
+                    return Err(MinigrepError::MissingArg(format!(
+                        "query argument not found\n{}",
+                        &usage_message
+                    )))
+                }
+            };
+        }
 
-        return Ok(Config {
-            query,
-            file_path,
+        // arg 3.. - file or directory paths (none means read from stdin, same as "-",
+        // or the whole tree of a git revision, the current directory for --tui
+        // or the one indexed)
+        let mut file_paths: Vec<String> = args.collect();
+        if file_paths.is_empty() {
+            let tree = rev.is_some() || tui || index.is_some();
+            file_paths.push(String::from(if tree { "." } else { "-" }));
+        }
+        if index.is_some()
+            && (write
+                || watch
+                || follow_appends
+                || tui
+                || rev.is_some()
+                || file_paths.iter().any(|path| !Path::new(path).is_dir()))
+        {
+            return Err(MinigrepError::InvalidArg(
+                "minigrep index and minigrep query only take directories, without --write, --watch, --follow-appends, --tui or --rev"
+                    .to_string(),
+            ));
+        }
+
+        if (write || diff) && replace.is_none() {
+            return Err(MinigrepError::InvalidArg(format!(
+                "--write and --diff require --replace\n{}",
+                &usage_message
+            )));
+        }
+        if write && file_paths.iter().any(|path| path == "-") {
+            return Err(MinigrepError::InvalidArg(
+                "--write can't rewrite standard input".to_string(),
+            ));
+        }
+        if write && file_paths.iter().any(|path| http::is_url(path)) {
+            return Err(MinigrepError::InvalidArg(
+                "--write can't rewrite a URL".to_string(),
+            ));
+        }
+        if write && rev.is_some() {
+            return Err(MinigrepError::InvalidArg(
+                "--write can't rewrite a git revision".to_string(),
+            ));
+        }
+        if watch && (write || rev.is_some() || file_paths.iter().any(|path| path == "-")) {
+            return Err(MinigrepError::InvalidArg(
+                "--watch only works on files and directories, without --write or --rev".to_string(),
+            ));
+        }
+        if follow_appends && (write || watch || rev.is_some()) {
+            return Err(MinigrepError::InvalidArg(
+                "--follow-appends can't be combined with --write, --watch or --rev".to_string(),
+            ));
+        }
+        if multiline && !(all_of.is_empty() && not.is_empty()) {
+            return Err(MinigrepError::InvalidArg(
+                "--all-of and --not match lines, so they can't be combined with --multiline"
+                    .to_string(),
+            ));
+        }
+        if !only_in.is_empty() && (multiline || replace.is_some() || tui || follow_appends) {
+            return Err(MinigrepError::InvalidArg(
+                "--only-in can't be combined with --multiline, --replace, --tui or --follow-appends"
+                    .to_string(),
+            ));
+        }
+        if tui && (patterns.len() != 1 || !all_of.is_empty() || !not.is_empty()) {
+            return Err(MinigrepError::InvalidArg(
+                "--tui edits a single query, not several patterns".to_string(),
+            ));
+        }
+        if tui
+            && (write
+                || watch
+                || follow_appends
+                || rev.is_some()
+                || file_paths
+                    .iter()
+                    .any(|path| path == "-" || http::is_url(path)))
+        {
+            return Err(MinigrepError::InvalidArg(
+                "--tui browses files and directories, without --write, --watch, --follow-appends or --rev"
+                    .to_string(),
+            ));
+        }
+        if format != Format::Text
+            && (quiet
+                || files_with_matches
+                || files_without_match
+                || count.is_some()
+                || replace.is_some()
+                || stats
+                || pattern_counts
+                || tui
+                || watch
+                || follow_appends)
+        {
+            return Err(MinigrepError::InvalidArg("--format and --json only apply to printing matching lines, without -q, -l, -L, -c, --replace, --stats, --tui, --watch or --follow-appends".to_string()));
+        }
+        if count.is_some()
+            && (quiet
+                || files_with_matches
+                || files_without_match
+                || replace.is_some()
+                || unique.is_some()
+                || tui
+                || follow_appends)
+        {
+            return Err(MinigrepError::InvalidArg(
+                "-c and --count-matches can't be combined with -q, -l, -L, --replace, --unique, --tui or --follow-appends"
+                    .to_string(),
+            ));
+        }
+        if include_zero && count.is_none() {
+            return Err(MinigrepError::InvalidArg(
+                "--include-zero only applies to -c and --count-matches".to_string(),
+            ));
+        }
+        if pattern_counts
+            && (invert
+                || quiet
+                || files_with_matches
+                || files_without_match
+                || replace.is_some()
+                || tui
+                || follow_appends)
+        {
+            return Err(MinigrepError::InvalidArg(
+                "--pattern-counts counts the matched lines printed, so it can't be combined with -v, -q, -l, -L, --replace, --tui or --follow-appends"
+                    .to_string(),
+            ));
+        }
+        if line_range.is_some() && (write || diff || interactive || tui || follow_appends) {
+            return Err(MinigrepError::InvalidArg(
+                "--line-range can't be combined with --write, --diff, --interactive, --tui or --follow-appends"
+                    .to_string(),
+            ));
+        }
+        if rev.is_some() && matches!(sort, Some(SortBy::Modified | SortBy::Size)) {
+            return Err(MinigrepError::InvalidArg(
+                "--rev can only sort by path".to_string(),
+            ));
+        }
+        if unique.is_some()
+            && (before_context > 0 || after_context > 0 || passthru || format != Format::Text)
+        {
+            return Err(MinigrepError::InvalidArg(
+                "--unique only applies to text output, without context lines".to_string(),
+            ));
+        }
+        if output.is_some() && (quiet || interactive || tui || watch || follow_appends) {
+            return Err(MinigrepError::InvalidArg(
+                "--output can't be combined with -q, --interactive, --tui, --watch or --follow-appends"
+                    .to_string(),
+            ));
+        }
+        if line_buffered == Some(false) && (watch || follow_appends) {
+            return Err(MinigrepError::InvalidArg(
+                "--block-buffered can't be combined with --watch or --follow-appends".to_string(),
+            ));
+        }
+        if interactive && file_paths.iter().any(|path| path == "-") {
+            return Err(MinigrepError::InvalidArg(
+                "--interactive reads answers from standard input".to_string(),
+            ));
+        }
+
+        // with --smart-case case is ignored when no pattern has a capital letter
+        let ignore_case = ignore_case
+            || (smart_case
+                && !patterns
+                    .iter()
+                    .any(|pattern| pattern.chars().any(char::is_uppercase)));
+
+        Ok(Config {
+            patterns,
+            all_of,
+            not,
+            only_in,
+            file_paths,
             ignore_case,
-        });
+            regex,
+            word,
+            line_regexp,
+            multiline,
+            fuzzy,
+            dfa_size_limit,
+            regex_timeout,
+            show_score,
+            invert,
+            max_count,
+            line_range,
+            max_total,
+            only_matching,
+            quiet,
+            files_with_matches,
+            files_without_match,
+            count,
+            include_zero,
+            line_number,
+            byte_offset,
+            column,
+            null,
+            replace,
+            write,
+            diff,
+            interactive,
+            color,
+            colors,
+            before_context,
+            after_context,
+            passthru,
+            max_depth,
+            hidden,
+            follow,
+            no_ignore,
+            no_ignore_global,
+            globs,
+            types,
+            types_not,
+            type_add,
+            max_filesize,
+            one_file_system,
+            binary,
+            search_zip,
+            encoding,
+            crlf,
+            null_data,
+            pre,
+            rev,
+            watch,
+            follow_appends,
+            tui,
+            stats,
+            pattern_counts,
+            format,
+            heading,
+            with_filename,
+            sort,
+            sort_reverse,
+            unique,
+            max_columns,
+            max_columns_preview,
+            trim,
+            delimiters,
+            output,
+            line_buffered,
+            info: None,
+            index,
+            log_level,
+            mmap,
+            threads,
+            ordered,
+            max_memory,
+        })
     }
 }
 
-// note: Box<dyn Error> means a type that implements the Error trait
-pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-    // Read file contents
-    let contents = fs::read_to_string(config.file_path)?;
+// one pattern per line, skipping blank lines and # comments
+#[cfg(feature = "cli")]
+fn parse_pattern_file(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect()
+}
+
+// the value following a flag like "-e foo"
+#[cfg(feature = "cli")]
+fn parse_value(flag: &str, value: Option<String>) -> Result<String, String> {
+    match value {
+        Some(value) => Ok(value),
+        None => Err(format!("{flag} requires a value")),
+    }
+}
+
+// parse the numeric value following a flag like "-A 3"
+#[cfg(feature = "cli")]
+fn parse_number(flag: &str, value: Option<String>) -> Result<usize, String> {
+    let value = parse_value(flag, value)?;
+    value
+        .parse()
+        .map_err(|_| format!("{flag} expects a number, got '{value}'"))
+}
+
+// a --line-range like 100:500, 100: (to the end) or :500 (from the start),
+// or just 100 for the one line
+#[cfg(feature = "cli")]
+fn parse_line_range(flag: &str, value: Option<String>) -> Result<RangeInclusive<usize>, String> {
+    let value = parse_value(flag, value)?;
+    let (start, end) = value.split_once(':').unwrap_or((&value, &value));
+    let number = |text: &str, open: usize| match text {
+        "" => Some(open),
+        text => text.parse().ok().filter(|&number| number > 0),
+    };
+    match (number(start, 1), number(end, usize::MAX)) {
+        (Some(start), Some(end)) if start <= end && !value.is_empty() => Ok(start..=end),
+        _ => Err(format!(
+            "{flag} expects a range of line numbers like 100:500, 100: or :500, got '{value}'"
+        )),
+    }
+}
+
+// a separator like --context-separator's, which can be empty and have \t,
+// \n, \0 or \xHH escapes in it for what's awkward to type
+#[cfg(feature = "cli")]
+fn parse_separator(flag: &str, value: Option<String>) -> Result<String, String> {
+    let value = parse_value(flag, value)?;
+    let mut separator = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            separator.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => separator.push('\t'),
+            Some('n') => separator.push('\n'),
+            Some('r') => separator.push('\r'),
+            Some('0') => separator.push('\0'),
+            Some('\\') => separator.push('\\'),
+            Some('x') => {
+                let hex: String = chars.by_ref().take(2).collect();
+                match u8::from_str_radix(&hex, 16) {
+                    Ok(byte) if byte.is_ascii() && hex.len() == 2 => separator.push(byte as char),
+                    _ => return Err(format!("{flag}: '\\x{hex}' isn't an ASCII character")),
+                }
+            }
+            Some(other) => return Err(format!("{flag}: unknown escape '\\{other}'")),
+            None => return Err(format!("{flag}: '{value}' ends in a lone '\\'")),
+        }
+    }
+    Ok(separator)
+}
+
+// the encoding named after --encoding - "auto" means detect it
+#[cfg(feature = "cli")]
+fn parse_encoding(flag: &str, value: Option<String>) -> Result<Option<Encoding>, String> {
+    let value = parse_value(flag, value)?;
+    if value == "auto" {
+        return Ok(None);
+    }
+    match Encoding::from_name(&value) {
+        Some(encoding) => Ok(Some(encoding)),
+        None => Err(format!("{flag}: unknown encoding '{value}'")),
+    }
+}
+
+// the output format named after --format
+#[cfg(feature = "cli")]
+fn parse_format(flag: &str, value: Option<String>) -> Result<Format, String> {
+    let value = parse_value(flag, value)?;
+    Format::from_name(&value).ok_or_else(|| format!("{flag}: unknown format '{value}'"))
+}
+
+#[cfg(feature = "cli")]
+fn parse_syntax_kind(flag: &str, value: Option<String>) -> Result<syntax::Kind, String> {
+    let value = parse_value(flag, value)?;
+    match syntax::Kind::from_name(&value) {
+        Some(syntax::Kind::Function) if !cfg!(feature = "tree-sitter") => Err(format!(
+            "{flag} {value} needs minigrep built with the tree-sitter feature"
+        )),
+        Some(kind) => Ok(kind),
+        None => Err(format!(
+            "{flag} expects comment, string, code or function, got '{value}'"
+        )),
+    }
+}
 
-    // search contents for query
-    let results = if config.ignore_case {
-        search_case_insensitive(&config.query, &contents)
+#[cfg(feature = "cli")]
+fn parse_color(flag: &str, value: Option<String>) -> Result<ColorChoice, String> {
+    let value = parse_value(flag, value)?;
+    match value.as_str() {
+        "auto" => Ok(ColorChoice::Auto),
+        "always" => Ok(ColorChoice::Always),
+        "never" => Ok(ColorChoice::Never),
+        _ => Err(format!(
+            "{flag} expects auto, always or never, got '{value}'"
+        )),
+    }
+}
+
+// the key named after --sort or --sortr - "none" means walk order
+#[cfg(feature = "cli")]
+fn parse_sort(flag: &str, value: Option<String>) -> Result<Option<SortBy>, String> {
+    let value = parse_value(flag, value)?;
+    match value.as_str() {
+        "path" => Ok(Some(SortBy::Path)),
+        "modified" => Ok(Some(SortBy::Modified)),
+        "size" => Ok(Some(SortBy::Size)),
+        "none" => Ok(None),
+        _ => Err(format!(
+            "{flag} expects path, modified, size or none, got '{value}'"
+        )),
+    }
+}
+
+// parse a size like "10M" - a number of bytes with an optional K, M or G
+// suffix (powers of 1024)
+#[cfg(feature = "cli")]
+fn parse_size(flag: &str, value: Option<String>) -> Result<u64, String> {
+    let value = parse_value(flag, value)?;
+    let (digits, multiplier) = match value.to_ascii_uppercase().chars().last() {
+        Some('K') => (&value[..value.len() - 1], 1 << 10),
+        Some('M') => (&value[..value.len() - 1], 1 << 20),
+        Some('G') => (&value[..value.len() - 1], 1 << 30),
+        _ => (value.as_str(), 1),
+    };
+    digits
+        .parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(multiplier))
+        .ok_or_else(|| format!("{flag} expects a size like 10M, got '{value}'"))
+}
+
+// a --regex-timeout like 500ms, 2s or 1m - a bare number is in seconds
+#[cfg(feature = "cli")]
+fn parse_duration(flag: &str, value: Option<String>) -> Result<Duration, String> {
+    let value = parse_value(flag, value)?;
+    let (number, unit) = match value.find(|c: char| c.is_ascii_alphabetic()) {
+        Some(index) => value.split_at(index),
+        None => (value.as_str(), "s"),
+    };
+    let seconds = match unit {
+        "ms" => 0.001,
+        "s" => 1.0,
+        "m" => 60.0,
+        _ => 0.0,
+    };
+    number
+        .parse::<f64>()
+        .ok()
+        .and_then(|number| Duration::try_from_secs_f64(number * seconds).ok())
+        .filter(|duration| !duration.is_zero())
+        .ok_or_else(|| format!("{flag} expects a time like 500ms or 2s, got '{value}'"))
+}
+
+// the command line with the defaults from the config file and the MINIGREP_
+// environment variables put in after the program name, so the flags that
+// follow win - --no-config leaves out the file
+#[cfg(feature = "cli")]
+pub fn with_defaults(args: impl Iterator<Item = String>) -> Result<Vec<String>, MinigrepError> {
+    let mut args: Vec<String> = args.collect();
+    let given = |flag: &str| {
+        args.iter()
+            .skip(1)
+            .take_while(|arg| *arg != "--")
+            .any(|arg| arg == flag)
+    };
+    // --debug on the command line also says where the defaults came from
+    if given("--trace") {
+        log::set_level(LogLevel::Trace);
+    } else if given("--debug") {
+        log::set_level(LogLevel::Debug);
+    }
+    let mut defaults = Vec::new();
+    if !given("--no-config") {
+        defaults.extend(config_file::flags().map_err(MinigrepError::Config)?);
+    }
+    let env_flags = env_vars::flags(env::vars_os(), OPTIONS_HELP).map_err(MinigrepError::Config)?;
+    if !env_flags.is_empty() {
+        log::debug!("environment flags {env_flags:?}");
+    }
+    defaults.extend(env_flags);
+    let program_name = args.len().min(1);
+    args.splice(program_name..program_name, defaults);
+    Ok(args)
+}
+
+// returns what was searched and found - main sets the exit code like grep,
+// from stats.exit_code (or 2 for any error)
+#[cfg(feature = "cli")]
+pub fn run(config: Config) -> Result<Stats, MinigrepError> {
+    if let Some(info) = &config.info {
+        let mut stdout = io::stdout();
+        match info {
+            Info::Help(usage) => writeln!(stdout, "{usage}")?,
+            Info::Version => writeln!(stdout, "minigrep {}", env!("CARGO_PKG_VERSION"))?,
+            Info::Completions(shell) => {
+                write!(stdout, "{}", completions::script(*shell, OPTIONS_HELP))?
+            }
+        }
+        return Ok(Stats {
+            matched: true,
+            ..Stats::default()
+        });
+    }
+    log::set_level(config.log_level);
+    // Ctrl-C stops the search at the next line, keeping what was found -
+    // except while asking about changes, where it quits as it always did
+    if !config.interactive && !config.tui {
+        signal::catch_interrupt();
+    }
+    let start = Instant::now();
+    // build the matcher for the patterns
+    let matcher_options = MatcherOptions {
+        ignore_case: config.ignore_case,
+        regex: config.regex,
+        word: config.word,
+        line: config.line_regexp,
+        multiline: config.multiline,
+        crlf: config.crlf,
+        fuzzy: config.fuzzy,
+        dfa_size_limit: config.dfa_size_limit,
+    };
+    let all_of = config
+        .all_of
+        .iter()
+        .map(|pattern| Matcher::with_options(pattern, &matcher_options))
+        .collect::<Result<_, _>>()?;
+    let not = config
+        .not
+        .iter()
+        .map(|pattern| Matcher::with_options(pattern, &matcher_options))
+        .collect::<Result<_, _>>()?;
+    let matcher = Matcher::with_patterns(&config.patterns, &matcher_options)?
+        .all_of(all_of)
+        .none_of(not);
+    // each pattern on its own, to count the lines it matches
+    let pattern_counts = if config.pattern_counts {
+        config
+            .patterns
+            .iter()
+            .map(|pattern| Matcher::with_options(pattern, &matcher_options))
+            .collect::<Result<_, _>>()?
     } else {
-        search(&config.query, &contents)
+        Vec::new()
     };
+    log::debug!("built the matcher in {:?}", start.elapsed());
 
-    // output search results
-    for line in results {
-        println!("{line}");
+    // directories are searched recursively
+    let walk_options = WalkOptions {
+        max_depth: config.max_depth,
+        hidden: config.hidden,
+        follow: config.follow,
+        no_ignore: config.no_ignore,
+        no_ignore_global: config.no_ignore_global,
+        globs: config.globs.clone(),
+        types: Types::new(&config.types, &config.types_not, &config.type_add)
+            .map_err(MinigrepError::InvalidArg)?,
+        max_filesize: config.max_filesize,
+        one_file_system: config.one_file_system,
+    };
+    let walking = Instant::now();
+    let mut walked = Stats::default();
+    let threads = config.threads.unwrap_or_else(pool::default_threads);
+    if config.index == Some(IndexCommand::Build) {
+        for dir in &config.file_paths {
+            let files = expand_paths(std::slice::from_ref(dir), &walk_options, &mut walked);
+            let built =
+                index::build(dir, &files, threads).map_err(|err| MinigrepError::io(dir, err))?;
+            println!(
+                "indexed {} ({} trigrams) into {}",
+                stats::plural(built.files, "file", "files"),
+                built.trigrams,
+                Path::new(dir).join(index::FILE_NAME).display()
+            );
+        }
+        return Ok(Stats {
+            matched: true,
+            ..walked
+        });
+    }
+    let mut file_paths = match &config.rev {
+        Some(rev) => {
+            rev_paths(rev, &config.file_paths).map_err(|err| MinigrepError::io(rev, err))?
+        }
+        // every file is needed to say which have no match, or to print the
+        // lines that don't, and the index can't see into what --pre or -z read
+        None if config.index.is_some() => {
+            let everything = config.invert
+                || config.files_without_match
+                || config.include_zero
+                || config.passthru
+                || config.pre.is_some()
+                || config.search_zip;
+            let mut candidates = Vec::new();
+            for dir in &config.file_paths {
+                let found = index::candidates(dir, &config.patterns, &matcher_options, everything)
+                    .map_err(|err| MinigrepError::io(dir, err))?;
+                candidates.extend(found.into_iter().map(|path| (path, true)));
+            }
+            candidates
+        }
+        None => expand_paths(&config.file_paths, &walk_options, &mut walked),
+    };
+    if let Some(sort) = config.sort {
+        sort_paths(&mut file_paths, sort, config.sort_reverse);
     }
+    log::debug!(
+        "found {} to search in {:?}",
+        stats::plural(file_paths.len(), "file", "files"),
+        walking.elapsed()
+    );
+
+    let printer = Printer {
+        // prefix each line with its file name when searching more than one file
+        // (or when watching, since later rounds only search the changed files),
+        // unless -H or -I says otherwise
+        show_filename: config.with_filename.unwrap_or(
+            config.file_paths.len() > 1
+                || file_paths.iter().any(|(_, walked)| *walked)
+                || config.watch,
+        ),
+        line_number: config.line_number,
+        byte_offset: config.byte_offset,
+        column: config.column,
+        // escapes would only get in the way of whatever reads a pipe or a file,
+        // and NO_COLOR (set to anything) turns them off, see https://no-color.org
+        color: match config.color {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                config.output.is_none()
+                    && io::stdout().is_terminal()
+                    && env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+            }
+        },
+        colors: config.colors.clone(),
+        show_score: config.show_score,
+        only_matching: config.only_matching,
+        null: config.null,
+        null_data: config.null_data,
+        // tailed lines can come from any of the files, so they keep the prefix
+        heading: config
+            .heading
+            .unwrap_or_else(|| config.output.is_none() && io::stdout().is_terminal())
+            && !config.follow_appends,
+        max_columns: config.max_columns,
+        max_columns_preview: config.max_columns_preview,
+        trim: config.trim,
+        delimiters: config.delimiters.clone(),
+    };
+    let options = SearchOptions {
+        invert: config.invert,
+        before_context: config.before_context,
+        after_context: config.after_context,
+        multiline: config.multiline,
+        max_count: config.max_count,
+        line_range: config.line_range.clone(),
+        passthru: config.passthru,
+        null_data: config.null_data,
+        total: config
+            .max_total
+            .map(|limit| std::sync::Arc::new(search::TotalLimit::new(limit))),
+        // a deadline of each file's own, once it's searched
+        deadline: None,
+        interrupt: Some(signal::flag()),
+    };
+    // past --max-total there's no point opening another file
+    let total_reached = || options.total.as_ref().is_some_and(|total| total.reached());
+    // what goes between groups of context lines, when there are any
+    let context_break = (options.before_context > 0 || options.after_context > 0)
+        .then_some(printer.delimiters.context.as_deref())
+        .flatten();
+
+    if config.tui {
+        let files = file_paths.into_iter().map(|(path, _)| path).collect();
+        let matched = tui::run(files, &config.patterns[0], &matcher_options, &options)?;
+        return Ok(Stats {
+            matched,
+            ..Stats::default()
+        });
+    }
+
+    // a file given with --output only takes the place of the old one once
+    // everything has been written to it
+    let mut output_file = match &config.output {
+        Some(path) => Some(
+            output::OutputFile::create(Path::new(path))
+                .map_err(|err| MinigrepError::io(path, err))?,
+        ),
+        None => None,
+    };
+    // lines show up as they're found on a terminal, and while following files
+    // that change
+    let mut stdout =
+        output::stdout(config.line_buffered.unwrap_or_else(|| {
+            config.watch || config.follow_appends || io::stdout().is_terminal()
+        }));
+    let out: &mut dyn Write = match &mut output_file {
+        Some(file) => file,
+        None => stdout.as_mut(),
+    };
+    // a line on stderr saying how far the search has got, when it's a terminal
+    // and there are directories to go through
+    let mut out = progress::Progress::new(
+        out,
+        io::stderr().is_terminal()
+            && !config.interactive
+            && !log::enabled(LogLevel::Debug)
+            && file_paths.iter().any(|(_, walked)| *walked),
+    );
+    let mut separators = Separators::default();
+    let mut stats = walked;
+    // an "a" answer in interactive mode carries on into the following files
+    let mut all_remaining = false;
+    // the files to carry on reading with --follow-appends
+    let mut tails = Vec::new();
+    let decoders = Decoders::new(config.pre.as_deref(), config.search_zip);
+    let mut report = Report::begin(config.format, &mut out)?;
+    // the lines (or matches) printed so far, with --unique
+    let mut seen = config.unique.map(|_| HashSet::new());
+
+    // with --watch the files that change are searched again after the first
+    // round, for as long as minigrep runs
+    let mut stamps = watch::Stamps::default();
+    let notifier = config.watch.then(|| {
+        stamps.changed(&file_paths);
+        watch::Notifier::new(&config.file_paths)
+    });
+    let changes = std::iter::from_fn(|| {
+        let notifier = notifier.as_ref()?;
+        loop {
+            let notified = notifier.wait();
+            if signal::interrupted() {
+                return None;
+            }
+            if !notified {
+                continue;
+            }
+            // there's no exit code to give while watching
+            let paths = expand_paths(&config.file_paths, &walk_options, &mut Stats::default());
+            let mut changed = stamps.changed(&paths);
+            if let Some(sort) = config.sort {
+                sort_paths(&mut changed, sort, config.sort_reverse);
+            }
+            if !changed.is_empty() {
+                return Some(changed);
+            }
+        }
+    });
 
+    let searching = Instant::now();
+    let budget = config.max_memory.map(memory::Budget::new);
+    let search = FileSearch {
+        budget: budget.as_ref(),
+        config: &config,
+        matcher: &matcher,
+        pattern_counts: &pattern_counts,
+        printer: &printer,
+        options: &options,
+        decoders: &decoders,
+    };
+    // the files are shared out among threads when each can be searched
+    // without knowing about the others - and with --sort only if what's
+    // found is printed in that order
+    let ordered = config.ordered.unwrap_or_else(|| {
+        config.sort.is_some() || config.output.is_some() || !io::stdout().is_terminal()
+    });
+    let parallel = threads > 1
+        && file_paths.len() > 1
+        && (config.sort.is_none() || ordered)
+        && !config.watch
+        && !config.follow_appends
+        && !config.interactive
+        && report.is_none()
+        && seen.is_none();
+    if parallel {
+        log::debug!("searching {} files on {threads} threads", file_paths.len());
+        let mut failed = None;
+        pool::for_each(
+            &file_paths,
+            threads,
+            ordered,
+            |(path, walked)| {
+                if signal::interrupted() || total_reached() {
+                    return Ok(None);
+                }
+                // printed into a buffer, and written out in one piece
+                let mut buffer = Vec::new();
+                let mut separators = Separators::default();
+                let carried = Carried {
+                    separators: &mut separators,
+                    report: None,
+                    seen: None,
+                    all_remaining: &mut false,
+                };
+                let (result, _) = search.file(path, *walked, &mut buffer, carried)?;
+                // the results count against --max-memory until they're printed -
+                // unless they might have to wait their turn, since the file
+                // they're waiting for could then never fit
+                let reserved = search
+                    .budget
+                    .filter(|_| !ordered)
+                    .map(|budget| budget.reserve(buffer.len() as u64));
+                Ok(Some((result, separators, buffer, reserved)))
+            },
+            |index, searched: Result<_, MinigrepError>| {
+                let written = searched.and_then(|searched| {
+                    // None after Ctrl-C, or for the files left once --max-total
+                    // is reached - the ones still being searched may have
+                    // some of the last matches
+                    let Some((result, next, buffer, _reserved)) = searched else {
+                        return Ok(!signal::interrupted());
+                    };
+                    out.update(&stats, &file_paths[index].0)?;
+                    warn(&mut out, &result.warnings)?;
+                    separators.before(next, context_break, &mut out)?;
+                    out.write_all(&buffer)?;
+                    stats.add(result.stats);
+                    // nothing to print with -q - the first match anywhere answers the question
+                    Ok(!(signal::interrupted() || config.quiet && stats.matched))
+                });
+                written.unwrap_or_else(|err| {
+                    failed = Some(err);
+                    false
+                })
+            },
+        );
+        if let Some(err) = failed {
+            return Err(err);
+        }
+        if config.quiet && stats.matched {
+            stats.elapsed = start.elapsed();
+            return Ok(stats);
+        }
+    } else {
+        for (path, walked) in std::iter::once(file_paths).chain(changes).flatten() {
+            if signal::interrupted() || total_reached() {
+                break;
+            }
+            out.update(&stats, &path)?;
+            let carried = Carried {
+                separators: &mut separators,
+                report: report.as_mut(),
+                seen: seen.as_mut(),
+                all_remaining: &mut all_remaining,
+            };
+            let (result, tail) = search.file(&path, walked, &mut out, carried)?;
+            if let Some(tail) = tail {
+                tails.push((path.clone(), tail));
+            }
+            warn(&mut out, &result.warnings)?;
+            let quit = result.quit;
+            stats.add(result.stats);
+            if config.quiet && stats.matched {
+                stats.elapsed = start.elapsed();
+                return Ok(stats);
+            }
+            if quit {
+                break;
+            }
+        }
+    }
+
+    out.clear()?;
+    if signal::interrupted() {
+        eprintln!("minigrep: interrupted, stopping");
+    }
+    log::debug!(
+        "searched {} in {:?}",
+        stats::plural(stats.files_searched, "file", "files"),
+        searching.elapsed()
+    );
+    stats.elapsed = start.elapsed();
+    if let Some(report) = report {
+        report.end(&mut out, stats.elapsed)?;
+    }
+    if config.stats {
+        writeln!(out, "\n{stats}")?;
+    }
+    if config.pattern_counts {
+        writeln!(out, "\n{}", stats.pattern_counts(&config.patterns))?;
+    }
+    out.flush()?;
+
+    if !tails.is_empty() {
+        follow_appends(&mut tails, &matcher, &options, &printer, &mut out)?;
+    }
+    drop(out);
+    if let Some(file) = output_file {
+        let path = config.output.as_deref().unwrap_or_default();
+        file.commit().map_err(|err| MinigrepError::io(path, err))?;
+    }
+
+    stats.interrupted = signal::interrupted();
+    Ok(stats)
+}
+
+// a file read (through its preprocessor or decompressor, with --pre or -z)
+// and decoded, ready to search
+#[cfg(feature = "cli")]
+struct Loaded<'a> {
+    contents: Text,
+    encoding: Option<Encoding>,
+    binary: bool,
+    // exact when the text is the file's bytes as they are, so replacing can
+    // write it back without mangling anything
+    exact: bool,
+    // the decoder it was read through
+    decoder: Option<&'a dyn Decoder>,
+    // where the file ended, to carry on from there with --follow-appends
+    tail: Option<tail::Tail>,
+    // its part of --max-memory, until it's searched
+    reserved: Option<memory::Reservation<'a>>,
+}
+
+// which separators have been printed so far: "--" (or --context-separator)
+// between groups of context lines, and with headings a blank line between the files instead
+#[cfg(feature = "cli")]
+#[derive(Debug, Default, Clone, Copy)]
+struct Separators {
+    group: bool,
+    heading: bool,
+}
+
+#[cfg(feature = "cli")]
+impl Separators {
+    // print the separator that goes in front of a file printed with separators
+    // of its own, starting from nothing
+    fn before(
+        &mut self,
+        next: Separators,
+        context_break: Option<&str>,
+        out: &mut impl Write,
+    ) -> io::Result<()> {
+        if next.heading {
+            if self.heading {
+                writeln!(out)?;
+            }
+        } else if let Some(separator) = context_break.filter(|_| next.group && self.group) {
+            writeln!(out, "{separator}")?;
+        }
+        self.group |= next.group;
+        self.heading |= next.heading;
+        Ok(())
+    }
+}
+
+// how far FileSearch::stream got with a file
+#[cfg(feature = "cli")]
+enum Streamed {
+    // it was searched as it was read
+    Searched,
+    // it's for load instead, along with the bytes already read if it was binary
+    // or not UTF-8 (None when it's to be mapped, with --mmap)
+    Load(Option<Bytes>),
+    Failed(io::Error),
+}
+
+// the file (or standard input) to stream, or None for one to map with --mmap
+// - reading as it goes is as quick as mapping, without the memory to match
+#[cfg(feature = "cli")]
+fn open_stream(file_path: &str, mmap: Option<bool>) -> io::Result<Option<Box<dyn Read>>> {
+    if file_path == "-" {
+        return Ok(Some(Box::new(io::stdin().lock())));
+    }
+    let file = File::open(file_path)?;
+    let metadata = file.metadata()?;
+    if mmap == Some(true)
+        && metadata.is_file()
+        && mmap::worth_mapping(Path::new(file_path), metadata.len(), mmap)
+    {
+        return Ok(None);
+    }
+    Ok(Some(Box::new(file)))
+}
+
+// what carries over from one file to the next when they're searched in turn
+#[cfg(feature = "cli")]
+struct Carried<'a> {
+    separators: &'a mut Separators,
+    report: Option<&'a mut Report>,
+    // the lines (or matches) printed so far, with --unique
+    seen: Option<&'a mut HashSet<String>>,
+    // an "a" answer in interactive mode carries on into the following files
+    all_remaining: &'a mut bool,
+}
+
+// what searching one file came to
+#[cfg(feature = "cli")]
+#[derive(Debug, Default)]
+struct FileResult {
+    // the file's part of the stats
+    stats: Stats,
+    // for stderr, once the progress line is out of the way
+    warnings: Vec<String>,
+    // a "q" answer in interactive mode
+    quit: bool,
+}
+
+// print the warnings about a file, clearing the progress line first
+#[cfg(feature = "cli")]
+fn warn(out: &mut progress::Progress<impl Write>, warnings: &[String]) -> io::Result<()> {
+    if !warnings.is_empty() {
+        out.clear()?;
+    }
+    for warning in warnings {
+        eprintln!("minigrep: {warning}");
+    }
     Ok(())
 }
 
+// what searching any one file needs, shared by all of them
+#[cfg(feature = "cli")]
+#[derive(Clone, Copy)]
+struct FileSearch<'a> {
+    config: &'a Config,
+    matcher: &'a Matcher,
+    // a matcher for each pattern, with --pattern-counts (or none)
+    pattern_counts: &'a [Matcher],
+    printer: &'a Printer,
+    options: &'a SearchOptions,
+    decoders: &'a Decoders,
+    budget: Option<&'a memory::Budget>,
+}
+
+#[cfg(feature = "cli")]
+impl<'a> FileSearch<'a> {
+    // the decoder to read the file through - with --pre every file is read
+    // through the preprocessor, with -z a compressed file through its
+    // decompressor (unless a decoder built in takes it first)
+    fn decoder(&self, file_path: &str) -> Option<&'a dyn Decoder> {
+        if file_path == "-" || http::is_url(file_path) || self.config.rev.is_some() {
+            None
+        } else {
+            self.decoders.find(Path::new(file_path))
+        }
+    }
+
+    // the lines found in the file's contents - with --only-in, only where the
+    // matches are in the parts of its language asked for (a file of a
+    // language that isn't known never gets this far)
+    fn search<'c>(
+        &self,
+        file_path: &str,
+        contents: &'c str,
+        options: &SearchOptions,
+    ) -> Vec<Vec<Line<'c>>> {
+        if self.config.only_in.is_empty() {
+            return search_context(self.matcher, contents, options);
+        }
+        let within = syntax::regions(Path::new(file_path), contents, &self.config.only_in);
+        search_context_within(self.matcher, contents, options, &within.unwrap_or_default())
+    }
+
+    // whether any line is found, stopping at the first
+    fn has_match(&self, file_path: &str, contents: &str) -> bool {
+        if self.config.only_in.is_empty() {
+            return has_match(self.matcher, contents, self.options);
+        }
+        let options = SearchOptions {
+            before_context: 0,
+            after_context: 0,
+            max_count: Some(1),
+            ..self.options.clone()
+        };
+        !self.search(file_path, contents, &options).is_empty()
+    }
+
+    // count the matched line against each pattern it matches
+    fn count_patterns(&self, stats: &mut Stats, line: &str) {
+        if self.pattern_counts.is_empty() {
+            return;
+        }
+        stats.pattern_lines.resize(self.pattern_counts.len(), 0);
+        for (lines, matcher) in stats.pattern_lines.iter_mut().zip(self.pattern_counts) {
+            if matcher.is_match(line) {
+                *lines += 1;
+            }
+        }
+    }
+
+    // whether the file can be searched a line at a time as it's read: plain
+    // text from a file or standard input, printed as it's found
+    fn streams(&self, file_path: &str, carried: &Carried) -> bool {
+        let config = self.config;
+        !self.options.multiline
+            && config.only_in.is_empty()
+            && !config.diff
+            && !config.write
+            && !config.interactive
+            && !config.follow_appends
+            && carried.report.is_none()
+            && carried.seen.is_none()
+            && config.rev.is_none()
+            && !http::is_url(file_path)
+    }
+
+    // search the file as it's read, so memory use doesn't grow with its size
+    // - unless --mmap asks to map it, or it turns out to be binary or in
+    // another encoding, which leaves it to load after all
+    fn stream<W: Write>(
+        &self,
+        file_path: &str,
+        out: &mut W,
+        separators: &mut Separators,
+        stats: &mut Stats,
+    ) -> Result<Streamed, MinigrepError> {
+        let config = self.config;
+        let mut input = match open_stream(file_path, config.mmap) {
+            Ok(Some(input)) => input,
+            Ok(None) => return Ok(Streamed::Load(None)),
+            Err(err) => return Ok(Streamed::Failed(err)),
+        };
+        // the start, to look for a byte order mark and NUL bytes in - what one
+        // read gives, so lines piped in a few at a time are searched as they come
+        let mut head = vec![0; BINARY_CHECK_LEN];
+        let len = loop {
+            match input.read(&mut head) {
+                Ok(len) => break len,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Ok(Streamed::Failed(err)),
+            }
+        };
+        head.truncate(len);
+        if config.encoding.or_else(|| encoding::sniff(&head)).is_some()
+            || !config.null_data && is_binary(&head)
+        {
+            // reading the rest has to fit in --max-memory, which load sees to
+            if self.budget.is_some() && file_path != "-" {
+                return Ok(Streamed::Load(None));
+            }
+            return Ok(match input.read_to_end(&mut head) {
+                Ok(_) => Streamed::Load(Some(Bytes::Read(head))),
+                Err(err) => Streamed::Failed(err),
+            });
+        }
+
+        let reader = io::BufReader::new(io::Cursor::new(head).chain(input));
+        let file_path = display_name(file_path);
+        log::debug!("{file_path}: searching as it's read");
+
+        // -q, -l and -L only need the first match
+        if config.quiet || config.files_with_matches || config.files_without_match {
+            let options = SearchOptions {
+                before_context: 0,
+                after_context: 0,
+                max_count: Some(1),
+                ..self.options.clone()
+            };
+            let mut found = false;
+            match search_reader(self.matcher, reader, &options, |_, _| {
+                found = true;
+                Ok(())
+            }) {
+                Ok(lines) => stats.lines_searched += lines,
+                Err(err) => return Ok(Streamed::Failed(err)),
+            }
+            stats.files_searched += 1;
+            if found {
+                stats.files_matched += 1;
+            }
+            if config.quiet {
+                stats.matched |= found;
+            } else if found == config.files_with_matches {
+                self.printer.print_path(out, file_path)?;
+                stats.matched = true;
+            }
+            return Ok(Streamed::Searched);
+        }
+
+        // -c and --count-matches only need the lines counted
+        if let Some(mode) = config.count {
+            let options = SearchOptions {
+                before_context: 0,
+                after_context: 0,
+                passthru: false,
+                ..self.options.clone()
+            };
+            match search_reader(self.matcher, reader, &options, |_, line| {
+                if let Line::Matched(m) = line {
+                    stats.matched_lines += 1;
+                    stats.matches += m.spans.iter().filter(|span| !span.is_empty()).count();
+                    self.count_patterns(stats, m.line);
+                }
+                Ok(())
+            }) {
+                Ok(lines) => stats.lines_searched += lines,
+                Err(err) => return Ok(Streamed::Failed(err)),
+            }
+            stats.files_searched += 1;
+            let count = if mode == CountMode::Matches && !config.invert {
+                stats.matches
+            } else {
+                stats.matched_lines
+            };
+            if stats.matched_lines > 0 {
+                stats.files_matched += 1;
+                stats.matched = true;
+            }
+            if count > 0 || config.include_zero {
+                self.printer.print_count(out, file_path, count)?;
+            }
+            return Ok(Streamed::Searched);
+        }
+
+        let mut printed = false;
+        // a failed write is the output's fault, and ends the search
+        let mut output_failed = false;
+        let read = search_reader(self.matcher, reader, self.options, |new_group, line| {
+            if let Line::Matched(m) = line {
+                stats.matched_lines += 1;
+                stats.matches += m.spans.iter().filter(|span| !span.is_empty()).count();
+                self.count_patterns(stats, m.line);
+            }
+            let written = self.print_line(out, file_path, separators, !printed, new_group, line);
+            printed = true;
+            output_failed = written.is_err();
+            written
+        });
+        match read {
+            Ok(lines) => stats.lines_searched += lines,
+            Err(err) if output_failed => return Err(MinigrepError::Output(err)),
+            Err(err) => return Ok(Streamed::Failed(err)),
+        }
+        stats.files_searched += 1;
+        if stats.matched_lines > 0 {
+            stats.files_matched += 1;
+            stats.matched = true;
+        }
+        Ok(Streamed::Searched)
+    }
+
+    // print one of the lines found in a file - after the heading if it's the
+    // file's first, and "--" if it starts a group
+    fn print_line<W: Write>(
+        &self,
+        out: &mut W,
+        file_path: &str,
+        separators: &mut Separators,
+        first: bool,
+        new_group: bool,
+        line: &Line,
+    ) -> io::Result<()> {
+        let (config, printer) = (self.config, self.printer);
+        // with headings a blank line separates the files instead of "--"
+        if first && printer.heading && printer.show_filename {
+            if separators.heading {
+                writeln!(out)?;
+            }
+            printer.print_heading(out, file_path)?;
+            separators.heading = true;
+            separators.group = false;
+        }
+        // separate groups of context lines with "--", including across files
+        if new_group {
+            let has_context = self.options.before_context > 0 || self.options.after_context > 0;
+            if has_context && separators.group {
+                printer.print_context_break(out)?;
+            }
+            separators.group = true;
+        }
+
+        match line {
+            Line::Matched(m) => match &config.replace {
+                Some(template) => {
+                    // only the output changes, the file is left alone
+                    let (replaced, spans) = self.matcher.replace(m.line, &m.spans, template);
+                    let m = Match {
+                        line: &replaced,
+                        spans,
+                        ..m.clone()
+                    };
+                    printer.print_match(out, file_path, &m)
+                }
+                None => printer.print_match(out, file_path, m),
+            },
+            Line::Context(number, byte_offset, text) => {
+                printer.print_context(out, file_path, *number, *byte_offset, text)
+            }
+        }
+    }
+
+    // Read file contents
+    // None for a binary file found in a directory, which is skipped
+    // (or the bytes `read` already got from it, when stream couldn't search it)
+    fn load(
+        &self,
+        file_path: &str,
+        walked: bool,
+        read: Option<Bytes>,
+        decoder: Option<&'a dyn Decoder>,
+    ) -> io::Result<Option<Loaded<'a>>> {
+        let config = self.config;
+        let url = http::is_url(file_path);
+        let (mmap, reserved) = match self.budget {
+            Some(budget) if read.is_none() && config.rev.is_none() && !url && decoder.is_none() => {
+                self.reserve(budget, file_path)?
+            }
+            _ => (config.mmap, None),
+        };
+        let bytes = if let Some(bytes) = read {
+            Ok(bytes)
+        } else if let Some(rev) = &config.rev {
+            git::read_file(rev, file_path).map(Bytes::Read)
+        } else if url {
+            http::fetch(file_path, config.max_filesize.unwrap_or(http::MAX_BODY)).map(Bytes::Read)
+        } else if let Some(decoder) = decoder {
+            decoder.decode(Path::new(file_path)).map(Bytes::Read)
+        } else {
+            read_input(file_path, mmap)
+        }?;
+
+        // a byte order mark (or --encoding) says how to transcode the file - UTF-16
+        // text is full of NUL bytes, so this comes before looking for binary files
+        let encoding = config.encoding.or_else(|| encoding::sniff(&bytes));
+
+        // by default binary files found in a directory are skipped - a binary
+        // file named by the user is searched, but only whether it matched is reported
+        // (with --null-data the NUL bytes are expected)
+        let binary = encoding.is_none() && !config.null_data && is_binary(&bytes);
+        if binary && walked && config.binary == BinaryMode::Auto {
+            log::debug!("{file_path}: skipped, binary (see --binary and --text)");
+            return Ok(None);
+        }
+
+        let tail = if config.follow_appends && file_path != "-" && !url && decoder.is_none() {
+            Some(tail::Tail::after(Path::new(file_path), &bytes)?)
+        } else {
+            None
+        };
+
+        let (contents, exact) = match (encoding, bytes) {
+            (Some(encoding), bytes) => (Text::Owned(encoding::decode(&bytes, encoding)), false),
+            (None, Bytes::Read(bytes)) => match String::from_utf8(bytes) {
+                Ok(contents) => (Text::Owned(contents), !binary),
+                Err(err) => (Text::Owned(lossy(err.as_bytes())), false),
+            },
+            (None, Bytes::Mapped(map)) => match map.into_text() {
+                Ok(contents) => (Text::Mapped(contents), !binary),
+                Err(map) => (Text::Owned(lossy(&map)), false),
+            },
+        };
+        Ok(Some(Loaded {
+            contents,
+            encoding,
+            binary,
+            exact,
+            decoder,
+            tail,
+            reserved,
+        }))
+    }
+
+    // how to read the file within the budget: mapped if it's too big to ever
+    // fit (or would be mapped anyway), otherwise with its size held until
+    // it's been searched
+    fn reserve<'b>(
+        &self,
+        budget: &'b memory::Budget,
+        file_path: &str,
+    ) -> io::Result<(Option<bool>, Option<memory::Reservation<'b>>)> {
+        let mmap = self.config.mmap;
+        if file_path == "-" {
+            return Ok((mmap, None));
+        }
+        let path = Path::new(file_path);
+        let len = fs::metadata(path)?.len();
+        if len > budget.limit() && mmap != Some(false) {
+            if mmap::worth_mapping(path, len, Some(true)) {
+                log::debug!("{file_path}: mapped, it's bigger than --max-memory");
+                return Ok((Some(true), None));
+            }
+            return Err(io::Error::other(format!(
+                "{len} bytes, more than --max-memory allows"
+            )));
+        }
+        if mmap::worth_mapping(path, len, mmap) {
+            return Ok((mmap, None));
+        }
+        Ok((mmap, Some(budget.reserve(len))))
+    }
+
+    // search the file, printing what's found to `out` - with --regex-timeout,
+    // only for as long as that allows
+    fn file<W: Write>(
+        &self,
+        file_path: &str,
+        walked: bool,
+        out: &mut W,
+        carried: Carried,
+    ) -> Result<(FileResult, Option<tail::Tail>), MinigrepError> {
+        let Some(timeout) = self.config.regex_timeout else {
+            return self.search_file(file_path, walked, out, carried);
+        };
+        let deadline = std::sync::Arc::new(Deadline::after(timeout));
+        let options = SearchOptions {
+            deadline: Some(deadline.clone()),
+            ..self.options.clone()
+        };
+        let search = FileSearch {
+            options: &options,
+            ..*self
+        };
+        let (mut result, tail) = search.search_file(file_path, walked, out, carried)?;
+        if deadline.passed() {
+            result.warnings.push(format!(
+                "{}: gave up after --regex-timeout {:?}, the rest of it wasn't searched",
+                display_name(file_path),
+                deadline.timeout()
+            ));
+            result.stats.errors += 1;
+        }
+        Ok((result, tail))
+    }
+
+    fn search_file<W: Write>(
+        &self,
+        file_path: &str,
+        walked: bool,
+        out: &mut W,
+        carried: Carried,
+    ) -> Result<(FileResult, Option<tail::Tail>), MinigrepError> {
+        let (config, matcher, printer, options) =
+            (self.config, self.matcher, self.printer, self.options);
+        let mut result = FileResult::default();
+        let decoder = self.decoder(file_path);
+        let read = if decoder.is_none() && self.streams(file_path, &carried) {
+            match self.stream(file_path, out, carried.separators, &mut result.stats)? {
+                Streamed::Searched => return Ok((result, None)),
+                Streamed::Load(bytes) => Ok(bytes),
+                Streamed::Failed(err) => Err(err),
+            }
+        } else {
+            Ok(None)
+        };
+        let stats = &mut result.stats;
+        let loaded = match read.and_then(|bytes| self.load(file_path, walked, bytes, decoder)) {
+            Ok(Some(loaded)) => loaded,
+            Ok(None) => {
+                stats.skip(file_path, SkipReason::Binary);
+                return Ok((result, None));
+            }
+            // a file found in a directory (not named by the user) is skipped over
+            Err(err) if walked => {
+                result.warnings.push(format!("{file_path}: {err}"));
+                stats.skip(file_path, SkipReason::Unreadable);
+                stats.errors += 1;
+                return Ok((result, None));
+            }
+            Err(err) => return Err(MinigrepError::io(file_path, err)),
+        };
+        let Loaded {
+            contents,
+            encoding,
+            binary,
+            exact,
+            decoder,
+            tail,
+            reserved: _reserved,
+        } = loaded;
+        let rev_path;
+        let file_path = match &config.rev {
+            // named the way git names them, like "HEAD~2:src/lib.rs"
+            Some(rev) => {
+                rev_path = format!("{rev}:{file_path}");
+                &rev_path
+            }
+            None => display_name(file_path),
+        };
+
+        if !exact && (config.diff || config.write) {
+            let reason = if binary {
+                "binary file"
+            } else {
+                "not UTF-8 text"
+            };
+            result
+                .warnings
+                .push(format!("{file_path}: {reason}, not replacing"));
+            result.stats.skip(file_path, SkipReason::NotReplaced);
+            return Ok((result, tail));
+        }
+        if let Some(decoder) = decoder.filter(|_| config.write) {
+            result.warnings.push(format!(
+                "{file_path}: read through {}, not replacing",
+                decoder.name()
+            ));
+            result.stats.skip(file_path, SkipReason::NotReplaced);
+            return Ok((result, tail));
+        }
+        if !config.only_in.is_empty() && !syntax::knows(Path::new(file_path), &config.only_in) {
+            let message = "--only-in doesn't know its language";
+            if !walked {
+                return Err(MinigrepError::InvalidArg(format!("{file_path}: {message}")));
+            }
+            result.warnings.push(format!("{file_path}: {message}"));
+            result.stats.skip(file_path, SkipReason::UnknownLanguage);
+            result.stats.errors += 1;
+            return Ok((result, tail));
+        }
+
+        log::debug!(
+            "{file_path}: searching {} bytes{}{}{}",
+            contents.len(),
+            if matches!(contents, Text::Mapped(_)) {
+                " mapped"
+            } else {
+                ""
+            },
+            encoding.map_or(String::new(), |encoding| format!(" of {encoding:?}")),
+            decoder.map_or(String::new(), |decoder| format!(" from {}", decoder.name()))
+        );
+        stats.files_searched += 1;
+        // counted a terminator at a time, as the search skips most lines
+        let terminator = if config.null_data { b'\0' } else { b'\n' };
+        stats.lines_searched += memchr::memchr_iter(terminator, contents.as_bytes()).count()
+            + usize::from(
+                contents
+                    .as_bytes()
+                    .last()
+                    .is_some_and(|&last| last != terminator),
+            );
+
+        // preview the replacements as a diff, leaving the file alone
+        if config.diff {
+            let template = config.replace.as_deref().unwrap_or_default();
+            let (replaced, count) = edit::replace_contents(matcher, &contents, template);
+            if count > 0 {
+                write!(
+                    out,
+                    "{}",
+                    edit::unified_diff(file_path, &contents, &replaced)
+                )?;
+                stats.files_matched += 1;
+                stats.matched = true;
+            }
+            return Ok((result, tail));
+        }
+
+        // ask about each change before rewriting the file
+        if config.interactive {
+            let template = config.replace.as_deref().unwrap_or_default();
+            let all_remaining = carried.all_remaining;
+            let (replaced, count, answer) =
+                edit::replace_interactive(matcher, &contents, template, |change| {
+                    if *all_remaining {
+                        return edit::Answer::All;
+                    }
+                    ask_about_change(out, file_path, change).unwrap_or(edit::Answer::Quit)
+                });
+            if count > 0 {
+                edit::write_atomic(Path::new(file_path), &replaced)
+                    .map_err(|err| MinigrepError::io(file_path, err))?;
+                writeln!(out, "{file_path}: {count} replacements")?;
+                stats.files_matched += 1;
+                stats.matched = true;
+            }
+            match answer {
+                Some(edit::Answer::All) => *all_remaining = true,
+                Some(edit::Answer::Quit) => result.quit = true,
+                _ => {}
+            }
+            return Ok((result, tail));
+        }
+
+        // rewrite the file in place, reporting how many substitutions were made
+        if config.write {
+            let template = config.replace.as_deref().unwrap_or_default();
+            let (replaced, count) = edit::replace_contents(matcher, &contents, template);
+            if count > 0 {
+                edit::write_atomic(Path::new(file_path), &replaced)
+                    .map_err(|err| MinigrepError::io(file_path, err))?;
+                writeln!(out, "{file_path}: {count} replacements")?;
+                stats.files_matched += 1;
+                stats.matched = true;
+            }
+            return Ok((result, tail));
+        }
+
+        // nothing to print - the first match anywhere answers the question
+        if config.quiet {
+            if self.has_match(file_path, &contents) {
+                stats.files_matched += 1;
+                stats.matched = true;
+            }
+            return Ok((result, tail));
+        }
+
+        // only list the file names - stop at the first match in each file
+        if config.files_with_matches || config.files_without_match {
+            let found = self.has_match(file_path, &contents);
+            if found {
+                stats.files_matched += 1;
+            }
+            if found == config.files_with_matches {
+                printer.print_path(out, file_path)?;
+                stats.matched = true;
+            }
+            return Ok((result, tail));
+        }
+
+        // matching lines of a binary file would only dump garbage on the
+        // terminal - a count of them is fine
+        if binary && config.binary != BinaryMode::Text && config.count.is_none() {
+            // (the other formats only have lines to show, so they leave it out)
+            if self.has_match(file_path, &contents) {
+                if config.format == Format::Text {
+                    writeln!(out, "{file_path}: binary file matches")?;
+                }
+                stats.files_matched += 1;
+                stats.matched = true;
+            }
+            return Ok((result, tail));
+        }
+
+        // search contents for query, then output search results
+        let searched_at = Instant::now();
+        let groups = self.search(file_path, &contents, options);
+        let elapsed = searched_at.elapsed();
+        let matches: Vec<&Match> = groups
+            .iter()
+            .flatten()
+            .filter_map(|line| match line {
+                Line::Matched(m) => Some(m),
+                Line::Context(..) => None,
+            })
+            .collect();
+        // with --passthru a group can be all context
+        if !matches.is_empty() {
+            stats.files_matched += 1;
+            stats.matched = true;
+        }
+        stats.matched_lines += matches.len();
+        stats.matches += matches
+            .iter()
+            .flat_map(|m| &m.spans)
+            .filter(|span| !span.is_empty())
+            .count();
+        for m in &matches {
+            self.count_patterns(stats, m.line);
+        }
+
+        if let Some(mode) = config.count {
+            let count = if mode == CountMode::Matches && !config.invert {
+                matches
+                    .iter()
+                    .flat_map(|m| &m.spans)
+                    .filter(|span| !span.is_empty())
+                    .count()
+            } else {
+                matches.len()
+            };
+            if count > 0 || config.include_zero {
+                printer.print_count(out, file_path, count)?;
+            }
+            return Ok((result, tail));
+        }
+
+        if let Some(report) = carried.report {
+            let searched = format::Searched {
+                path: file_path,
+                contents: &contents,
+                groups: &groups,
+                terminator: if config.null_data { '\0' } else { '\n' },
+                elapsed,
+            };
+            report.file(out, &searched)?;
+            return Ok((result, tail));
+        }
+
+        let groups = match carried.seen {
+            Some(seen) => {
+                if config.unique == Some(Unique::PerFile) {
+                    seen.clear();
+                }
+                dedupe(groups, config.only_matching, seen)
+            }
+            None => groups,
+        };
+
+        let lines = groups
+            .iter()
+            .flat_map(|group| group.iter().enumerate())
+            .enumerate();
+        for (index, (in_group, line)) in lines {
+            self.print_line(
+                out,
+                file_path,
+                carried.separators,
+                index == 0,
+                in_group == 0,
+                line,
+            )?;
+        }
+        Ok((result, tail))
+    }
+}
+
+// print the lines appended to the files that match, until Ctrl-C - only the lines
+// themselves, without context
+#[cfg(feature = "cli")]
+fn follow_appends(
+    tails: &mut [(String, tail::Tail)],
+    matcher: &Matcher,
+    options: &SearchOptions,
+    printer: &Printer,
+    out: &mut impl Write,
+) -> Result<(), MinigrepError> {
+    loop {
+        thread::sleep(tail::POLL_INTERVAL);
+        if signal::interrupted() {
+            return Ok(());
+        }
+        for (path, tail) in tails.iter_mut() {
+            for line in tail.poll().map_err(|err| MinigrepError::io(path, err))? {
+                if matcher.is_match(&line.text) == options.invert {
+                    continue;
+                }
+                let spans = if options.invert {
+                    Vec::new()
+                } else {
+                    matcher.find_spans(&line.text)
+                };
+                let m = Match {
+                    line_number: line.line_number,
+                    byte_offset: line.byte_offset,
+                    line: &line.text,
+                    spans,
+                    score: matcher.score(&line.text),
+                };
+                printer.print_match(out, path, &m)?;
+            }
+        }
+    }
+}
+
+// show a proposed change and read y/n/a/q from stdin, asking again until valid
+// end of input counts as quitting
+#[cfg(feature = "cli")]
+fn ask_about_change(
+    out: &mut impl Write,
+    file_path: &str,
+    change: &edit::Change,
+) -> io::Result<edit::Answer> {
+    writeln!(out, "{file_path}:{}", change.line_number)?;
+    writeln!(out, "-{}", change.old)?;
+    writeln!(out, "+{}", change.new)?;
+    loop {
+        write!(out, "Replace? [y,n,a,q] ")?;
+        out.flush()?;
+
+        let mut input = String::new();
+        if io::stdin().read_line(&mut input)? == 0 {
+            return Ok(edit::Answer::Quit);
+        }
+        if let Some(answer) = edit::Answer::parse(&input) {
+            return Ok(answer);
+        }
+    }
+}
+
+// replace each directory with the files under it, in walk order
+// each path comes with whether it was found by walking a directory
+// the files to search, counting the directories that couldn't be read and
+// what the walk left out in `walked`
+#[cfg(feature = "cli")]
+fn expand_paths(
+    paths: &[String],
+    options: &WalkOptions,
+    walked: &mut Stats,
+) -> Vec<(String, bool)> {
+    let mut expanded = Vec::new();
+    for path in paths {
+        if path == "-" || !Path::new(path).is_dir() {
+            expanded.push((path.clone(), false));
+            continue;
+        }
+        let mut walk = Walk::new(Path::new(path), options);
+        for entry in walk.by_ref() {
+            if signal::interrupted() {
+                break;
+            }
+            match entry {
+                Ok(file) => expanded.push((file.to_string_lossy().into_owned(), true)),
+                // an unreadable directory shouldn't stop the rest of the search
+                Err(err) => {
+                    eprintln!("minigrep: {err}");
+                    walked.errors += 1;
+                }
+            }
+        }
+        for (skipped, reason) in walk.take_skipped() {
+            walked.skip(&skipped.to_string_lossy(), reason);
+        }
+    }
+    expanded
+}
+
+// the files tracked under each path at a git revision - like walking a
+// directory, but the ignore files and filters don't apply (git already chose)
+#[cfg(feature = "cli")]
+fn rev_paths(rev: &str, paths: &[String]) -> io::Result<Vec<(String, bool)>> {
+    let mut expanded = Vec::new();
+    for path in paths {
+        for file in git::list_files(rev, path)? {
+            expanded.push((file, true));
+        }
+    }
+    Ok(expanded)
+}
+
+// put the files in --sort order - the sort is stable, so files that compare
+// equal (or whose metadata can't be read) stay in walk order
+#[cfg(feature = "cli")]
+fn sort_paths(paths: &mut [(String, bool)], sort: SortBy, reverse: bool) {
+    match sort {
+        SortBy::Path => paths.sort_by(|(a, _), (b, _)| Path::new(a).cmp(Path::new(b))),
+        SortBy::Modified => {
+            paths.sort_by_cached_key(|(path, _)| fs::metadata(path).and_then(|m| m.modified()).ok())
+        }
+        SortBy::Size => {
+            paths.sort_by_cached_key(|(path, _)| fs::metadata(path).map(|m| m.len()).ok())
+        }
+    }
+    if reverse {
+        paths.reverse();
+    }
+}
+
+// leave out the matched lines already in `seen` - or with -o, the matches - and
+// add the rest to it, dropping the groups that end up empty
+#[cfg(feature = "cli")]
+fn dedupe<'a>(
+    groups: Vec<Vec<Line<'a>>>,
+    only_matching: bool,
+    seen: &mut HashSet<String>,
+) -> Vec<Vec<Line<'a>>> {
+    groups
+        .into_iter()
+        .map(|group| {
+            group
+                .into_iter()
+                .filter_map(|line| match line {
+                    Line::Matched(m) if only_matching => {
+                        let spans: Vec<_> = m
+                            .spans
+                            .iter()
+                            .filter(|span| !span.is_empty())
+                            .filter(|span| seen.insert(m.line[(*span).clone()].to_string()))
+                            .cloned()
+                            .collect();
+                        (!spans.is_empty()).then_some(Line::Matched(Match { spans, ..m }))
+                    }
+                    Line::Matched(m) => seen.insert(m.line.to_string()).then_some(Line::Matched(m)),
+                    context => Some(context),
+                })
+                .collect::<Vec<_>>()
+        })
+        .filter(|group| !group.is_empty())
+        .collect()
+}
+
+// read the contents of a file, or of stdin when the path is "-"
+// a file's bytes, read into memory or mapped
+#[cfg(feature = "cli")]
+enum Bytes {
+    Read(Vec<u8>),
+    Mapped(mmap::Mmap),
+}
+
+#[cfg(feature = "cli")]
+impl Deref for Bytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Bytes::Read(bytes) => bytes,
+            Bytes::Mapped(map) => map,
+        }
+    }
+}
+
+// a file's text, decoded into a string or UTF-8 already where it's mapped
+#[cfg(feature = "cli")]
+enum Text {
+    Owned(String),
+    Mapped(mmap::MappedText),
+}
+
+#[cfg(feature = "cli")]
+impl Deref for Text {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        match self {
+            Text::Owned(text) => text,
+            Text::Mapped(text) => text,
+        }
+    }
+}
+
+#[cfg(feature = "cli")]
+fn lossy(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+// big files are mapped rather than read, unless --mmap or --no-mmap says
+#[cfg(feature = "cli")]
+fn read_input(file_path: &str, mmap: Option<bool>) -> io::Result<Bytes> {
+    if file_path == "-" {
+        let mut contents = Vec::new();
+        io::stdin().read_to_end(&mut contents)?;
+        return Ok(Bytes::Read(contents));
+    }
+    let mut file = File::open(file_path)?;
+    let metadata = file.metadata()?;
+    if metadata.is_file() && mmap::worth_mapping(Path::new(file_path), metadata.len(), mmap) {
+        match mmap::Mmap::open(&file, metadata.len()) {
+            Ok(map) => return Ok(Bytes::Mapped(map)),
+            Err(err) => log::debug!("{file_path}: couldn't map it ({err}), reading it instead"),
+        }
+    }
+    let mut contents = Vec::new();
+    file.read_to_end(&mut contents)?;
+    Ok(Bytes::Read(contents))
+}
+
+// how much of the start of a file is checked for NUL bytes
+#[cfg(feature = "cli")]
+const BINARY_CHECK_LEN: usize = 8192;
+
+// text files practically never contain NUL bytes, binary files almost always
+// do early on - the same heuristic grep and git use
+#[cfg(feature = "cli")]
+fn is_binary(contents: &[u8]) -> bool {
+    contents[..contents.len().min(BINARY_CHECK_LEN)].contains(&0)
+}
+
+// name used when prefixing output lines
+#[cfg(feature = "cli")]
+fn display_name(file_path: &str) -> &str {
+    if file_path == "-" {
+        "(standard input)"
+    } else {
+        file_path
+    }
+}
+
 // ORIGINAL SEARCH METHOD
 // pub fn search<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
 //     let mut results = Vec::new();
@@ -87,13 +2677,32 @@ pub fn search<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
         .collect()
 }
 
-// optimized search method
+// case insensitive search - delegates to the case folding matcher, since
+// lowercasing every line is slow and gets characters like 'ß' wrong
 pub fn search_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
-    let query = query.to_lowercase();
-    contents
-        .lines()
-        .filter(|line| line.to_lowercase().contains(&query))
-        .collect()
+    let matcher = Matcher::new(query, true, false).expect("an escaped literal is a valid regex");
+    search_with(&matcher, contents)
+}
+
+// like search, but yielding a Match for each line as it's found - with the
+// line's number and the byte ranges of the query in it - so a caller can stop
+// early without the whole of the contents being searched
+pub fn search_iter<'a>(query: &str, contents: &'a str) -> impl Iterator<Item = Match<'a>> {
+    let matcher = Matcher::new(query, false, false).expect("a literal never fails to build");
+    search_matches_iter(matcher, contents)
+}
+
+// the lines containing the query, each with the byte ranges of every place
+// it's found in the line, for a caller to highlight
+pub fn search_spans<'a>(query: &str, contents: &'a str) -> Vec<Match<'a>> {
+    search_iter(query, contents).collect()
+}
+
+// the same, ignoring case (the ranges are into the lines as they are, so a
+// folded character of a different length doesn't shift them)
+pub fn search_case_insensitive_spans<'a>(query: &str, contents: &'a str) -> Vec<Match<'a>> {
+    let matcher = Matcher::new(query, true, false).expect("an escaped literal is a valid regex");
+    search_matches(&matcher, contents)
 }
 
 // TESTS
@@ -127,4 +2736,819 @@ Trust me.";
             search_case_insensitive(query, contents)
         );
     }
+
+    #[test]
+    fn search_iter_yields_matches_as_it_goes() {
+        let contents = "\
+Rust:
+safe, fast, productive.
+Pick three.
+Duct tape, productive duct tape.";
+        let mut matches = search_iter("duct", contents);
+
+        let first = matches.next().unwrap();
+        assert_eq!(
+            (2, "safe, fast, productive."),
+            (first.line_number, first.line)
+        );
+        assert_eq!(vec![15..19], first.spans);
+        let second = matches.next().unwrap();
+        assert_eq!(4, second.line_number);
+        assert_eq!(vec![14..18, 22..26], second.spans);
+        assert!(matches.next().is_none());
+    }
+
+    #[test]
+    fn spans_cover_every_match_in_a_line() {
+        let contents = "\
+Rust:
+safe, fast, productive.
+Duct tape, productive duct tape.";
+        let matches = search_spans("duct", contents);
+        assert_eq!(2, matches.len());
+        assert_eq!(vec![15..19], matches[0].spans);
+        assert_eq!(vec![14..18, 22..26], matches[1].spans);
+
+        // 'İ' is longer than its lowercase, and the ranges still fit the line
+        let matches = search_case_insensitive_spans("rust", "İ RuSt, trust RUST\nno");
+        assert_eq!(1, matches.len());
+        assert_eq!(vec![3..7, 10..14, 15..19], matches[0].spans);
+        let found: Vec<_> = matches[0]
+            .spans
+            .iter()
+            .map(|span| &matches[0].line[span.clone()])
+            .collect();
+        assert_eq!(vec!["RuSt", "rust", "RUST"], found);
+    }
+
+    // what's left without the cli feature (like in a wasm build) searches
+    // and prints pasted text the same way the command line tool does
+    #[test]
+    fn pasted_text_is_searched_and_printed_like_a_file() {
+        let pasted = "I'm Nobody! Who are you?\nAre you nobody, too?\nThen there's a pair of us!\n";
+        let searcher = SearcherBuilder::new()
+            .case(CaseMode::Smart)
+            .context(0, 1)
+            .build(&["nobody".to_string()])
+            .unwrap();
+        let printer = Printer {
+            show_filename: false,
+            line_number: true,
+            byte_offset: false,
+            column: true,
+            color: false,
+            colors: Colors::default(),
+            show_score: false,
+            only_matching: false,
+            null: false,
+            null_data: false,
+            heading: false,
+            max_columns: None,
+            max_columns_preview: false,
+            trim: false,
+            delimiters: Delimiters::default(),
+        };
+        let mut standard = sink::Standard::new(printer, Vec::new());
+        searcher
+            .search_slice_to("pasted", pasted, &mut standard)
+            .unwrap();
+
+        assert_eq!(
+            "1:5: I'm Nobody! Who are you?\n2:9: Are you nobody, too?\n3- Then there's a pair of us!\n",
+            String::from_utf8(standard.into_inner()).unwrap()
+        );
+    }
+}
+
+#[cfg(all(test, feature = "cli"))]
+mod cli_tests {
+    use super::*;
+    use crate::test_dir::TempDir;
+
+    #[test]
+    fn build_collects_all_file_paths() {
+        let args = ["minigrep", "to", "a.txt", "b.txt"].map(String::from);
+        let config = Config::build(args.into_iter()).unwrap();
+
+        assert_eq!(vec!["to"], config.patterns);
+        assert_eq!(vec!["a.txt", "b.txt"], config.file_paths);
+    }
+
+    #[test]
+    fn errors_say_what_kind_they_are() {
+        let build = |args: &[&str]| Config::build(args.iter().map(|arg| arg.to_string()));
+
+        assert!(matches!(
+            build(&["minigrep"]),
+            Err(MinigrepError::MissingArg(_))
+        ));
+        assert!(matches!(
+            build(&["minigrep", "-A"]),
+            Err(MinigrepError::MissingArg(_))
+        ));
+        assert!(matches!(
+            build(&["minigrep", "--frobnicate", "to"]),
+            Err(MinigrepError::InvalidArg(_))
+        ));
+
+        let config = build(&["minigrep", "(unclosed", "poem.txt"]).unwrap();
+        assert!(matches!(run(config), Err(MinigrepError::InvalidPattern(_))));
+        let config = build(&["minigrep", "to", "no-such-file.txt"]).unwrap();
+        match run(config) {
+            Err(MinigrepError::Io { path, source }) => {
+                assert_eq!("no-such-file.txt", path);
+                assert_eq!(io::ErrorKind::NotFound, source.kind());
+            }
+            other => panic!("expected an I/O error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn build_defaults_to_stdin() {
+        let args = ["minigrep", "to"].map(String::from);
+        let config = Config::build(args.into_iter()).unwrap();
+
+        assert_eq!(vec!["-"], config.file_paths);
+    }
+
+    #[test]
+    fn build_parses_context_flags() {
+        let args = ["minigrep", "-C", "2", "to", "-A", "4", "poem.txt"].map(String::from);
+        let config = Config::build(args.into_iter()).unwrap();
+
+        assert_eq!(2, config.before_context);
+        assert_eq!(4, config.after_context);
+        assert_eq!(vec!["poem.txt"], config.file_paths);
+    }
+
+    #[test]
+    fn build_repeated_patterns() {
+        let args = ["minigrep", "-e", "foo", "poem.txt", "-e", "bar"].map(String::from);
+        let config = Config::build(args.into_iter()).unwrap();
+
+        assert_eq!(vec!["foo", "bar"], config.patterns);
+        assert_eq!(vec!["poem.txt"], config.file_paths);
+    }
+
+    #[test]
+    fn build_all_of() {
+        let args = ["minigrep", "--all-of", "foo", "--all-of", "bar", "poem.txt"].map(String::from);
+        let config = Config::build(args.into_iter()).unwrap();
+        assert_eq!(vec!["foo"], config.patterns);
+        assert_eq!(vec!["bar"], config.all_of);
+        assert_eq!(vec!["poem.txt"], config.file_paths);
+
+        // any of the -e patterns, and all of the --all-of ones
+        let args = ["minigrep", "-e", "a", "-e", "b", "--all-of", "c", "index"].map(String::from);
+        let config = Config::build(args.into_iter()).unwrap();
+        assert_eq!(vec!["a", "b"], config.patterns);
+        assert_eq!(vec!["c"], config.all_of);
+        assert_eq!(vec!["index"], config.file_paths);
+
+        let args = ["minigrep", "-U", "--all-of", "a", "--all-of", "b", "c"].map(String::from);
+        assert!(Config::build(args.into_iter()).is_err());
+    }
+
+    #[test]
+    fn build_not() {
+        let args = ["minigrep", "--not", "test", "fn", "src", "--not", "mod"].map(String::from);
+        let config = Config::build(args.into_iter()).unwrap();
+        assert_eq!(vec!["fn"], config.patterns);
+        assert_eq!(vec!["test", "mod"], config.not);
+        assert_eq!(vec!["src"], config.file_paths);
+
+        let args = ["minigrep", "--tui", "--not", "test", "fn"].map(String::from);
+        assert!(Config::build(args.into_iter()).is_err());
+    }
+
+    #[test]
+    fn build_only_in() {
+        let args = [
+            "minigrep",
+            "--only-in",
+            "comment",
+            "--only-in=string",
+            "TODO",
+        ]
+        .map(String::from);
+        let config = Config::build(args.into_iter()).unwrap();
+        assert_eq!(
+            vec![syntax::Kind::Comment, syntax::Kind::String],
+            config.only_in
+        );
+
+        let args = ["minigrep", "--only-in", "function", "TODO"].map(String::from);
+        assert_eq!(
+            cfg!(feature = "tree-sitter"),
+            Config::build(args.into_iter()).is_ok()
+        );
+        let args = ["minigrep", "--only-in", "class", "TODO"].map(String::from);
+        assert!(Config::build(args.into_iter()).is_err());
+        let args = ["minigrep", "-U", "--only-in", "code", "TODO"].map(String::from);
+        assert!(Config::build(args.into_iter()).is_err());
+    }
+
+    #[test]
+    fn pattern_counts_need_matched_lines() {
+        let args = ["minigrep", "--pattern-counts", "-e", "a", "-e", "b"].map(String::from);
+        assert!(Config::build(args.into_iter()).unwrap().pattern_counts);
+
+        for flag in ["-v", "-l", "-q"] {
+            let args = ["minigrep", "--pattern-counts", flag, "a"].map(String::from);
+            assert!(Config::build(args.into_iter()).is_err(), "{flag}");
+        }
+    }
+
+    #[test]
+    fn pattern_file_skips_blanks_and_comments() {
+        let contents = "\
+# deprecated APIs
+old_api
+
+try!
+";
+
+        assert_eq!(vec!["old_api", "try!"], parse_pattern_file(contents));
+    }
+
+    #[test]
+    fn build_fixed_strings() {
+        let args = ["minigrep", "a.b"].map(String::from);
+        assert!(Config::build(args.into_iter()).unwrap().regex);
+
+        let args = ["minigrep", "-F", "a.b"].map(String::from);
+        assert!(!Config::build(args.into_iter()).unwrap().regex);
+    }
+
+    #[test]
+    fn build_parses_max_depth() {
+        let args = ["minigrep", "to", "src", "--max-depth", "2"].map(String::from);
+        let config = Config::build(args.into_iter()).unwrap();
+
+        assert_eq!(Some(2), config.max_depth);
+        assert_eq!(vec!["src"], config.file_paths);
+    }
+
+    #[test]
+    fn build_parses_threads() {
+        let args = ["minigrep", "-j4", "to", "src"].map(String::from);
+        let config = Config::build(args.into_iter()).unwrap();
+        assert_eq!(Some(4), config.threads);
+
+        let args = ["minigrep", "--threads", "0", "to"].map(String::from);
+        assert!(Config::build(args.into_iter()).is_err());
+
+        let args = ["minigrep", "--unordered", "--ordered", "to"].map(String::from);
+        let config = Config::build(args.into_iter()).unwrap();
+        assert_eq!((None, Some(true)), (config.threads, config.ordered));
+    }
+
+    #[test]
+    fn build_index_and_query() {
+        let build = |args: &[&str]| {
+            let args = std::iter::once("minigrep").chain(args.iter().copied());
+            Config::build(args.map(String::from))
+        };
+        let config = build(&["index"]).unwrap();
+        assert_eq!(Some(IndexCommand::Build), config.index);
+        assert_eq!(vec!["."], config.file_paths);
+
+        let config = build(&["query", "-e", "to", "-e", "be", "src"]).unwrap();
+        assert_eq!(Some(IndexCommand::Query), config.index);
+        assert_eq!(vec!["to", "be"], config.patterns);
+        assert_eq!(vec!["src"], config.file_paths);
+
+        // after -e they're paths like any other
+        let config = build(&["-e", "to", "index"]).unwrap();
+        assert_eq!(None, config.index);
+        assert_eq!(vec!["index"], config.file_paths);
+        let config = build(&["index", "poem.txt"]);
+        assert!(config.is_err());
+    }
+
+    #[test]
+    fn build_counts() {
+        let build = |args: &[&str]| {
+            let args = std::iter::once("minigrep").chain(args.iter().copied());
+            Config::build(args.map(String::from))
+        };
+        assert_eq!(None, build(&["to", "poem.txt"]).unwrap().count);
+        assert_eq!(
+            Some(CountMode::Lines),
+            build(&["-ic", "to", "poem.txt"]).unwrap().count
+        );
+        assert_eq!(
+            Some(CountMode::Matches),
+            build(&["--count-matches", "to", "poem.txt"]).unwrap().count
+        );
+        assert!(build(&["-c", "-l", "to", "poem.txt"]).is_err());
+        assert!(
+            build(&["-c", "--include-zero", "to", "poem.txt"])
+                .unwrap()
+                .include_zero
+        );
+        assert!(build(&["--include-zero", "to", "poem.txt"]).is_err());
+    }
+
+    #[test]
+    fn build_parses_line_ranges() {
+        let range = |value: &str| parse_line_range("--line-range", Some(value.to_string()));
+
+        assert_eq!(Ok(100..=500), range("100:500"));
+        assert_eq!(Ok(100..=usize::MAX), range("100:"));
+        assert_eq!(Ok(1..=500), range(":500"));
+        assert_eq!(Ok(7..=7), range("7"));
+        assert!(range("0:5").is_err());
+        assert!(range("500:100").is_err());
+        assert!(range("a:b").is_err());
+    }
+
+    #[test]
+    fn build_parses_separators() {
+        let separator =
+            |value: &str| parse_separator("--context-separator", Some(value.to_string()));
+
+        assert_eq!(Ok("==".to_string()), separator("=="));
+        assert_eq!(Ok(String::new()), separator(""));
+        assert_eq!(Ok("\t|\0".to_string()), separator("\\t|\\x00"));
+        assert_eq!(Ok("\\".to_string()), separator("\\\\"));
+        assert!(separator("\\q").is_err());
+        assert!(separator("\\xff").is_err());
+        assert!(separator("a\\").is_err());
+
+        let config = Config::build(
+            [
+                "minigrep",
+                "--no-context-separator",
+                "--field-match-separator",
+                "\\t",
+                "to",
+                "poem.txt",
+            ]
+            .map(String::from)
+            .into_iter(),
+        )
+        .unwrap();
+        assert_eq!(None, config.delimiters.context);
+        assert_eq!("\t", config.delimiters.field_match);
+        assert_eq!("-", config.delimiters.field_context);
+        assert_eq!("", config.delimiters.before_text);
+    }
+
+    #[test]
+    fn build_parses_durations() {
+        let duration = |value: &str| parse_duration("--regex-timeout", Some(value.to_string()));
+
+        assert_eq!(Ok(Duration::from_millis(500)), duration("500ms"));
+        assert_eq!(Ok(Duration::from_secs(2)), duration("2s"));
+        assert_eq!(Ok(Duration::from_millis(1500)), duration("1.5"));
+        assert_eq!(Ok(Duration::from_secs(60)), duration("1m"));
+        assert!(duration("0s").is_err());
+        assert!(duration("2h").is_err());
+        assert!(duration("soon").is_err());
+    }
+
+    #[test]
+    fn build_parses_sizes() {
+        let size = |value: &str| parse_size("--max-filesize", Some(value.to_string()));
+
+        assert_eq!(Ok(512), size("512"));
+        assert_eq!(Ok(10 * 1024 * 1024), size("10M"));
+        assert_eq!(Ok(2 * 1024), size("2k"));
+        assert!(size("10MB").is_err());
+        assert!(size("M").is_err());
+    }
+
+    #[test]
+    fn nul_bytes_near_the_start_mean_binary() {
+        assert!(!is_binary(b"I'm nobody! Who are you?\n"));
+        assert!(is_binary(b"\x7fELF\x02\x01\x01\x00\x00"));
+
+        let mut late_nul = vec![b'a'; BINARY_CHECK_LEN];
+        late_nul.push(0);
+        assert!(!is_binary(&late_nul));
+    }
+
+    #[test]
+    fn build_binary_modes() {
+        let mode = |flag: &str| {
+            let args = ["minigrep", flag, "to"].map(String::from);
+            Config::build(args.into_iter()).unwrap().binary
+        };
+
+        assert_eq!(BinaryMode::Auto, mode("-n"));
+        assert_eq!(BinaryMode::Text, mode("-a"));
+        assert_eq!(BinaryMode::Report, mode("--binary"));
+    }
+
+    #[test]
+    fn build_smart_case_ignores_case_without_capitals() {
+        let ignore_case = |query: &str| {
+            let args = ["minigrep", "-S", query].map(String::from);
+            Config::build(args.into_iter()).unwrap().ignore_case
+        };
+
+        assert!(ignore_case("nobody"));
+        assert!(!ignore_case("Nobody"));
+    }
+
+    #[test]
+    fn build_color_choices() {
+        let color = |args: &[&str]| {
+            let args = ["minigrep"]
+                .iter()
+                .chain(args)
+                .chain(&["to"])
+                .map(|arg| arg.to_string());
+            Config::build(args)
+                .map(|config| config.color)
+                .map_err(|err| err.to_string())
+        };
+
+        assert_eq!(Ok(ColorChoice::Auto), color(&[]));
+        assert_eq!(Ok(ColorChoice::Always), color(&["--color", "always"]));
+        assert_eq!(Ok(ColorChoice::Never), color(&["--color", "never"]));
+        assert!(color(&["--color", "sometimes"]).is_err());
+    }
+
+    #[test]
+    fn build_rev_searches_the_whole_tree() {
+        let args = ["minigrep", "--rev", "HEAD~1", "to"].map(String::from);
+        let config = Config::build(args.into_iter()).unwrap();
+        assert_eq!(Some("HEAD~1".to_string()), config.rev);
+        assert_eq!(vec!["."], config.file_paths);
+
+        let args = ["minigrep", "--rev", "HEAD", "-r", "x", "--write", "to"].map(String::from);
+        assert!(Config::build(args.into_iter()).is_err());
+    }
+
+    #[test]
+    fn separators_go_between_files_searched_apart() {
+        let mut out = Vec::new();
+        let mut separators = Separators::default();
+        let group = Separators {
+            group: true,
+            heading: false,
+        };
+        // nothing in front of the first file, "--" in front of the next
+        separators.before(group, Some("--"), &mut out).unwrap();
+        separators
+            .before(Separators::default(), Some("--"), &mut out)
+            .unwrap();
+        separators.before(group, Some("--"), &mut out).unwrap();
+        // and a blank line between headings
+        let heading = Separators {
+            group: true,
+            heading: true,
+        };
+        separators.before(heading, Some("--"), &mut out).unwrap();
+        separators.before(heading, Some("--"), &mut out).unwrap();
+        assert_eq!("--\n\n", String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn run_returns_what_it_searched() {
+        let dir = TempDir::new("stats");
+        fs::write(dir.join("poem.txt"), "I'm nobody!\nWho are you?\n").unwrap();
+        fs::write(dir.join("logo.png"), b"\x89PNG\r\n\x1a\n\0\0").unwrap();
+
+        let path = dir.to_string_lossy().into_owned();
+        let args = ["minigrep".to_string(), "zebra".to_string(), path];
+        let stats = run(Config::build(args.into_iter()).unwrap()).unwrap();
+
+        assert!(!stats.matched);
+        assert_eq!(1, stats.files_searched);
+        assert_eq!(2, stats.lines_searched);
+        assert_eq!(
+            vec![(
+                dir.join("logo.png").to_string_lossy().into_owned(),
+                SkipReason::Binary
+            )],
+            stats.skipped
+        );
+    }
+
+    #[test]
+    fn stats_count_what_the_walk_leaves_out() {
+        let dir = TempDir::new("walk-stats");
+        fs::write(dir.join("poem.txt"), "I'm nobody!\n").unwrap();
+        fs::write(dir.join("big.txt"), "nobody\n".repeat(100)).unwrap();
+
+        let args = ["minigrep", "--max-filesize", "100", "nobody"]
+            .map(String::from)
+            .into_iter()
+            .chain([dir.to_string_lossy().into_owned()]);
+        let stats = run(Config::build(args).unwrap()).unwrap();
+
+        assert_eq!(1, stats.files_searched);
+        assert_eq!(
+            vec![(
+                dir.join("big.txt").to_string_lossy().into_owned(),
+                SkipReason::TooBig
+            )],
+            stats.skipped
+        );
+    }
+
+    #[test]
+    fn only_in_needs_a_language_it_knows() {
+        let dir = TempDir::new("only-in");
+        fs::write(dir.join("main.rs"), "// TODO: tidy\nlet todo = \"TODO\";\n").unwrap();
+        fs::write(dir.join("notes.txt"), "TODO: everything\n").unwrap();
+        let only_comments = |path: &Path| {
+            let args = ["minigrep", "--only-in", "comment", "TODO"]
+                .map(String::from)
+                .into_iter()
+                .chain([path.to_string_lossy().into_owned()]);
+            run(Config::build(args).unwrap())
+        };
+
+        // one found in a directory is skipped with a warning
+        let stats = only_comments(&dir).unwrap();
+        assert_eq!(1, stats.files_searched);
+        assert_eq!(1, stats.errors);
+        assert_eq!(
+            vec![(
+                dir.join("notes.txt").to_string_lossy().into_owned(),
+                SkipReason::UnknownLanguage
+            )],
+            stats.skipped
+        );
+        // one named on the command line can't be searched the way it was asked
+        assert!(only_comments(&dir.join("notes.txt")).is_err_and(|err| err.is_usage()));
+    }
+
+    // minigrep searching its standard input in a process of its own, for
+    // what only a whole process shows (how its output is buffered, what a
+    // real Ctrl-C does): this test binary run again for just the `test`
+    // calling it, which there runs the search and exits instead - with the
+    // lines it prints as they come out
+    #[cfg(unix)]
+    fn search_in_child(
+        test: &str,
+        args: &[&str],
+    ) -> (std::process::Child, std::sync::mpsc::Receiver<String>) {
+        use std::io::{BufRead, BufReader};
+        use std::process::{Command, Stdio};
+
+        if env::var_os("MINIGREP_CHILD").is_some() {
+            let args = std::iter::once("minigrep").chain(args.iter().copied());
+            let stats = run(Config::build(args.map(String::from)).unwrap()).unwrap();
+            std::process::exit(stats.exit_code(false));
+        }
+        let mut child = Command::new(env::current_exe().unwrap())
+            .args(["--exact", &format!("cli_tests::{test}"), "--test-threads=1"])
+            .env("MINIGREP_CHILD", "1")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        let (sender, lines) = std::sync::mpsc::channel();
+        thread::spawn(move || {
+            for line in stdout.lines().map_while(Result::ok) {
+                let _ = sender.send(line);
+            }
+        });
+        (child, lines)
+    }
+
+    // whether a line with `text` in it comes out within `timeout`
+    #[cfg(unix)]
+    fn printed(lines: &std::sync::mpsc::Receiver<String>, text: &str, timeout: Duration) -> bool {
+        let waited = Instant::now();
+        while let Some(left) = timeout.checked_sub(waited.elapsed()) {
+            match lines.recv_timeout(left) {
+                Ok(line) if line.contains(text) => return true,
+                Ok(_) => {}
+                Err(_) => return false,
+            }
+        }
+        false
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn interrupt_stops_at_the_next_line_with_exit_code_130() {
+        let (mut child, lines) = search_in_child(
+            "interrupt_stops_at_the_next_line_with_exit_code_130",
+            &["--line-buffered", "us"],
+        );
+        let mut stdin = child.stdin.take().unwrap();
+        writeln!(stdin, "Then there's a pair of us").unwrap();
+        // once the line is out the search is waiting for the next one
+        assert!(printed(&lines, "pair of us", Duration::from_secs(10)));
+        let kill = std::process::Command::new("kill")
+            .args(["-INT", &child.id().to_string()])
+            .status();
+        assert!(kill.unwrap().success());
+
+        // it stops at a line read once the signal's handled, with standard
+        // input still open
+        let waited = Instant::now();
+        let status = loop {
+            if let Some(status) = child.try_wait().unwrap() {
+                break status;
+            }
+            if waited.elapsed() > Duration::from_secs(10) {
+                child.kill().unwrap();
+                panic!("still searching after Ctrl-C");
+            }
+            let _ = writeln!(stdin, "They'd banish us");
+            thread::sleep(Duration::from_millis(10));
+        };
+        assert_eq!(Some(130), status.code());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn line_buffered_output_comes_out_as_each_line_is_found() {
+        let (mut child, lines) = search_in_child(
+            "line_buffered_output_comes_out_as_each_line_is_found",
+            &["--line-buffered", "us"],
+        );
+        let mut stdin = child.stdin.take().unwrap();
+        writeln!(stdin, "Then there's a pair of us").unwrap();
+        let found = printed(&lines, "pair of us", Duration::from_secs(10));
+        drop(stdin);
+        assert!(found);
+        assert_eq!(Some(0), child.wait().unwrap().code());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn block_buffered_output_waits_for_the_buffer() {
+        let (mut child, lines) = search_in_child(
+            "block_buffered_output_waits_for_the_buffer",
+            &["--block-buffered", "us"],
+        );
+        let mut stdin = child.stdin.take().unwrap();
+        writeln!(stdin, "Then there's a pair of us").unwrap();
+        // nothing while the search goes on, then everything once it's done
+        assert!(!printed(&lines, "pair of us", Duration::from_millis(300)));
+        drop(stdin);
+        assert!(printed(&lines, "pair of us", Duration::from_secs(10)));
+        assert_eq!(Some(0), child.wait().unwrap().code());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn quiet_exits_with_0_at_the_first_match_whatever_failed() {
+        let dir = TempDir::new("quiet");
+        // walked first, and an error to follow
+        std::os::unix::fs::symlink(dir.join("missing.txt"), dir.join("a.txt")).unwrap();
+        fs::write(dir.join("b.txt"), "I'm nobody!\n").unwrap();
+        fs::write(dir.join("c.txt"), "Are you nobody, too?\n").unwrap();
+        let quiet = |query: &str| {
+            let args = ["minigrep", "-q", "-j", "1", "--follow", query]
+                .map(String::from)
+                .into_iter()
+                .chain([dir.to_string_lossy().into_owned()]);
+            run(Config::build(args).unwrap()).unwrap()
+        };
+
+        // a match wins over the error, and there's no need to look any
+        // further than the first one
+        let stats = quiet("nobody");
+        assert_eq!(0, stats.exit_code(true));
+        assert_eq!(2, stats.exit_code(false));
+        assert_eq!(1, stats.files_searched);
+
+        let stats = quiet("zebra");
+        assert_eq!(2, stats.exit_code(true));
+        assert_eq!(2, stats.files_searched);
+        fs::remove_file(dir.join("a.txt")).unwrap();
+        assert_eq!(1, quiet("zebra").exit_code(true));
+    }
+
+    #[test]
+    fn include_zero_counts_every_file_searched() {
+        let dir = TempDir::new("include-zero");
+        fs::write(dir.join("a.txt"), "I'm nobody!\nWho are you?\n").unwrap();
+        fs::write(dir.join("b.txt"), "Are you nobody, too?\n").unwrap();
+        fs::write(dir.join("c.txt"), "They'd banish us, you know.\n").unwrap();
+        fs::write(dir.join("logo.png"), b"\x89PNG\r\n\x1a\n\0nobody\n").unwrap();
+        let out = TempDir::new("include-zero-output");
+        let output = out.join("counts.txt");
+        let path = |name: &str| dir.join(name).to_string_lossy().into_owned();
+        let counts = |paths: &[String]| {
+            let args = ["minigrep", "-c", "--include-zero", "--output"]
+                .map(String::from)
+                .into_iter()
+                .chain([output.to_string_lossy().into_owned(), "nobody".to_string()])
+                .chain(paths.iter().cloned());
+            run(Config::build(args).unwrap()).unwrap();
+            let mut counts: Vec<String> = fs::read_to_string(&output)
+                .unwrap()
+                .lines()
+                .map(String::from)
+                .collect();
+            counts.sort();
+            counts
+        };
+
+        // a binary file named on the command line is searched, so counted
+        assert_eq!(
+            vec![
+                format!("{}:1", path("a.txt")),
+                format!("{}:0", path("c.txt")),
+                format!("{}:1", path("logo.png")),
+            ],
+            counts(&[path("a.txt"), path("c.txt"), path("logo.png")])
+        );
+        // one found in a directory is skipped, so isn't
+        assert_eq!(
+            vec![
+                format!("{}:1", path("a.txt")),
+                format!("{}:1", path("b.txt")),
+                format!("{}:0", path("c.txt")),
+            ],
+            counts(&[dir.to_string_lossy().into_owned()])
+        );
+    }
+
+    #[test]
+    fn build_filename_flags_win_over_the_default() {
+        let with_filename = |flags: &[&str]| {
+            let args = ["minigrep", "to"]
+                .iter()
+                .chain(flags)
+                .map(|arg| arg.to_string());
+            Config::build(args).unwrap().with_filename
+        };
+
+        assert_eq!(None, with_filename(&["a.txt", "b.txt"]));
+        assert_eq!(Some(true), with_filename(&["-H", "a.txt"]));
+        assert_eq!(
+            Some(false),
+            with_filename(&["-H", "--no-filename", "a.txt"])
+        );
+    }
+
+    #[test]
+    fn sort_paths_by_path_and_size() {
+        let dir = TempDir::new("sort");
+        let file = |name: &str, size: usize| {
+            let path = dir.join(name);
+            fs::write(&path, "x".repeat(size)).unwrap();
+            (path.to_string_lossy().into_owned(), true)
+        };
+        let mut paths = vec![file("b.txt", 1), file("c.txt", 30), file("a.txt", 20)];
+        let names = |paths: &[(String, bool)]| -> Vec<String> {
+            paths
+                .iter()
+                .map(|(path, _)| {
+                    Path::new(path)
+                        .file_name()
+                        .unwrap()
+                        .to_string_lossy()
+                        .into_owned()
+                })
+                .collect()
+        };
+
+        sort_paths(&mut paths, SortBy::Path, false);
+        assert_eq!(vec!["a.txt", "b.txt", "c.txt"], names(&paths));
+        sort_paths(&mut paths, SortBy::Size, true);
+        assert_eq!(vec!["c.txt", "a.txt", "b.txt"], names(&paths));
+    }
+
+    #[test]
+    fn dedupe_skips_lines_and_matches_already_seen() {
+        let matcher = Matcher::new("err[0-9]", false, true).unwrap();
+        let contents = "err1 err2\nok\nerr1 err2\nerr2 err3\n";
+        let options = SearchOptions::default();
+        let lines = |groups: Vec<Vec<Line>>| -> Vec<String> {
+            groups
+                .iter()
+                .flatten()
+                .map(|line| match line {
+                    Line::Matched(m) => format!("{}: {:?}", m.line_number, m.spans),
+                    Line::Context(number, ..) => number.to_string(),
+                })
+                .collect()
+        };
+
+        let mut seen = HashSet::new();
+        let groups = search_context(&matcher, contents, &options);
+        assert_eq!(
+            vec!["1: [0..4, 5..9]", "4: [0..4, 5..9]"],
+            lines(dedupe(groups, false, &mut seen))
+        );
+
+        let mut seen = HashSet::new();
+        let groups = search_context(&matcher, contents, &options);
+        assert_eq!(
+            vec!["1: [0..4, 5..9]", "4: [5..9]"],
+            lines(dedupe(groups, true, &mut seen))
+        );
+    }
+
+    #[test]
+    fn build_rejects_bad_context_number() {
+        let args = ["minigrep", "-A", "lots", "to"].map(String::from);
+
+        assert!(Config::build(args.into_iter()).is_err());
+    }
 }
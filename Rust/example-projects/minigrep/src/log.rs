@@ -0,0 +1,77 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+// --debug and --trace: messages on stderr about what minigrep is doing, for
+// working out why a file wasn't searched or where the time went. --debug says
+// which files are searched and skipped (and why) and how long each part took;
+// --trace adds every directory read and every pattern compiled.
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    #[default]
+    Off,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    fn name(self) -> &'static str {
+        match self {
+            LogLevel::Off => "off",
+            LogLevel::Debug => "debug",
+            LogLevel::Trace => "trace",
+        }
+    }
+}
+
+// one level for the whole process, so the walk and the readers can log
+// without being handed anything
+static LEVEL: AtomicU8 = AtomicU8::new(0);
+
+pub fn set_level(level: LogLevel) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn enabled(level: LogLevel) -> bool {
+    level != LogLevel::Off && level as u8 <= LEVEL.load(Ordering::Relaxed)
+}
+
+pub fn write(level: LogLevel, message: fmt::Arguments) {
+    eprintln!("minigrep: {}: {message}", level.name());
+}
+
+// debug!("...", args) and trace!("...", args), formatting nothing unless the
+// level is on
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled($crate::log::LogLevel::Debug) {
+            $crate::log::write($crate::log::LogLevel::Debug, format_args!($($arg)*));
+        }
+    };
+}
+
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled($crate::log::LogLevel::Trace) {
+            $crate::log::write($crate::log::LogLevel::Trace, format_args!($($arg)*));
+        }
+    };
+}
+
+pub(crate) use debug;
+pub(crate) use trace;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels_include_the_ones_below() {
+        set_level(LogLevel::Debug);
+        assert!(enabled(LogLevel::Debug));
+        assert!(!enabled(LogLevel::Trace));
+        assert!(!enabled(LogLevel::Off));
+        set_level(LogLevel::Off);
+        assert!(!enabled(LogLevel::Debug));
+    }
+}
@@ -1,18 +1,34 @@
 use std::env;
+use std::io;
 use std::process;
 
-use minigrep::Config;
+use minigrep::{Config, MinigrepError};
+
+// grep's exit code for an error, whatever matched before it
+const ERROR: i32 = 2;
 
 fn main() {
     // get configuration - parse command line arguments
-    let config = Config::build(env::args()).unwrap_or_else(|err| {
-        println!("Command line parsing error: {}", err);
-        process::exit(1);
+    // with the defaults from the config file and environment in front of them
+    let args = minigrep::with_defaults(env::args()).unwrap_or_else(|err| {
+        eprintln!("Configuration error: {err}");
+        process::exit(ERROR);
+    });
+    let config = Config::build(args.into_iter()).unwrap_or_else(|err| {
+        eprintln!("Command line parsing error: {}", err);
+        process::exit(ERROR);
     });
 
-    // run the program
-    if let Err(e) = minigrep::run(config) {
-        println!("Application error: {e}");
-        process::exit(1);
+    // run the program - exit with 0 when something matched, 1 when nothing
+    // did and 2 on errors, like grep
+    let quiet = config.quiet;
+    match minigrep::run(config) {
+        Ok(stats) => process::exit(stats.exit_code(quiet)),
+        // whatever was reading the results (like head) has all it wants
+        Err(MinigrepError::Output(err)) if err.kind() == io::ErrorKind::BrokenPipe => {}
+        Err(e) => {
+            eprintln!("Application error: {e}");
+            process::exit(ERROR);
+        }
     }
 }
@@ -0,0 +1,866 @@
+use std::ops::Range;
+
+use aho_corasick::{AhoCorasick, Anchored, Input, MatchKind, StartKind};
+use memchr::memmem::Finder;
+use regex::{Regex, RegexBuilder};
+
+use crate::fuzzy::Fuzzy;
+use crate::log::trace;
+
+// options controlling how the query is interpreted
+#[derive(Debug, Default, Clone)]
+pub struct MatcherOptions {
+    pub ignore_case: bool,
+    // treat the query as a regular expression
+    pub regex: bool,
+    // only match whole words (like grep -w)
+    pub word: bool,
+    // only match when the whole line equals the query (like grep -x)
+    pub line: bool,
+    // the pattern is matched against the whole buffer, so ^ and $ match at
+    // every line boundary rather than only at the start and end
+    pub multiline: bool,
+    // treat "\r\n" as a line break too, so $ matches before a '\r'
+    pub crlf: bool,
+    // approximate matching, allowing up to this many edits
+    pub fuzzy: Option<usize>,
+    // how big the regex engine's lazy DFA can grow, in bytes, before it falls
+    // back on slower ways of matching (the regex crate's default when None)
+    pub dfa_size_limit: Option<usize>,
+}
+
+// A Matcher decides whether a line matches the query.
+pub struct Matcher {
+    engine: Box<dyn Engine>,
+    word: bool,
+    line: bool,
+    // how many patterns the query was made of
+    patterns: usize,
+    // more matchers that each have to match a line as well, for --all-of
+    all_of: Vec<Matcher>,
+    // matchers none of which may match a line, for --not
+    none_of: Vec<Matcher>,
+}
+
+// An Engine is a way of finding the query in a line. Matcher takes care of
+// whole words (-w) and whole lines (-x) on top of what the engine finds, so
+// all an engine needs is find_at - the rest it can do faster if it knows how.
+// The ones minigrep picks from:
+// - Literal: plain substring search (SIMD-accelerated, like memmem)
+// - Several: several substrings at once, in one pass over the line (by an
+//   Aho-Corasick automaton, which also knows which of them was found)
+// - Pattern: regular expression search, with case folding handled by the
+//   regex itself - case-insensitive literals are escaped into one too, so
+//   'ß' matches 'ẞ' and no line is ever lowercased
+// - Fuzzy: approximate search by edit distance
+// and any other can be given to Matcher::with_engine.
+pub trait Engine: Send + Sync {
+    // the first match starting at or after byte offset `start`
+    fn find_at(&self, line: &str, start: usize) -> Option<Range<usize>>;
+
+    fn is_match(&self, line: &str) -> bool {
+        self.find_at(line, 0).is_some()
+    }
+
+    // byte ranges of every (non-overlapping) match within the line
+    fn find_spans(&self, line: &str) -> Vec<Range<usize>> {
+        let mut spans = Vec::new();
+        let mut start = 0;
+        while let Some(span) = self.find_at(line, start) {
+            start = span.end;
+            // step over empty matches so the loop always makes progress
+            if span.is_empty() {
+                start += line[start..].chars().next().map_or(1, char::len_utf8);
+            }
+            spans.push(span);
+            if start > line.len() {
+                break;
+            }
+        }
+        spans
+    }
+
+    // add what the match at `span` is replaced with to `replaced`: the
+    // template, with $0 for the match - or with $1, ${name} etc. for its
+    // capture groups, in an engine that has them
+    fn expand(&self, line: &str, span: &Range<usize>, template: &str, replaced: &mut String) {
+        replaced.push_str(&template.replace("$0", &line[span.clone()]));
+    }
+
+    // how far the line's match is from the query, for approximate engines
+    fn score(&self, _line: &str) -> Option<usize> {
+        None
+    }
+
+    // which of several patterns the match starting at `start` is for, when
+    // the engine can tell
+    fn pattern_at(&self, _line: &str, _start: usize) -> Option<usize> {
+        None
+    }
+
+    // the literals one of which every match contains, if there are any
+    fn literals(&self) -> Option<Literals<'_>> {
+        None
+    }
+
+    // what to call it in --trace
+    fn name(&self) -> &'static str {
+        "custom"
+    }
+}
+
+impl Matcher {
+    pub fn new(query: &str, ignore_case: bool, regex: bool) -> Result<Matcher, regex::Error> {
+        let options = MatcherOptions {
+            ignore_case,
+            regex,
+            ..Default::default()
+        };
+        Matcher::with_options(query, &options)
+    }
+
+    // a line matches if any one of the patterns matches
+    // several patterns are combined into a single regex alternation, so each
+    // line is still only scanned once
+    pub fn with_patterns(
+        patterns: &[String],
+        options: &MatcherOptions,
+    ) -> Result<Matcher, regex::Error> {
+        // fuzzy matching checks each pattern itself rather than through a regex
+        if let Some(max_distance) = options.fuzzy {
+            trace!("fuzzy matching {patterns:?}, up to {max_distance} edits away");
+            let fuzzy = Fuzzy::new(patterns, max_distance, options.ignore_case);
+            let mut matcher = Matcher::with_engine(fuzzy, options);
+            matcher.patterns = patterns.len();
+            return Ok(matcher);
+        }
+
+        match patterns {
+            [pattern] => return Matcher::with_options(pattern, options),
+            // no patterns at all (e.g. an empty pattern file) matches nothing
+            [] => {
+                return Matcher::with_options(
+                    r"[^\s\S]",
+                    &MatcherOptions {
+                        regex: true,
+                        ..options.clone()
+                    },
+                )
+            }
+            _ => {}
+        }
+
+        // plain strings don't need a regex - except to fold case the Unicode
+        // way, or to anchor them all to the whole line or to whole words
+        // (where the automaton would only find the first of them at a place)
+        let literal = !options.regex || !patterns.iter().any(|pattern| has_metacharacters(pattern));
+        if literal
+            && !options.ignore_case
+            && !options.line
+            && !options.word
+            && !patterns.iter().any(String::is_empty)
+        {
+            // leftmost-first, so the same pattern wins as in a regex alternation
+            let automaton = AhoCorasick::builder()
+                .match_kind(MatchKind::LeftmostFirst)
+                .start_kind(StartKind::Both)
+                .build(patterns)
+                .map_err(|err| regex::Error::Syntax(err.to_string()))?;
+            trace!("searching for the literals {patterns:?} at once");
+            let mut matcher = Matcher::with_engine(Several(automaton), options);
+            matcher.patterns = patterns.len();
+            return Ok(matcher);
+        }
+
+        let combined = patterns
+            .iter()
+            .map(|pattern| {
+                if options.regex {
+                    format!("(?:{pattern})")
+                } else {
+                    regex::escape(pattern)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("|");
+        let options = MatcherOptions {
+            regex: true,
+            ..options.clone()
+        };
+        let mut matcher = Matcher::with_options(&combined, &options)?;
+        matcher.patterns = patterns.len();
+        Ok(matcher)
+    }
+
+    pub fn with_options(query: &str, options: &MatcherOptions) -> Result<Matcher, regex::Error> {
+        if options.fuzzy.is_some() {
+            return Matcher::with_patterns(&[query.to_string()], options);
+        }
+
+        // a pattern without metacharacters is just a literal, so skip the regex
+        // engine and use the faster substring search instead
+        let regex = options.regex && has_metacharacters(query);
+
+        let engine: Box<dyn Engine> = if regex {
+            // anchor the pattern so it has to span the entire line, or start
+            // and end at a word boundary - so of an alternation like a|ab the
+            // part that makes a whole word is found, not just the first
+            let pattern = if options.line {
+                format!("^(?:{query})$")
+            } else if options.word {
+                format!(r"\b{{start-half}}(?:{query})\b{{end-half}}")
+            } else {
+                query.to_string()
+            };
+            let mut builder = RegexBuilder::new(&pattern);
+            builder
+                .case_insensitive(options.ignore_case)
+                .multi_line(options.multiline)
+                .crlf(options.crlf);
+            if let Some(limit) = options.dfa_size_limit {
+                builder.dfa_size_limit(limit);
+            }
+            let re = builder.build()?;
+            trace!("compiled the regex {pattern:?}");
+            Box::new(Pattern { re, captures: true })
+        } else if options.ignore_case {
+            let re = RegexBuilder::new(&regex::escape(query))
+                .case_insensitive(true)
+                .build()?;
+            trace!("compiled {query:?} as a literal, ignoring case");
+            Box::new(Pattern {
+                re,
+                captures: false,
+            })
+        } else {
+            trace!("searching for the literal {query:?}");
+            Box::new(Literal(Finder::new(query).into_owned()))
+        };
+
+        Ok(Matcher {
+            engine,
+            word: options.word,
+            line: options.line,
+            patterns: 1,
+            all_of: Vec::new(),
+            none_of: Vec::new(),
+        })
+    }
+
+    // a line then matches only if every one of `required` matches it too,
+    // and the matches of them all are highlighted
+    pub fn all_of(mut self, required: Vec<Matcher>) -> Matcher {
+        self.all_of.extend(required);
+        self
+    }
+
+    // a line then matches only if none of `excluded` matches it
+    pub fn none_of(mut self, excluded: Vec<Matcher>) -> Matcher {
+        self.none_of.extend(excluded);
+        self
+    }
+
+    // a matcher that finds the query with an engine of the caller's, with
+    // -w and -x applied on top as usual (the rest of `options` is the
+    // engine's to honour, or not)
+    pub fn with_engine(engine: impl Engine + 'static, options: &MatcherOptions) -> Matcher {
+        trace!("searching with the {} engine", engine.name());
+        Matcher {
+            engine: Box::new(engine),
+            word: options.word,
+            line: options.line,
+            patterns: 1,
+            all_of: Vec::new(),
+            none_of: Vec::new(),
+        }
+    }
+
+    pub fn is_match(&self, line: &str) -> bool {
+        let matched = if self.word || self.line {
+            self.find_at(line, 0).is_some()
+        } else {
+            self.engine.is_match(line)
+        };
+        matched
+            && self.all_of.iter().all(|required| required.is_match(line))
+            && !self.none_of.iter().any(|excluded| excluded.is_match(line))
+    }
+
+    // how far a fuzzy match is from the query (0 is exact)
+    // None when the line doesn't match, or this isn't a fuzzy matcher
+    pub fn score(&self, line: &str) -> Option<usize> {
+        self.engine.score(line)
+    }
+
+    // which pattern (by its index) the match starting at `start` is for - None
+    // if no match starts there, or the engine can't tell (a regex combining
+    // the patterns doesn't say which part of it matched)
+    pub fn pattern_at(&self, line: &str, start: usize) -> Option<usize> {
+        if self.patterns > 1 {
+            return self.engine.pattern_at(line, start);
+        }
+        self.find_at(line, start)
+            .filter(|span| span.start == start)
+            .map(|_| 0)
+    }
+
+    // the line with each span swapped for the expanded replacement template
+    // ($1, ${name} etc. refer to capture groups when the query is a regex)
+    // also returns where each replacement ended up, so it can be highlighted
+    pub fn replace(
+        &self,
+        line: &str,
+        spans: &[Range<usize>],
+        template: &str,
+    ) -> (String, Vec<Range<usize>>) {
+        let mut replaced = String::with_capacity(line.len());
+        let mut new_spans = Vec::with_capacity(spans.len());
+        let mut last = 0;
+        for span in spans {
+            replaced.push_str(&line[last..span.start]);
+            let start = replaced.len();
+            self.engine.expand(line, span, template, &mut replaced);
+            new_spans.push(start..replaced.len());
+            last = span.end;
+        }
+        replaced.push_str(&line[last..]);
+        (replaced, new_spans)
+    }
+
+    // the first match starting at or after byte offset `start`
+    pub fn find_at(&self, line: &str, start: usize) -> Option<Range<usize>> {
+        if self.line {
+            return self.find_whole_line(line, start);
+        }
+        if !self.word {
+            return self.engine.find_at(line, start);
+        }
+
+        // keep looking until a candidate is a whole word
+        let mut start = start;
+        loop {
+            let span = self.engine.find_at(line, start)?;
+            if is_whole_word(line, &span) {
+                return Some(span);
+            }
+            // retry from the char after the rejected candidate's start
+            start = match line[span.start..].chars().next() {
+                Some(c) => span.start + c.len_utf8(),
+                None => return None,
+            };
+        }
+    }
+
+    // the whole line is the only possible match in line mode
+    // (regexes are already anchored, literals must match from start to end)
+    fn find_whole_line(&self, line: &str, start: usize) -> Option<Range<usize>> {
+        if start > 0 {
+            return None;
+        }
+        let span = self.engine.find_at(line, 0)?;
+        if span == (0..line.len()) {
+            Some(span)
+        } else {
+            None
+        }
+    }
+
+    // the substrings one of which every match contains when the query is made
+    // of literals, so contents can be searched for them all at once instead
+    // of line by line
+    pub(crate) fn literals(&self) -> Option<Literals<'_>> {
+        self.engine.literals()
+    }
+
+    // byte ranges of every (non-overlapping) match within the line - with
+    // --all-of, of every pattern, the first of any that overlap
+    pub fn find_spans(&self, line: &str) -> Vec<Range<usize>> {
+        let spans = self.own_spans(line);
+        if self.all_of.is_empty() {
+            return spans;
+        }
+        let mut all: Vec<Range<usize>> = spans
+            .into_iter()
+            .chain(
+                self.all_of
+                    .iter()
+                    .flat_map(|required| required.find_spans(line)),
+            )
+            .collect();
+        all.sort_by_key(|span| (span.start, std::cmp::Reverse(span.end)));
+        let mut spans: Vec<Range<usize>> = Vec::with_capacity(all.len());
+        for span in all {
+            if spans
+                .last()
+                .is_none_or(|last| span.start >= last.end && span != *last)
+            {
+                spans.push(span);
+            }
+        }
+        spans
+    }
+
+    fn own_spans(&self, line: &str) -> Vec<Range<usize>> {
+        if self.line {
+            return self.find_whole_line(line, 0).into_iter().collect();
+        }
+        if !self.word {
+            return self.engine.find_spans(line);
+        }
+
+        let mut spans = Vec::new();
+        let mut start = 0;
+        while let Some(span) = self.find_at(line, start) {
+            start = span.end;
+            // step over empty matches so the loop always makes progress
+            if span.is_empty() {
+                start += line[start..].chars().next().map_or(1, char::len_utf8);
+            }
+            spans.push(span);
+            if start > line.len() {
+                break;
+            }
+        }
+        spans
+    }
+}
+
+// plain substring search
+struct Literal(Finder<'static>);
+
+impl Engine for Literal {
+    fn find_at(&self, line: &str, start: usize) -> Option<Range<usize>> {
+        self.0
+            .find(&line.as_bytes()[start..])
+            .map(|index| start + index..start + index + self.0.needle().len())
+    }
+
+    fn is_match(&self, line: &str) -> bool {
+        self.0.find(line.as_bytes()).is_some()
+    }
+
+    fn find_spans(&self, line: &str) -> Vec<Range<usize>> {
+        // an empty query matches between chars, not between bytes
+        if self.0.needle().is_empty() {
+            return line
+                .match_indices("")
+                .map(|(start, _)| start..start)
+                .collect();
+        }
+        self.0
+            .find_iter(line.as_bytes())
+            .map(|start| start..start + self.0.needle().len())
+            .collect()
+    }
+
+    fn literals(&self) -> Option<Literals<'_>> {
+        Some(Literals::One(&self.0))
+    }
+
+    fn name(&self) -> &'static str {
+        "literal"
+    }
+}
+
+// several substrings at once
+struct Several(AhoCorasick);
+
+impl Engine for Several {
+    fn find_at(&self, line: &str, start: usize) -> Option<Range<usize>> {
+        self.0
+            .find(Input::new(line).range(start..))
+            .map(|m| m.range())
+    }
+
+    fn is_match(&self, line: &str) -> bool {
+        self.0.is_match(line)
+    }
+
+    fn find_spans(&self, line: &str) -> Vec<Range<usize>> {
+        self.0.find_iter(line).map(|m| m.range()).collect()
+    }
+
+    fn pattern_at(&self, line: &str, start: usize) -> Option<usize> {
+        let input = Input::new(line).range(start..).anchored(Anchored::Yes);
+        self.0.find(input).map(|m| m.pattern().as_usize())
+    }
+
+    fn literals(&self) -> Option<Literals<'_>> {
+        Some(Literals::Many(&self.0))
+    }
+
+    fn name(&self) -> &'static str {
+        "literals"
+    }
+}
+
+// a substring ignoring case, or a regular expression
+struct Pattern {
+    re: Regex,
+    // a literal ignoring case has no groups for a replacement to refer to
+    captures: bool,
+}
+
+impl Engine for Pattern {
+    fn find_at(&self, line: &str, start: usize) -> Option<Range<usize>> {
+        self.re.find_at(line, start).map(|m| m.range())
+    }
+
+    fn is_match(&self, line: &str) -> bool {
+        self.re.is_match(line)
+    }
+
+    fn find_spans(&self, line: &str) -> Vec<Range<usize>> {
+        self.re.find_iter(line).map(|m| m.range()).collect()
+    }
+
+    fn expand(&self, line: &str, span: &Range<usize>, template: &str, replaced: &mut String) {
+        // a span this regex doesn't match at (another --all-of pattern's)
+        // only has $0
+        let captures = if self.captures {
+            let captures = self.re.captures_at(line, span.start);
+            captures.filter(|captures| captures.get(0).unwrap().start() == span.start)
+        } else {
+            None
+        };
+        match captures {
+            Some(captures) => captures.expand(template, replaced),
+            None => replaced.push_str(&template.replace("$0", &line[span.clone()])),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        if self.captures {
+            "regex"
+        } else {
+            "case-insensitive"
+        }
+    }
+}
+
+impl Engine for Fuzzy {
+    fn find_at(&self, line: &str, start: usize) -> Option<Range<usize>> {
+        Fuzzy::find_at(self, line, start).map(|(span, _)| span)
+    }
+
+    fn find_spans(&self, line: &str) -> Vec<Range<usize>> {
+        let mut spans = Vec::new();
+        let mut start = 0;
+        while let Some(span) = Engine::find_at(self, line, start) {
+            // stop at empty matches, a fuzzy query can always shrink to nothing
+            if span.is_empty() {
+                break;
+            }
+            start = span.end;
+            spans.push(span);
+        }
+        spans
+    }
+
+    fn score(&self, line: &str) -> Option<usize> {
+        Fuzzy::find_at(self, line, 0).map(|(_, distance)| distance)
+    }
+
+    fn name(&self) -> &'static str {
+        "fuzzy"
+    }
+}
+
+// what a query made of literals can be found by
+pub enum Literals<'a> {
+    One(&'a Finder<'static>),
+    Many(&'a AhoCorasick),
+}
+
+impl Literals<'_> {
+    // where the first of them starts in `haystack`
+    pub fn find(&self, haystack: &[u8]) -> Option<usize> {
+        match self {
+            Literals::One(finder) => finder.find(haystack),
+            Literals::Many(automaton) => automaton.find(haystack).map(|m| m.start()),
+        }
+    }
+}
+
+// true if the pattern means something different as a regex than as a literal
+fn has_metacharacters(pattern: &str) -> bool {
+    regex::escape(pattern) != pattern
+}
+
+// word chars are letters, digits and underscores (in any script)
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+// a match is a whole word if it isn't glued to a word char on either side
+fn is_whole_word(line: &str, span: &Range<usize>) -> bool {
+    let before = line[..span.start].chars().next_back();
+    let after = line[span.end..].chars().next();
+    !before.is_some_and(is_word_char) && !after.is_some_and(is_word_char)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(query: &str, regex: bool) -> Matcher {
+        let options = MatcherOptions {
+            regex,
+            word: true,
+            ..Default::default()
+        };
+        Matcher::with_options(query, &options).unwrap()
+    }
+
+    #[test]
+    fn regex_matches_pattern() {
+        let matcher = Matcher::new(r"fn\s+\w+_test", false, true).unwrap();
+
+        assert!(matcher.is_match("    fn parse_test() {"));
+        assert!(!matcher.is_match("fn parse() {"));
+    }
+
+    #[test]
+    fn regex_case_insensitive() {
+        let matcher = Matcher::new("^rust", true, true).unwrap();
+
+        assert!(matcher.is_match("RUST is fun"));
+        assert!(!matcher.is_match("Trust me."));
+    }
+
+    #[test]
+    fn spans_for_every_match() {
+        let matcher = Matcher::new("to", false, false).unwrap();
+
+        assert_eq!(vec![0..2, 4..6, 8..10], matcher.find_spans("tomato, toast"));
+    }
+
+    #[test]
+    fn empty_literal_matches_between_chars() {
+        let matcher = Matcher::new("", false, false).unwrap();
+
+        assert_eq!(vec![0..0, 2..2, 3..3], matcher.find_spans("éa"));
+    }
+
+    #[test]
+    fn case_insensitive_spans_map_to_original_line() {
+        // 'İ' is two bytes long, so later spans must be byte offsets in the original line
+        let matcher = Matcher::new("rust", true, false).unwrap();
+
+        assert_eq!(vec![3..7], matcher.find_spans("İ RuSt"));
+    }
+
+    #[test]
+    fn plain_pattern_uses_literal_engine() {
+        let matcher = Matcher::new("nobody", false, true).unwrap();
+        assert_eq!("literal", matcher.engine.name());
+
+        let matcher = Matcher::new("no.ody", false, true).unwrap();
+        assert_eq!("regex", matcher.engine.name());
+    }
+
+    #[test]
+    fn fixed_strings_treat_metacharacters_literally() {
+        let matcher = Matcher::new("a.b", false, false).unwrap();
+
+        assert!(matcher.is_match("call a.b()"));
+        assert!(!matcher.is_match("axb"));
+    }
+
+    #[test]
+    fn any_pattern_can_match() {
+        let patterns = ["frog".to_string(), "b.g".to_string()];
+        let matcher = Matcher::with_patterns(&patterns, &MatcherOptions::default()).unwrap();
+
+        assert!(matcher.is_match("How public, like a frog"));
+        assert!(matcher.is_match("public, like b.g"));
+        assert!(!matcher.is_match("To an admiring bog!"));
+    }
+
+    #[test]
+    fn several_literals_say_which_one_matched() {
+        let patterns = ["frog".to_string(), "bog".to_string(), "frogs".to_string()];
+        let matcher = Matcher::with_patterns(&patterns, &MatcherOptions::default()).unwrap();
+        assert_eq!("literals", matcher.engine.name());
+
+        // the first pattern that matches wins, like in a regex alternation
+        let line = "frogs in a bog";
+        assert_eq!(vec![0..4, 11..14], matcher.find_spans(line));
+        assert_eq!(Some(0), matcher.pattern_at(line, 0));
+        assert_eq!(Some(1), matcher.pattern_at(line, 11));
+        assert_eq!(None, matcher.pattern_at(line, 5));
+
+        // a regex (or ignoring case) still goes through an alternation
+        let options = MatcherOptions {
+            ignore_case: true,
+            ..Default::default()
+        };
+        let matcher = Matcher::with_patterns(&patterns, &options).unwrap();
+        assert_eq!("regex", matcher.engine.name());
+        assert_eq!(None, matcher.pattern_at(line, 0));
+    }
+
+    // a run of ASCII digits
+    struct Digits;
+
+    impl Engine for Digits {
+        fn find_at(&self, line: &str, start: usize) -> Option<Range<usize>> {
+            let from = start + line[start..].find(|c: char| c.is_ascii_digit())?;
+            let len = line[from..]
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(line.len() - from);
+            Some(from..from + len)
+        }
+    }
+
+    #[test]
+    fn other_engines_slot_in() {
+        let matcher = Matcher::with_engine(Digits, &MatcherOptions::default());
+        assert!(matcher.is_match("pick 3"));
+        assert_eq!(vec![0..2, 5..8], matcher.find_spans("42 a 123x"));
+        assert_eq!(Some(0), matcher.pattern_at("42 a 123x", 5));
+        assert_eq!(
+            "<42> a <123>x",
+            matcher.replace("42 a 123x", &[0..2, 5..8], "<$0>").0
+        );
+
+        // whole words are still up to the matcher
+        let options = MatcherOptions {
+            word: true,
+            ..Default::default()
+        };
+        let matcher = Matcher::with_engine(Digits, &options);
+        assert_eq!(vec![0..2], matcher.find_spans("42 a 123x"));
+    }
+
+    #[test]
+    fn all_of_needs_every_pattern() {
+        let options = MatcherOptions::default();
+        let matcher = Matcher::with_patterns(&["Who".to_string(), "Then".to_string()], &options)
+            .unwrap()
+            .all_of(vec![
+                Matcher::with_options("you", &options).unwrap(),
+                Matcher::with_options("o", &options).unwrap(),
+            ]);
+
+        assert!(matcher.is_match("I'm nobody! Who are you?"));
+        assert!(!matcher.is_match("Then there's a pair of us"));
+        assert!(!matcher.is_match("Are you nobody, too?"));
+
+        // every pattern's matches are highlighted, without overlapping
+        assert_eq!(
+            vec![5..6, 7..8, 12..15, 20..23],
+            matcher.find_spans("I'm nobody! Who are you?")
+        );
+    }
+
+    #[test]
+    fn none_of_rules_lines_out() {
+        let options = MatcherOptions::default();
+        let matcher = Matcher::with_options("you", &options)
+            .unwrap()
+            .none_of(vec![
+                Matcher::with_options("Who", &options).unwrap(),
+                Matcher::with_options("know", &options).unwrap(),
+            ]);
+
+        assert!(matcher.is_match("Are you nobody, too?"));
+        assert!(!matcher.is_match("I'm nobody! Who are you?"));
+        assert!(!matcher.is_match("They'd banish us, you know."));
+        assert!(!matcher.is_match("Then there's a pair of us"));
+        assert_eq!(vec![4..7], matcher.find_spans("Are you nobody, too?"));
+    }
+
+    #[test]
+    fn no_patterns_match_nothing() {
+        let matcher = Matcher::with_patterns(&[], &MatcherOptions::default()).unwrap();
+
+        assert!(!matcher.is_match(""));
+        assert!(!matcher.is_match("anything"));
+    }
+
+    #[test]
+    fn case_insensitive_uses_case_folding() {
+        let matcher = Matcher::new("straße", true, false).unwrap();
+        assert!(matcher.is_match("STRAẞE"));
+
+        // the Kelvin sign folds to a plain 'k'
+        let matcher = Matcher::new("kelvin", true, false).unwrap();
+        assert_eq!(vec![2..10], matcher.find_spans("0 \u{212A}elvin"));
+    }
+
+    #[test]
+    fn replace_expands_capture_groups() {
+        let matcher = Matcher::new(r"(\w+)@(\w+)", false, true).unwrap();
+        let line = "mail bob@home or amy@work";
+        let spans = matcher.find_spans(line);
+
+        assert_eq!(
+            ("mail home:bob or work:amy".to_string(), vec![5..13, 17..25]),
+            matcher.replace(line, &spans, "$2:$1")
+        );
+    }
+
+    #[test]
+    fn invalid_regex_is_an_error() {
+        assert!(Matcher::new("(unclosed", false, true).is_err());
+    }
+
+    #[test]
+    fn word_mode_skips_partial_words() {
+        let matcher = words("add", false);
+
+        assert!(!matcher.is_match("address"));
+        assert!(!matcher.is_match("re_add"));
+        assert_eq!(vec![12..15], matcher.find_spans("address, or add(1)"));
+    }
+
+    #[test]
+    fn word_boundaries_are_unicode_aware() {
+        let matcher = words("café", false);
+
+        assert!(matcher.is_match("un café noir"));
+        assert!(!matcher.is_match("cafés"));
+        assert!(!words("ça", true).is_match("déçà"));
+    }
+
+    #[test]
+    fn word_mode_finds_the_alternative_that_is_a_whole_word() {
+        assert_eq!(vec![0..2, 3..4], words("a|ab", true).find_spans("ab a"));
+
+        let options = MatcherOptions {
+            word: true,
+            ..Default::default()
+        };
+        let patterns = ["a".to_string(), "ab".to_string()];
+        let matcher = Matcher::with_patterns(&patterns, &options).unwrap();
+        assert_eq!(vec![0..2, 3..4], matcher.find_spans("ab a"));
+        assert!(!matcher.is_match("abc"));
+    }
+
+    #[test]
+    fn line_mode_requires_the_whole_line() {
+        let literal = MatcherOptions {
+            ignore_case: true,
+            line: true,
+            ..Default::default()
+        };
+        let matcher = Matcher::with_options("Pick three.", &literal).unwrap();
+
+        assert!(matcher.is_match("pick THREE."));
+        assert!(!matcher.is_match("Pick three. Or four."));
+    }
+
+    #[test]
+    fn line_mode_anchors_regex_alternation() {
+        let regex = MatcherOptions {
+            regex: true,
+            line: true,
+            ..Default::default()
+        };
+        let matcher = Matcher::with_options("a|ab", &regex).unwrap();
+
+        assert_eq!(vec![0..2], matcher.find_spans("ab"));
+        assert!(!matcher.is_match("abc"));
+    }
+}
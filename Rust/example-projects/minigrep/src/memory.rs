@@ -0,0 +1,91 @@
+use std::sync::{Condvar, Mutex};
+
+// --max-memory: a cap on the memory files are read into whole (and, searching
+// on several threads, on the results waiting their turn to be printed). A
+// file that fits waits for others to be done with theirs rather than going
+// over, and one too big to ever fit is searched out of a memory map instead.
+// Plain text is searched as it's read, a few kilobytes at a time, so it
+// doesn't need any of it.
+
+pub struct Budget {
+    limit: u64,
+    used: Mutex<u64>,
+    freed: Condvar,
+}
+
+impl Budget {
+    pub fn new(limit: u64) -> Budget {
+        Budget {
+            limit,
+            used: Mutex::new(0),
+            freed: Condvar::new(),
+        }
+    }
+
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    // hold `bytes` of the budget until the reservation is dropped, waiting
+    // for them to be free - anything bigger than the budget waits until
+    // nothing else is held, so it doesn't wait forever
+    pub fn reserve(&self, bytes: u64) -> Reservation<'_> {
+        let mut used = self.used.lock().unwrap_or_else(|err| err.into_inner());
+        while *used > 0 && *used + bytes > self.limit {
+            used = self.freed.wait(used).unwrap_or_else(|err| err.into_inner());
+        }
+        *used += bytes;
+        Reservation {
+            budget: self,
+            bytes,
+        }
+    }
+}
+
+pub struct Reservation<'a> {
+    budget: &'a Budget,
+    bytes: u64,
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        let mut used = self
+            .budget
+            .used
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        *used -= self.bytes;
+        self.budget.freed.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::thread;
+
+    #[test]
+    fn reservations_wait_for_room() {
+        let budget = Budget::new(100);
+        // the most held at once
+        let most = AtomicU64::new(0);
+        let held = AtomicU64::new(0);
+        thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    for bytes in [60, 30, 150, 10] {
+                        let _reserved = budget.reserve(bytes);
+                        let now = held.fetch_add(bytes, Ordering::SeqCst) + bytes;
+                        most.fetch_max(now, Ordering::SeqCst);
+                        thread::yield_now();
+                        held.fetch_sub(bytes, Ordering::SeqCst);
+                    }
+                });
+            }
+        });
+        // only the one too big to fit ever went over, and then on its own
+        assert!(most.load(Ordering::SeqCst) <= 150);
+        assert_eq!(0, *budget.used.lock().unwrap());
+    }
+}
@@ -0,0 +1,169 @@
+use std::fs::File;
+use std::io;
+use std::ops::Deref;
+use std::path::Path;
+
+// Big files that have to be searched whole (with --multiline or --write, say)
+// are searched straight out of a memory map rather than copied into memory
+// first - the pages are read in as the search gets to them, and a file of
+// several gigabytes doesn't need as much memory again.
+//
+// A file changing underneath its map changes what's being searched (and one
+// cut short can kill the process), which is the price ripgrep pays too.
+
+// smaller files are quicker to read than to map
+const MIN_LEN: u64 = 4 * 1024 * 1024;
+
+// whether to map the file (of `len` bytes) with --mmap (Some(true)),
+// --no-mmap (Some(false)) or neither
+pub fn worth_mapping(path: &Path, len: u64, choice: Option<bool>) -> bool {
+    // the files under /proc and /sys are made up as they're read, and say
+    // they're empty or the size of a page
+    let virtual_file = path.is_absolute()
+        && (path.starts_with("/proc") || path.starts_with("/sys") || path.starts_with("/dev"));
+    SUPPORTED && len > 0 && !virtual_file && choice.unwrap_or(len >= MIN_LEN)
+}
+
+// the whole of a file, mapped read-only
+pub struct Mmap {
+    ptr: *const u8,
+    len: usize,
+}
+
+// nothing writes through the map, so it can be read from any thread
+unsafe impl Send for Mmap {}
+unsafe impl Sync for Mmap {}
+
+impl Deref for Mmap {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+impl Mmap {
+    // the map as text, or back again if it isn't valid UTF-8
+    pub fn into_text(self) -> Result<MappedText, Mmap> {
+        match std::str::from_utf8(&self) {
+            Ok(_) => Ok(MappedText(self)),
+            Err(_) => Err(self),
+        }
+    }
+}
+
+// a map that's been checked to be UTF-8
+pub struct MappedText(Mmap);
+
+impl Deref for MappedText {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        // into_text checked it, and nothing writes through the map
+        unsafe { std::str::from_utf8_unchecked(&self.0) }
+    }
+}
+
+#[cfg(all(unix, target_pointer_width = "64"))]
+const SUPPORTED: bool = true;
+
+#[cfg(all(unix, target_pointer_width = "64"))]
+mod sys {
+    use std::os::raw::{c_int, c_void};
+
+    pub const PROT_READ: c_int = 1;
+    pub const MAP_PRIVATE: c_int = 2;
+    pub const MAP_FAILED: *mut c_void = !0 as *mut c_void;
+
+    extern "C" {
+        // from the C library std already links against
+        pub fn mmap(
+            addr: *mut c_void,
+            len: usize,
+            prot: c_int,
+            flags: c_int,
+            fd: c_int,
+            offset: i64,
+        ) -> *mut c_void;
+        pub fn munmap(addr: *mut c_void, len: usize) -> c_int;
+    }
+}
+
+#[cfg(all(unix, target_pointer_width = "64"))]
+impl Mmap {
+    pub fn open(file: &File, len: u64) -> io::Result<Mmap> {
+        use std::os::unix::io::AsRawFd;
+
+        let len = usize::try_from(len).map_err(io::Error::other)?;
+        let ptr = unsafe {
+            sys::mmap(
+                std::ptr::null_mut(),
+                len,
+                sys::PROT_READ,
+                sys::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == sys::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Mmap {
+            ptr: ptr as *const u8,
+            len,
+        })
+    }
+}
+
+#[cfg(all(unix, target_pointer_width = "64"))]
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        unsafe {
+            sys::munmap(self.ptr as *mut _, self.len);
+        }
+    }
+}
+
+// elsewhere files are always read
+#[cfg(not(all(unix, target_pointer_width = "64")))]
+const SUPPORTED: bool = false;
+
+#[cfg(not(all(unix, target_pointer_width = "64")))]
+impl Mmap {
+    pub fn open(_file: &File, _len: u64) -> io::Result<Mmap> {
+        Err(io::Error::from(io::ErrorKind::Unsupported))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TempDir;
+    use std::fs;
+
+    #[test]
+    fn a_mapped_file_reads_the_same() {
+        let dir = TempDir::new("mmap");
+        let path = dir.join("poem.txt");
+        fs::write(&path, "I'm nobody!\nWho are you?\n").unwrap();
+        let file = File::open(&path).unwrap();
+        let len = file.metadata().unwrap().len();
+        if SUPPORTED {
+            assert_eq!(
+                b"I'm nobody!\nWho are you?\n",
+                &*Mmap::open(&file, len).unwrap()
+            );
+        }
+
+        assert!(!worth_mapping(
+            Path::new("/proc/self/maps"),
+            4096,
+            Some(true)
+        ));
+        assert!(!worth_mapping(Path::new("poem.txt"), 1024, None));
+        assert_eq!(
+            SUPPORTED,
+            worth_mapping(Path::new("poem.txt"), 1024, Some(true))
+        );
+    }
+}
@@ -0,0 +1,93 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+// Standard output, either line-buffered so each line shows up as soon as it's
+// found, or block-buffered so printing a lot of lines doesn't cost a write each.
+pub fn stdout(line_buffered: bool) -> Box<dyn Write> {
+    // the stdout lock does line buffering by itself
+    let stdout = io::stdout().lock();
+    if line_buffered {
+        Box::new(stdout)
+    } else {
+        Box::new(BufWriter::new(stdout))
+    }
+}
+
+// OutputFile is where the results go with --output. They're written to a temp
+// file next to it, which replaces the file once the search is done (the same
+// way --write rewrites files) - so a search that fails or is interrupted part
+// way through leaves the previous results alone rather than half of the new ones.
+pub struct OutputFile {
+    file: BufWriter<File>,
+    path: PathBuf,
+    temp_path: PathBuf,
+}
+
+impl OutputFile {
+    pub fn create(path: &Path) -> io::Result<OutputFile> {
+        let file_name = match path.file_name() {
+            Some(name) => name.to_string_lossy(),
+            None => return Err(io::Error::new(io::ErrorKind::InvalidInput, "not a file")),
+        };
+        let temp_path = path.with_file_name(format!(".{file_name}.minigrep-tmp"));
+        let file = File::create(&temp_path)?;
+        Ok(OutputFile {
+            file: BufWriter::new(file),
+            path: path.to_path_buf(),
+            temp_path,
+        })
+    }
+
+    // put the results in place
+    pub fn commit(mut self) -> io::Result<()> {
+        self.file.flush()?;
+        self.file.get_ref().sync_all()?;
+        fs::rename(&self.temp_path, &self.path)
+    }
+}
+
+impl Write for OutputFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+// a file that was never committed is thrown away (after a commit there's
+// nothing left to remove)
+impl Drop for OutputFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.temp_path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TempDir;
+
+    #[test]
+    fn results_replace_the_file_only_once_committed() {
+        let dir = TempDir::new("output");
+        let path = dir.join("results.txt");
+        fs::write(&path, "old results\n").unwrap();
+
+        let mut output = OutputFile::create(&path).unwrap();
+        writeln!(output, "poem.txt:I'm nobody! Who are you?").unwrap();
+        drop(output);
+        assert_eq!("old results\n", fs::read_to_string(&path).unwrap());
+
+        let mut output = OutputFile::create(&path).unwrap();
+        writeln!(output, "poem.txt:I'm nobody! Who are you?").unwrap();
+        output.commit().unwrap();
+        assert_eq!(
+            "poem.txt:I'm nobody! Who are you?\n",
+            fs::read_to_string(&path).unwrap()
+        );
+        assert_eq!(1, fs::read_dir(&dir).unwrap().count());
+    }
+}
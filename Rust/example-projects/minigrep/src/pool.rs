@@ -0,0 +1,142 @@
+use std::collections::BTreeMap;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+// A pool of threads sharing out a list of items: each thread takes the next
+// item nobody has started on, works on it and sends back the result, which
+// the calling thread gets to handle as soon as it's ready.
+
+// more threads than this mostly wait on each other (and on the disk)
+const MAX_THREADS: usize = 12;
+
+// how many threads to search with by default: one per CPU, within reason
+pub fn default_threads() -> usize {
+    thread::available_parallelism()
+        .map_or(1, NonZeroUsize::get)
+        .min(MAX_THREADS)
+}
+
+// run `work` on every item across `threads` threads, passing each result to
+// `done` on this thread with the index of its item, in the order they finish -
+// or `in_order`, the order of the items, with the results that finish before
+// their turn waiting for it. `done` returning false stops the threads taking
+// on any more
+pub fn for_each<T, R>(
+    items: &[T],
+    threads: usize,
+    in_order: bool,
+    work: impl Fn(&T) -> R + Sync,
+    mut done: impl FnMut(usize, R) -> bool,
+) where
+    T: Sync,
+    R: Send,
+{
+    let next = AtomicUsize::new(0);
+    let stop = AtomicBool::new(false);
+    thread::scope(|scope| {
+        // a few results can wait while the last one is handled, more than
+        // that and the threads wait instead of piling them up
+        let (sender, receiver) = mpsc::sync_channel(threads * 4);
+        for _ in 0..threads.min(items.len()) {
+            let sender = sender.clone();
+            let (next, stop, work) = (&next, &stop, &work);
+            scope.spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(item) = items.get(index) else {
+                        break;
+                    };
+                    // the receiver is gone once `done` has had enough
+                    if sender.send((index, work(item))).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+        let mut waiting = BTreeMap::new();
+        let mut next = 0;
+        'results: for (index, result) in receiver.iter() {
+            // out of order, whatever's just finished is next
+            if !in_order {
+                next = index;
+            }
+            waiting.insert(index, result);
+            while let Some(result) = waiting.remove(&next) {
+                if !done(next, result) {
+                    stop.store(true, Ordering::Relaxed);
+                    break 'results;
+                }
+                next += 1;
+            }
+        }
+        // so threads waiting to send give up rather than wait forever
+        drop(receiver);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_item_is_worked_on_once() {
+        let items: Vec<usize> = (0..100).collect();
+        let mut results = Vec::new();
+        for_each(
+            &items,
+            4,
+            false,
+            |item| item * 2,
+            |index, result| {
+                results.push((index, result));
+                true
+            },
+        );
+        results.sort();
+        assert_eq!(
+            items
+                .iter()
+                .map(|item| (*item, item * 2))
+                .collect::<Vec<_>>(),
+            results
+        );
+    }
+
+    #[test]
+    fn results_can_come_in_order() {
+        let items: Vec<u64> = (0..50).collect();
+        let mut indexes = Vec::new();
+        for_each(
+            &items,
+            4,
+            true,
+            // the early items take longest
+            |item| thread::sleep(std::time::Duration::from_micros(500 - item * 10)),
+            |index, _| {
+                indexes.push(index);
+                index < 40
+            },
+        );
+        assert_eq!((0..=40).collect::<Vec<_>>(), indexes);
+    }
+
+    #[test]
+    fn done_can_stop_early() {
+        let items: Vec<usize> = (0..1000).collect();
+        let mut handled = 0;
+        for_each(
+            &items,
+            4,
+            false,
+            |item| *item,
+            |_, _| {
+                handled += 1;
+                handled < 3
+            },
+        );
+        assert_eq!(3, handled);
+    }
+}
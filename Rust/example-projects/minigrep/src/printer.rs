@@ -0,0 +1,689 @@
+use std::fmt::Display;
+use std::io::{self, Write};
+
+use crate::color::{Colors, Style, RESET};
+use crate::Match;
+
+// Printer writes matching lines, decorated according to the options
+pub struct Printer {
+    pub show_filename: bool,
+    pub line_number: bool,
+    // print the byte offset of each line (or of each match with only_matching)
+    pub byte_offset: bool,
+    // print the 1-based column of the first match (implies line numbers)
+    pub column: bool,
+    pub color: bool,
+    // the styles used when color is on
+    pub colors: Colors,
+    // print the fuzzy match distance before each line
+    pub show_score: bool,
+    // print each matched part on its own line instead of the whole line
+    pub only_matching: bool,
+    // end file names with a NUL byte instead of ':' or a newline, for xargs -0
+    pub null: bool,
+    // end each output line with a NUL byte instead of a newline (--null-data)
+    pub null_data: bool,
+    // the file name is printed once above its lines (print_heading) instead
+    // of in front of each one
+    pub heading: bool,
+    // lines longer than this many characters are left out (saying how many
+    // matches they had), or with max_columns_preview cut short with an ellipsis
+    pub max_columns: Option<usize>,
+    pub max_columns_preview: bool,
+    // leave out the whitespace at the start of each line
+    pub trim: bool,
+    pub delimiters: Delimiters,
+}
+
+// what goes between the parts of the output
+#[derive(Debug, Clone, PartialEq)]
+pub struct Delimiters {
+    // the line between groups of context lines, or None for no line at all
+    pub context: Option<String>,
+    // after the file name and each number in front of a matching line
+    pub field_match: String,
+    // and in front of a context line
+    pub field_context: String,
+    // between the last number and the text of the line, part of the default
+    // separators - given ones are written exactly, so they set it to ""
+    pub before_text: String,
+}
+
+impl Default for Delimiters {
+    fn default() -> Delimiters {
+        Delimiters {
+            context: Some("--".to_string()),
+            field_match: ":".to_string(),
+            field_context: "-".to_string(),
+            before_text: " ".to_string(),
+        }
+    }
+}
+
+impl Printer {
+    pub fn print_match(&self, out: &mut impl Write, path: &str, m: &Match) -> io::Result<()> {
+        if self.only_matching {
+            for span in m.spans.iter().filter(|span| !span.is_empty()) {
+                self.write_prefix(out, path, m, span.start, m.byte_offset + span.start)?;
+                let text = &m.line[span.clone()];
+                self.paint(out, &self.colors.matched, text)?;
+                self.end_line(out)?;
+            }
+            return Ok(());
+        }
+
+        // the column is where the match is in the file, trimmed or not
+        let first_match = m.spans.first().map_or(0, |span| span.start);
+        self.write_prefix(out, path, m, first_match, m.byte_offset)?;
+        let trimmed;
+        let m = if self.trim {
+            trimmed = trim_start(m);
+            &trimmed
+        } else {
+            m
+        };
+        match self.cut(m.line) {
+            Some(end) if self.max_columns_preview => {
+                let spans = m
+                    .spans
+                    .iter()
+                    .filter(|span| span.start < end)
+                    .map(|span| span.start..span.end.min(end))
+                    .collect();
+                let preview = Match {
+                    line: &m.line[..end],
+                    spans,
+                    ..m.clone()
+                };
+                self.write_line(out, &preview)?;
+                write!(out, "…")?;
+            }
+            Some(_) => {
+                let matches = m.spans.iter().filter(|span| !span.is_empty()).count();
+                let noun = if matches == 1 { "match" } else { "matches" };
+                write!(out, "[omitted long line with {matches} {noun}]")?;
+            }
+            None => self.write_line(out, m)?,
+        }
+
+        self.end_line(out)
+    }
+
+    fn write_line(&self, out: &mut impl Write, m: &Match) -> io::Result<()> {
+        if self.color {
+            write_highlighted(out, m, &self.colors.matched)
+        } else {
+            write!(out, "{}", m.line)
+        }
+    }
+
+    // where a line longer than max_columns gets cut, if it is longer
+    fn cut(&self, line: &str) -> Option<usize> {
+        let (end, _) = line.char_indices().nth(self.max_columns?)?;
+        Some(end)
+    }
+
+    // "path:line:column:offset: " - each part only if enabled, with
+    // before_text after them when there were any numbers
+    fn write_prefix(
+        &self,
+        out: &mut impl Write,
+        path: &str,
+        m: &Match,
+        match_start: usize,
+        byte_offset: usize,
+    ) -> io::Result<()> {
+        let separator = &self.delimiters.field_match;
+        if self.show_filename && !self.heading {
+            self.write_path(out, path, separator)?;
+        }
+        if self.line_number || self.column {
+            self.paint(out, &self.colors.line, m.line_number)?;
+            write!(out, "{separator}")?;
+        }
+        if self.column {
+            self.paint(out, &self.colors.column, match_start + 1)?;
+            write!(out, "{separator}")?;
+        }
+        if self.byte_offset {
+            write!(out, "{byte_offset}{separator}")?;
+        }
+        if self.line_number || self.column || self.byte_offset {
+            write!(out, "{}", self.delimiters.before_text)?;
+        }
+        if self.show_score {
+            if let Some(score) = m.score {
+                write!(out, "[{score}] ")?;
+            }
+        }
+        Ok(())
+    }
+
+    // context lines use '-' instead of ':' so they stand out from matches
+    // (or whatever the delimiters say), with the same numbers in front but
+    // the column, as there's no match on them to give one for
+    pub fn print_context(
+        &self,
+        out: &mut impl Write,
+        path: &str,
+        line_number: usize,
+        byte_offset: usize,
+        line: &str,
+    ) -> io::Result<()> {
+        // there is no matched part of a context line to show
+        if self.only_matching {
+            return Ok(());
+        }
+        let separator = &self.delimiters.field_context;
+        if self.show_filename && !self.heading {
+            self.write_path(out, path, separator)?;
+        }
+        if self.line_number || self.column {
+            self.paint(out, &self.colors.line, line_number)?;
+            write!(out, "{separator}")?;
+        }
+        if self.byte_offset {
+            write!(out, "{byte_offset}{separator}")?;
+        }
+        if self.line_number || self.column || self.byte_offset {
+            write!(out, "{}", self.delimiters.before_text)?;
+        }
+        let line = if self.trim { line.trim_start() } else { line };
+        match self.cut(line) {
+            Some(end) if self.max_columns_preview => write!(out, "{}…", &line[..end])?,
+            Some(_) => write!(out, "[omitted long context line]")?,
+            None => write!(out, "{line}")?,
+        }
+        self.end_line(out)
+    }
+
+    // a file name on its own, for -l and -L
+    pub fn print_path(&self, out: &mut impl Write, path: &str) -> io::Result<()> {
+        self.write_path(out, path, "\n")
+    }
+
+    // how many lines or matches a file has, for -c and --count-matches
+    pub fn print_count(&self, out: &mut impl Write, path: &str, count: usize) -> io::Result<()> {
+        if self.show_filename {
+            self.write_path(out, path, &self.delimiters.field_match)?;
+        }
+        write!(out, "{count}")?;
+        self.end_line(out)
+    }
+
+    // the file name above its lines, when there are headings
+    pub fn print_heading(&self, out: &mut impl Write, path: &str) -> io::Result<()> {
+        self.write_path(out, path, "\n")
+    }
+
+    // the line between groups of context lines, unless there's to be none
+    pub fn print_context_break(&self, out: &mut impl Write) -> io::Result<()> {
+        match &self.delimiters.context {
+            Some(separator) => writeln!(out, "{separator}"),
+            None => Ok(()),
+        }
+    }
+
+    fn end_line(&self, out: &mut impl Write) -> io::Result<()> {
+        if self.null_data {
+            write!(out, "\0")
+        } else {
+            writeln!(out)
+        }
+    }
+
+    fn write_path(&self, out: &mut impl Write, path: &str, separator: &str) -> io::Result<()> {
+        self.paint(out, &self.colors.path, path)?;
+        if self.null {
+            write!(out, "\0")
+        } else {
+            write!(out, "{separator}")
+        }
+    }
+
+    // the text in the style, when there's color
+    fn paint(&self, out: &mut impl Write, style: &Style, text: impl Display) -> io::Result<()> {
+        if self.color && !style.is_plain() {
+            write!(out, "{style}{text}{RESET}")
+        } else {
+            write!(out, "{text}")
+        }
+    }
+}
+
+// the match without the whitespace its line starts with, its spans moved to suit
+fn trim_start<'a>(m: &Match<'a>) -> Match<'a> {
+    let line = m.line.trim_start();
+    let trimmed = m.line.len() - line.len();
+    let spans = m
+        .spans
+        .iter()
+        .filter(|span| span.end > trimmed)
+        .map(|span| span.start.max(trimmed) - trimmed..span.end - trimmed)
+        .collect();
+    Match {
+        line,
+        spans,
+        ..m.clone()
+    }
+}
+
+// write the line, splicing color escapes around each match span
+fn write_highlighted(out: &mut impl Write, m: &Match, style: &Style) -> io::Result<()> {
+    let mut last = 0;
+    for span in &m.spans {
+        if span.is_empty() {
+            continue;
+        }
+        write!(out, "{}", &m.line[last..span.start])?;
+        write!(out, "{style}{}{RESET}", &m.line[span.clone()])?;
+        last = span.end;
+    }
+    write!(out, "{}", &m.line[last..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn highlights_each_span() {
+        let printer = Printer {
+            show_filename: false,
+            line_number: true,
+            byte_offset: false,
+            column: false,
+            color: true,
+            colors: Colors::default(),
+            show_score: false,
+            only_matching: false,
+            null: false,
+            null_data: false,
+            heading: false,
+            max_columns: None,
+            max_columns_preview: false,
+            trim: false,
+            delimiters: Delimiters::default(),
+        };
+        let m = Match {
+            line_number: 3,
+            byte_offset: 0,
+            line: "a to b to",
+            spans: vec![2..4, 7..9],
+            score: None,
+        };
+
+        let mut out = Vec::new();
+        printer.print_match(&mut out, "poem.txt", &m).unwrap();
+
+        assert_eq!(
+            "3: a \x1b[1;31mto\x1b[0m b \x1b[1;31mto\x1b[0m\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn colors_apply_to_the_path_and_line_number() {
+        let mut colors = Colors::default();
+        colors.apply("path:fg:magenta").unwrap();
+        colors.apply("line:fg:green").unwrap();
+        colors.apply("match:fg:yellow").unwrap();
+        let printer = Printer {
+            show_filename: true,
+            line_number: true,
+            byte_offset: false,
+            column: false,
+            color: true,
+            colors,
+            show_score: false,
+            only_matching: false,
+            null: false,
+            null_data: false,
+            heading: false,
+            max_columns: None,
+            max_columns_preview: false,
+            trim: false,
+            delimiters: Delimiters::default(),
+        };
+        let m = Match {
+            line_number: 3,
+            byte_offset: 0,
+            line: "a to b to",
+            spans: vec![2..4, 7..9],
+            score: None,
+        };
+
+        let mut out = Vec::new();
+        printer.print_match(&mut out, "poem.txt", &m).unwrap();
+
+        assert_eq!(
+            "\x1b[35mpoem.txt\x1b[0m:\x1b[32m3\x1b[0m: a \x1b[1;33mto\x1b[0m b \x1b[1;33mto\x1b[0m\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn only_matching_prints_each_span() {
+        let printer = Printer {
+            show_filename: true,
+            line_number: false,
+            byte_offset: true,
+            column: false,
+            color: false,
+            colors: Colors::default(),
+            show_score: false,
+            only_matching: true,
+            null: false,
+            null_data: false,
+            heading: false,
+            max_columns: None,
+            max_columns_preview: false,
+            trim: false,
+            delimiters: Delimiters::default(),
+        };
+        let m = Match {
+            line_number: 1,
+            byte_offset: 10,
+            line: "a to b to",
+            spans: vec![2..4, 7..9],
+            score: None,
+        };
+
+        let mut out = Vec::new();
+        printer.print_match(&mut out, "poem.txt", &m).unwrap();
+
+        assert_eq!(
+            "poem.txt:12: to\npoem.txt:17: to\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn column_of_first_match() {
+        let printer = Printer {
+            show_filename: true,
+            line_number: false,
+            byte_offset: false,
+            column: true,
+            color: false,
+            colors: Colors::default(),
+            show_score: false,
+            only_matching: false,
+            null: false,
+            null_data: false,
+            heading: false,
+            max_columns: None,
+            max_columns_preview: false,
+            trim: false,
+            delimiters: Delimiters::default(),
+        };
+        let m = Match {
+            line_number: 7,
+            byte_offset: 0,
+            line: "a to b to",
+            spans: vec![2..4, 7..9],
+            score: None,
+        };
+
+        let mut out = Vec::new();
+        printer.print_match(&mut out, "poem.txt", &m).unwrap();
+
+        assert_eq!("poem.txt:7:3: a to b to\n", String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn heading_replaces_the_path_prefix() {
+        let printer = Printer {
+            show_filename: true,
+            line_number: true,
+            byte_offset: false,
+            column: false,
+            color: false,
+            colors: Colors::default(),
+            show_score: false,
+            only_matching: false,
+            null: false,
+            null_data: false,
+            heading: true,
+            max_columns: None,
+            max_columns_preview: false,
+            trim: false,
+            delimiters: Delimiters::default(),
+        };
+        let m = Match {
+            line_number: 2,
+            byte_offset: 0,
+            line: "to be, to be",
+            spans: vec![0..2, 7..9],
+            score: None,
+        };
+
+        let mut out = Vec::new();
+        printer.print_heading(&mut out, "poem.txt").unwrap();
+        printer.print_match(&mut out, "poem.txt", &m).unwrap();
+        printer
+            .print_context(&mut out, "poem.txt", 3, 0, "or not")
+            .unwrap();
+
+        assert_eq!(
+            "poem.txt\n2: to be, to be\n3- or not\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn long_lines_are_omitted_or_cut_short() {
+        let mut printer = Printer {
+            show_filename: false,
+            line_number: false,
+            byte_offset: false,
+            column: false,
+            color: true,
+            colors: Colors::default(),
+            show_score: false,
+            only_matching: false,
+            null: false,
+            null_data: false,
+            heading: false,
+            max_columns: Some(9),
+            max_columns_preview: false,
+            trim: false,
+            delimiters: Delimiters::default(),
+        };
+        let m = Match {
+            line_number: 1,
+            byte_offset: 0,
+            line: "áa to b to c",
+            spans: vec![4..6, 9..11],
+            score: None,
+        };
+
+        let mut out = Vec::new();
+        printer.print_match(&mut out, "min.js", &m).unwrap();
+        printer
+            .print_context(&mut out, "min.js", 2, 0, "context too long")
+            .unwrap();
+        printer
+            .print_context(&mut out, "min.js", 3, 0, "short")
+            .unwrap();
+        // an empty match isn't counted
+        let one = Match {
+            spans: vec![4..6, 9..9],
+            ..m.clone()
+        };
+        printer.print_match(&mut out, "min.js", &one).unwrap();
+        printer.max_columns_preview = true;
+        printer.print_match(&mut out, "min.js", &m).unwrap();
+        printer
+            .print_context(&mut out, "min.js", 2, 0, "context too long")
+            .unwrap();
+
+        assert_eq!(
+            "[omitted long line with 2 matches]\n[omitted long context line]\nshort\n\
+             [omitted long line with 1 match]\n\
+             áa \x1b[1;31mto\x1b[0m b \x1b[1;31mt\x1b[0m…\ncontext t…\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn trim_keeps_the_highlights_and_column() {
+        let printer = Printer {
+            show_filename: false,
+            line_number: false,
+            byte_offset: false,
+            column: true,
+            color: true,
+            colors: Colors::default(),
+            show_score: false,
+            only_matching: false,
+            null: false,
+            null_data: false,
+            heading: false,
+            max_columns: None,
+            max_columns_preview: false,
+            trim: true,
+            delimiters: Delimiters::default(),
+        };
+        let m = Match {
+            line_number: 4,
+            byte_offset: 0,
+            line: "\t    to be",
+            spans: vec![1..3, 5..7],
+            score: None,
+        };
+
+        let mut out = Vec::new();
+        printer.print_match(&mut out, "poem.txt", &m).unwrap();
+        printer
+            .print_context(&mut out, "poem.txt", 5, 0, "  or not")
+            .unwrap();
+
+        assert_eq!(
+            "4:2: \x1b[1;31mto\x1b[0m be\n5- or not\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn null_terminates_file_names() {
+        let printer = Printer {
+            show_filename: true,
+            line_number: true,
+            byte_offset: false,
+            column: false,
+            color: false,
+            colors: Colors::default(),
+            show_score: false,
+            only_matching: false,
+            null: true,
+            null_data: false,
+            heading: false,
+            max_columns: None,
+            max_columns_preview: false,
+            trim: false,
+            delimiters: Delimiters::default(),
+        };
+        let m = Match {
+            line_number: 2,
+            byte_offset: 0,
+            line: "to to",
+            spans: vec![0..2, 3..5],
+            score: None,
+        };
+
+        let mut out = Vec::new();
+        printer.print_match(&mut out, "a b.txt", &m).unwrap();
+        printer.print_path(&mut out, "c.txt").unwrap();
+        printer.print_count(&mut out, "d.txt", 3).unwrap();
+
+        assert_eq!(
+            "a b.txt\x002: to to\nc.txt\x00d.txt\x003\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn delimiters_can_be_changed_or_left_out() {
+        let printer = Printer {
+            show_filename: true,
+            line_number: true,
+            byte_offset: false,
+            column: true,
+            color: false,
+            colors: Colors::default(),
+            show_score: false,
+            only_matching: false,
+            null: false,
+            null_data: false,
+            heading: false,
+            max_columns: None,
+            max_columns_preview: false,
+            trim: false,
+            delimiters: Delimiters {
+                context: None,
+                field_match: "\t".to_string(),
+                field_context: "|".to_string(),
+                before_text: String::new(),
+            },
+        };
+        let m = Match {
+            line_number: 2,
+            byte_offset: 0,
+            line: "a to to",
+            spans: vec![2..4, 5..7],
+            score: None,
+        };
+
+        let mut out = Vec::new();
+        printer.print_match(&mut out, "poem.txt", &m).unwrap();
+        printer.print_context_break(&mut out).unwrap();
+        printer
+            .print_context(&mut out, "poem.txt", 3, 0, "b")
+            .unwrap();
+
+        assert_eq!(
+            "poem.txt\t2\t3\ta to to\npoem.txt|3|b\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn context_lines_get_the_same_numbers_but_the_column() {
+        let printer = Printer {
+            show_filename: true,
+            line_number: false,
+            byte_offset: true,
+            column: true,
+            color: false,
+            colors: Colors::default(),
+            show_score: false,
+            only_matching: false,
+            null: false,
+            null_data: false,
+            heading: false,
+            max_columns: None,
+            max_columns_preview: false,
+            trim: false,
+            delimiters: Delimiters::default(),
+        };
+        let m = Match {
+            line_number: 2,
+            byte_offset: 6,
+            line: "to be",
+            spans: vec![0..2, 3..5],
+            score: None,
+        };
+
+        let mut out = Vec::new();
+        printer
+            .print_context(&mut out, "poem.txt", 1, 0, "or not")
+            .unwrap();
+        printer.print_match(&mut out, "poem.txt", &m).unwrap();
+
+        assert_eq!(
+            "poem.txt-1-0- or not\npoem.txt:2:1:6: to be\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+}
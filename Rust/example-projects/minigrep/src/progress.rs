@@ -0,0 +1,137 @@
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use crate::Stats;
+
+// how often the progress line is redrawn (and how long a search runs before
+// it first shows up, so quick searches never see it)
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+// Progress shows how far a search of a directory tree has got, on a line of
+// its own at the bottom of stderr. It wraps the output: the line is erased
+// before anything is printed, and drawn again below it at the next update.
+pub struct Progress<W> {
+    out: W,
+    enabled: bool,
+    // whether the line is on the screen
+    shown: bool,
+    drawn_at: Instant,
+    columns: usize,
+}
+
+impl<W: Write> Progress<W> {
+    pub fn new(out: W, enabled: bool) -> Progress<W> {
+        Progress {
+            out,
+            enabled,
+            shown: false,
+            drawn_at: Instant::now(),
+            columns: if enabled { columns() } else { 80 },
+        }
+    }
+
+    // show the counts so far, with the file about to be searched
+    pub fn update(&mut self, stats: &Stats, path: &str) -> io::Result<()> {
+        if !self.enabled || self.drawn_at.elapsed() < REDRAW_INTERVAL {
+            return Ok(());
+        }
+        // whatever was printed goes above the line
+        self.out.flush()?;
+        let line = describe(stats, path, self.columns);
+        let mut stderr = io::stderr().lock();
+        write!(stderr, "\r{line}\x1b[K")?;
+        stderr.flush()?;
+        self.shown = true;
+        self.drawn_at = Instant::now();
+        Ok(())
+    }
+
+    // erase the line, before printing something else
+    pub fn clear(&mut self) -> io::Result<()> {
+        if self.shown {
+            let mut stderr = io::stderr().lock();
+            write!(stderr, "\r\x1b[K")?;
+            stderr.flush()?;
+            self.shown = false;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for Progress<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.clear()?;
+        self.out.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+impl<W> Drop for Progress<W> {
+    fn drop(&mut self) {
+        if self.shown {
+            eprint!("\r\x1b[K");
+        }
+    }
+}
+
+// the progress line, cut to fit in `columns` - the end of a long path is the
+// part worth keeping, so it loses its start
+fn describe(stats: &Stats, path: &str, columns: usize) -> String {
+    let counts = format!(
+        "{} files searched, {} matched: ",
+        stats.files_searched + stats.skipped.len(),
+        stats.files_matched
+    );
+    // leave the last column alone, so the terminal doesn't wrap
+    let room = columns.saturating_sub(counts.chars().count() + 1);
+    let path: Vec<char> = path
+        .chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect();
+    let path: String = if path.len() > room {
+        let kept = room.saturating_sub(1);
+        std::iter::once('…')
+            .chain(path[path.len() - kept..].iter().copied())
+            .collect()
+    } else {
+        path.into_iter().collect()
+    };
+    format!("{counts}{path}")
+}
+
+// the width of the terminal, from stty (80 if it can't tell)
+fn columns() -> usize {
+    let size = std::fs::File::open("/dev/tty")
+        .and_then(|tty| crate::tui::stty(&tty, &["size"]))
+        .unwrap_or_default();
+    size.split_whitespace()
+        .nth(1)
+        .and_then(|columns| columns.parse().ok())
+        .filter(|&columns| columns > 0)
+        .unwrap_or(80)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_paths_lose_their_start() {
+        let stats = Stats {
+            files_searched: 120,
+            files_matched: 3,
+            ..Stats::default()
+        };
+        assert_eq!(
+            "120 files searched, 3 matched: src/lib.rs",
+            describe(&stats, "src/lib.rs", 80)
+        );
+        assert_eq!(
+            "120 files searched, 3 matched: …/walk.rs",
+            describe(&stats, "very/deep/src/walk.rs", 41)
+        );
+    }
+}
@@ -0,0 +1,1260 @@
+use std::borrow::Borrow;
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, BufRead};
+use std::ops::{Range, RangeInclusive};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::matcher::Literals;
+use crate::Matcher;
+
+// a matching line, along with where the matches are within it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Match<'a> {
+    pub line_number: usize,
+    // byte offset of the start of the line within the searched contents
+    pub byte_offset: usize,
+    pub line: &'a str,
+    pub spans: Vec<Range<usize>>,
+    // edit distance of a fuzzy match (None for exact matchers)
+    pub score: Option<usize>,
+}
+
+// a line to print around matches: either a match itself, or a
+// (line number, byte offset, text) context line
+#[derive(Debug, PartialEq)]
+pub enum Line<'a> {
+    Matched(Match<'a>),
+    Context(usize, usize, &'a str),
+}
+
+// options controlling which lines are selected and what is printed around them
+#[derive(Debug, Default, Clone)]
+pub struct SearchOptions {
+    // select the lines that do NOT match
+    pub invert: bool,
+    pub before_context: usize,
+    pub after_context: usize,
+    // let matches span several lines by searching the whole buffer at once
+    pub multiline: bool,
+    // stop after this many selected lines (like grep -m)
+    pub max_count: Option<usize>,
+    // keep every line, as if there was unlimited context
+    pub passthru: bool,
+    // records end with NUL bytes instead of newlines (like grep -z)
+    pub null_data: bool,
+    // stop once this many lines are selected, across every search sharing it
+    pub total: Option<Arc<TotalLimit>>,
+    // only the lines numbered in this range are searched (or printed as
+    // context), like --line-range 100:500
+    pub line_range: Option<RangeInclusive<usize>>,
+    // give up on the search once it's past, leaving the lines after it out
+    pub deadline: Option<Arc<Deadline>>,
+    // stop at the next line once this is set, like minigrep's Ctrl-C does
+    pub interrupt: Option<&'static AtomicBool>,
+}
+
+// --max-total: a limit on the lines selected by all the searches in a run,
+// however many threads they're on - each search takes its lines from it one
+// at a time, and stops as soon as there are none left
+#[derive(Debug, Default)]
+pub struct TotalLimit {
+    limit: usize,
+    taken: AtomicUsize,
+}
+
+impl TotalLimit {
+    pub fn new(limit: usize) -> TotalLimit {
+        TotalLimit {
+            limit,
+            taken: AtomicUsize::new(0),
+        }
+    }
+
+    // take one line from the limit, or false if they're all gone
+    pub fn take(&self) -> bool {
+        self.taken
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |taken| {
+                (taken < self.limit).then_some(taken + 1)
+            })
+            .is_ok()
+    }
+
+    pub fn reached(&self) -> bool {
+        self.taken.load(Ordering::SeqCst) >= self.limit
+    }
+}
+
+// --regex-timeout: how long the search of a file can take from its first
+// line, so a pattern that's slow on what a file holds doesn't hold up the
+// rest of the run. It's looked at between lines, so a single line still
+// takes as long as it takes (which the regex crate keeps linear in its
+// length) - and so does -U's one search of the whole file.
+#[derive(Debug)]
+pub struct Deadline {
+    timeout: Duration,
+    // set by the first look at it, so reading the file isn't counted
+    at: OnceLock<Instant>,
+    passed: AtomicBool,
+    // how many looks there have been, the clock only being read every
+    // CHECK_EVERY of them
+    checks: AtomicUsize,
+}
+
+// lines between reads of the clock - often enough for a slow pattern, where
+// each line takes a while, and rarely enough not to slow down a quick one
+const CHECK_EVERY: usize = 32;
+
+impl Deadline {
+    pub fn after(timeout: Duration) -> Deadline {
+        Deadline {
+            timeout,
+            at: OnceLock::new(),
+            passed: AtomicBool::new(false),
+            checks: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    // whether it's past, remembering it was so the search can be told it gave up
+    pub fn check(&self) -> bool {
+        if !self
+            .checks
+            .fetch_add(1, Ordering::Relaxed)
+            .is_multiple_of(CHECK_EVERY)
+        {
+            return self.passed();
+        }
+        let at = *self.at.get_or_init(|| Instant::now() + self.timeout);
+        if Instant::now() >= at {
+            self.passed.store(true, Ordering::SeqCst);
+        }
+        self.passed()
+    }
+
+    // whether a search gave up because of it
+    pub fn passed(&self) -> bool {
+        self.passed.load(Ordering::SeqCst)
+    }
+}
+
+impl SearchOptions {
+    pub(crate) fn terminator(&self) -> char {
+        if self.null_data {
+            '\0'
+        } else {
+            '\n'
+        }
+    }
+
+    // no more lines can be selected, after max_count of them (or --max-total)
+    fn limited(&self, selected: usize) -> bool {
+        self.max_count.is_some_and(|max| selected >= max)
+            || self.total.as_ref().is_some_and(|total| total.reached())
+    }
+
+    // how many more lines to pass over before the line range starts, after
+    // line `line_number`
+    fn lines_before_range(&self, line_number: usize) -> usize {
+        self.line_range.as_ref().map_or(0, |range| {
+            range.start().saturating_sub(1).saturating_sub(line_number)
+        })
+    }
+
+    // whether line `line_number` comes after the line range, so the search
+    // is over
+    fn past_range(&self, line_number: usize) -> bool {
+        self.line_range
+            .as_ref()
+            .is_some_and(|range| line_number > *range.end())
+    }
+
+    // whether to stop before the next line, the deadline being past or the
+    // search interrupted
+    fn stopped(&self) -> bool {
+        self.deadline
+            .as_ref()
+            .is_some_and(|deadline| deadline.check())
+            || self
+                .interrupt
+                .is_some_and(|interrupt| interrupt.load(Ordering::SeqCst))
+    }
+
+    // count a line that would be selected against --max-total - once that's
+    // reached it's context at most
+    fn counted(&self) -> bool {
+        self.total.as_ref().is_none_or(|total| total.take())
+    }
+}
+
+// search using any matcher (literal, case insensitive or regex)
+pub fn search_with<'a>(matcher: &Matcher, contents: &'a str) -> Vec<&'a str> {
+    contents
+        .lines()
+        .filter(|line| matcher.is_match(line))
+        .collect()
+}
+
+// search returning the 1-based line number of each matching line
+pub fn search_lines<'a>(matcher: &Matcher, contents: &'a str) -> Vec<(usize, &'a str)> {
+    contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| matcher.is_match(line))
+        .map(|(index, line)| (index + 1, line))
+        .collect()
+}
+
+// search returning each matching line with the byte ranges of its matches
+pub fn search_matches<'a>(matcher: &Matcher, contents: &'a str) -> Vec<Match<'a>> {
+    search_matches_iter(matcher, contents).collect()
+}
+
+// the same, but finding each line only when it's asked for - so stopping
+// early leaves the rest of the contents unsearched
+// (the matcher can be borrowed, or owned by the iterator)
+pub fn search_matches_iter<'a, M: Borrow<Matcher>>(
+    matcher: M,
+    contents: &'a str,
+) -> impl Iterator<Item = Match<'a>> {
+    lines_with_offsets(contents, '\n').enumerate().filter_map(
+        move |(index, (byte_offset, line))| {
+            let matcher = matcher.borrow();
+            matcher.is_match(line).then(|| Match {
+                line_number: index + 1,
+                byte_offset,
+                line,
+                spans: matcher.find_spans(line),
+                score: matcher.score(line),
+            })
+        },
+    )
+}
+
+// search returning the lines that do NOT match (like grep -v)
+pub fn search_invert<'a>(matcher: &Matcher, contents: &'a str) -> Vec<Match<'a>> {
+    lines_with_offsets(contents, '\n')
+        .enumerate()
+        .filter(|(_, (_, line))| !matcher.is_match(line))
+        .map(|(index, (byte_offset, line))| Match {
+            line_number: index + 1,
+            byte_offset,
+            line,
+            spans: Vec::new(),
+            score: None,
+        })
+        .collect()
+}
+
+// search with context lines before and after each selected line
+// the result is split into groups of consecutive lines - overlapping or
+// touching context windows are merged into one group
+pub fn search_context<'a>(
+    matcher: &Matcher,
+    contents: &'a str,
+    options: &SearchOptions,
+) -> Vec<Vec<Line<'a>>> {
+    if options.multiline {
+        let matched = multiline_spans(matcher, contents, options.terminator());
+        return group_lines(contents, options, None, |line_number, byte_offset, line| {
+            let spans = match (matched.get(&line_number), options.invert) {
+                (Some(spans), false) => spans.clone(),
+                (None, true) => Vec::new(),
+                _ => return None,
+            };
+            Some(Match {
+                line_number,
+                byte_offset,
+                line,
+                spans,
+                score: None,
+            })
+        });
+    }
+
+    group_lines(
+        contents,
+        options,
+        skip_with(matcher, options),
+        |line_number, byte_offset, line| {
+            if matcher.is_match(line) == options.invert {
+                return None;
+            }
+            // inverted lines have nothing to highlight
+            if options.invert {
+                return Some(Match {
+                    line_number,
+                    byte_offset,
+                    line,
+                    spans: Vec::new(),
+                    score: None,
+                });
+            }
+            Some(Match {
+                line_number,
+                byte_offset,
+                line,
+                spans: matcher.find_spans(line),
+                score: matcher.score(line),
+            })
+        },
+    )
+}
+
+// search_context, counting only the matches that lie within one of the
+// `within` byte ranges of contents (in order, not overlapping) - a line is
+// selected when one of its matches does, or with invert when none do
+// lines are searched one at a time, even with options.multiline
+pub fn search_context_within<'a>(
+    matcher: &Matcher,
+    contents: &'a str,
+    options: &SearchOptions,
+    within: &[Range<usize>],
+) -> Vec<Vec<Line<'a>>> {
+    let inside = |span: &Range<usize>| {
+        let index = within.partition_point(|range| range.end < span.end);
+        within
+            .get(index)
+            .is_some_and(|range| range.start <= span.start)
+    };
+    group_lines(
+        contents,
+        options,
+        skip_with(matcher, options),
+        |line_number, byte_offset, line| {
+            if !matcher.is_match(line) && !options.invert {
+                return None;
+            }
+            let spans: Vec<Range<usize>> = matcher
+                .find_spans(line)
+                .into_iter()
+                .filter(|span| inside(&(byte_offset + span.start..byte_offset + span.end)))
+                .collect();
+            if spans.is_empty() != options.invert {
+                return None;
+            }
+            Some(Match {
+                line_number,
+                byte_offset,
+                line,
+                score: if options.invert {
+                    None
+                } else {
+                    matcher.score(line)
+                },
+                spans: if options.invert { Vec::new() } else { spans },
+            })
+        },
+    )
+}
+
+// true as soon as one line is selected - the rest of the contents isn't searched
+pub fn has_match(matcher: &Matcher, contents: &str, options: &SearchOptions) -> bool {
+    let options = SearchOptions {
+        before_context: 0,
+        after_context: 0,
+        max_count: Some(1),
+        ..options.clone()
+    };
+    !search_context(matcher, contents, &options).is_empty()
+}
+
+// walk the lines with a window of context, grouping the selected lines
+// `select` returns the Match for a selected line, or None to skip it
+// lines without any of `literals` in them are skipped over rather than walked, when
+// it's given
+fn group_lines<'a>(
+    contents: &'a str,
+    options: &SearchOptions,
+    literals: Option<Literals>,
+    mut select: impl FnMut(usize, usize, &'a str) -> Option<Match<'a>>,
+) -> Vec<Vec<Line<'a>>> {
+    let before = options.before_context;
+    let after = options.after_context;
+    let mut groups = Vec::new();
+    let mut group: Vec<Line<'a>> = Vec::new();
+
+    // window of recent lines that haven't been added to a group yet
+    let mut window: VecDeque<(usize, usize, &'a str)> = VecDeque::with_capacity(before + 1);
+    // how many more lines of context to take after the last match
+    let mut after_left: usize = 0;
+    // line number of the last line added to a group
+    let mut last_added = 0;
+    // how many lines have been selected so far
+    let mut selected = 0;
+
+    let terminator = options.terminator();
+    let bytes = contents.as_bytes();
+    let mut offset = 0;
+    let mut line_number = 0;
+    // the lines before the line range are only counted
+    let (count, len) = skip_lines(bytes, terminator, options.lines_before_range(0));
+    line_number += count;
+    offset += len;
+    while offset < contents.len() {
+        if let Some(literals) = &literals {
+            // with no context left to take, every line before the next one
+            // a literal is in can be counted rather than looked at
+            if after_left == 0 && !options.passthru && !options.limited(selected) {
+                let skipped =
+                    &contents[offset..offset + skip_to(literals, &bytes[offset..], terminator)];
+                let count = count_lines(skipped.as_bytes(), terminator);
+                let kept = last_lines(skipped.as_bytes(), terminator, before);
+                for (index, range) in kept.iter().enumerate() {
+                    let number = line_number + count - kept.len() + index + 1;
+                    window.push_back((
+                        number,
+                        offset + range.start,
+                        strip_terminator(&skipped[range.clone()], terminator),
+                    ));
+                    if window.len() > before {
+                        window.pop_front();
+                    }
+                }
+                line_number += count;
+                offset += skipped.len();
+                if offset == contents.len() {
+                    break;
+                }
+            }
+        }
+
+        let byte_offset = offset;
+        offset = memchr::memchr(terminator as u8, &bytes[offset..])
+            .map_or(contents.len(), |index| offset + index + 1);
+        let line = strip_terminator(&contents[byte_offset..offset], terminator);
+        line_number += 1;
+        if options.past_range(line_number) || options.stopped() {
+            break;
+        }
+
+        // once the max count is reached only trailing context is left to add,
+        // so stop reading as soon as that is done
+        if options.limited(selected) {
+            if after_left == 0 && !options.passthru {
+                break;
+            }
+            group.push(Line::Context(line_number, byte_offset, line));
+            after_left = after_left.saturating_sub(1);
+            continue;
+        }
+
+        if let Some(m) = select(line_number, byte_offset, line).filter(|_| options.counted()) {
+            selected += 1;
+            // start a new group if there is a gap since the last added line
+            let first = window.front().map_or(line_number, |&(number, ..)| number);
+            if !group.is_empty() && first > last_added + 1 {
+                groups.push(std::mem::take(&mut group));
+            }
+
+            for (number, byte_offset, text) in window.drain(..) {
+                group.push(Line::Context(number, byte_offset, text));
+            }
+            group.push(Line::Matched(m));
+            last_added = line_number;
+            after_left = after;
+        } else if after_left > 0 || options.passthru {
+            group.push(Line::Context(line_number, byte_offset, line));
+            last_added = line_number;
+            after_left = after_left.saturating_sub(1);
+        } else if before > 0 {
+            window.push_back((line_number, byte_offset, line));
+            if window.len() > before {
+                window.pop_front();
+            }
+        }
+    }
+
+    if !group.is_empty() {
+        groups.push(group);
+    }
+
+    groups
+}
+
+// Like search_context, but reading the lines from `reader` as the search gets
+// to them rather than from contents already in memory, so however big the
+// input is only the current line and the context before it are held at once.
+// `each` is handed every line to print, and whether it starts a new group.
+// Multiline matches need the whole buffer, so options.multiline is ignored.
+// Returns how many lines were read.
+pub fn search_reader(
+    matcher: &Matcher,
+    mut reader: impl BufRead,
+    options: &SearchOptions,
+    mut each: impl FnMut(bool, &Line) -> io::Result<()>,
+) -> io::Result<usize> {
+    let terminator = options.terminator();
+    let before = options.before_context;
+    let mut state = ReadState::new(options);
+
+    let literals = skip_with(matcher, options);
+    let mut buffer = Vec::new();
+    loop {
+        // the lines before the line range are only counted, however much of
+        // them has been read
+        let before_range = options.lines_before_range(state.line_number);
+        if before_range > 0 {
+            let read = reader.fill_buf()?;
+            if read.is_empty() {
+                break;
+            }
+            let (count, len) = skip_lines(read, terminator, before_range);
+            state.line_number += count;
+            state.byte_offset += len;
+            reader.consume(len);
+            continue;
+        }
+        if let Some(literals) = &literals {
+            // the same skipping as group_lines, over what's been read so far -
+            // a line the read cut short is left for read_until to finish
+            if state.after_left == 0 && !options.passthru && !options.limited(state.selected) {
+                let read = reader.fill_buf()?;
+                let skipped = &read[..skip_to(literals, read, terminator)];
+                let count = count_lines(skipped, terminator);
+                let kept = last_lines(skipped, terminator, before);
+                for (index, range) in kept.iter().enumerate() {
+                    let number = state.line_number + count - kept.len() + index + 1;
+                    let text = String::from_utf8_lossy(&skipped[range.clone()]);
+                    let byte_offset = state.byte_offset + range.start;
+                    state.remember(
+                        before,
+                        number,
+                        byte_offset,
+                        strip_terminator(&text, terminator),
+                    );
+                }
+                state.line_number += count;
+                state.byte_offset += skipped.len();
+                let len = skipped.len();
+                reader.consume(len);
+            }
+        }
+
+        buffer.clear();
+        // a line split across reads is put back together by read_until
+        if reader.read_until(terminator as u8, &mut buffer)? == 0
+            || !state.line(matcher, options, &buffer, &mut each)?
+        {
+            break;
+        }
+    }
+    Ok(state.line_number)
+}
+
+// What search_reader keeps from one line to the next: the same window, counts
+// and limits as group_lines, but owning the lines the window keeps since the
+// buffer they were read into is reused. Whatever reads the lines hands them
+// over one at a time.
+pub(crate) struct ReadState {
+    window: VecDeque<(usize, usize, String)>,
+    after_left: usize,
+    // line number of the last line handed to `each`, 0 before the first
+    last_added: usize,
+    selected: usize,
+    // line number and byte offset of the last line read
+    pub(crate) line_number: usize,
+    byte_offset: usize,
+}
+
+impl ReadState {
+    pub(crate) fn new(options: &SearchOptions) -> ReadState {
+        ReadState {
+            window: VecDeque::with_capacity(options.before_context + 1),
+            after_left: 0,
+            last_added: 0,
+            selected: 0,
+            line_number: 0,
+            byte_offset: 0,
+        }
+    }
+
+    // search the next line (`read`, with its terminator if it has one),
+    // handing `each` what there is to print - false once no line after it is
+    // needed
+    pub(crate) fn line(
+        &mut self,
+        matcher: &Matcher,
+        options: &SearchOptions,
+        read: &[u8],
+        each: &mut impl FnMut(bool, &Line) -> io::Result<()>,
+    ) -> io::Result<bool> {
+        self.line_number += 1;
+        let line_number = self.line_number;
+        let start = self.byte_offset;
+        self.byte_offset += read.len();
+        if options.past_range(line_number) || options.stopped() {
+            return Ok(false);
+        }
+        if options.lines_before_range(line_number - 1) > 0 {
+            return Ok(true);
+        }
+        let text = String::from_utf8_lossy(read);
+        let line = strip_terminator(&text, options.terminator());
+
+        if options.limited(self.selected) {
+            if self.after_left == 0 && !options.passthru {
+                return Ok(false);
+            }
+            each(false, &Line::Context(line_number, start, line))?;
+            self.after_left = self.after_left.saturating_sub(1);
+            return Ok(true);
+        }
+
+        if matcher.is_match(line) != options.invert && options.counted() {
+            self.selected += 1;
+            let first = self
+                .window
+                .front()
+                .map_or(line_number, |(number, ..)| *number);
+            let mut new_group = self.last_added == 0 || first > self.last_added + 1;
+            for (number, byte_offset, text) in self.window.drain(..) {
+                each(new_group, &Line::Context(number, byte_offset, &text))?;
+                new_group = false;
+            }
+            // inverted lines have nothing to highlight
+            let m = Match {
+                line_number,
+                byte_offset: start,
+                line,
+                spans: if options.invert {
+                    Vec::new()
+                } else {
+                    matcher.find_spans(line)
+                },
+                score: if options.invert {
+                    None
+                } else {
+                    matcher.score(line)
+                },
+            };
+            each(new_group, &Line::Matched(m))?;
+            self.last_added = line_number;
+            self.after_left = options.after_context;
+        } else if self.after_left > 0 || options.passthru {
+            each(
+                self.last_added == 0,
+                &Line::Context(line_number, start, line),
+            )?;
+            self.last_added = line_number;
+            self.after_left = self.after_left.saturating_sub(1);
+        } else if options.before_context > 0 {
+            self.remember(options.before_context, line_number, start, line);
+        }
+        Ok(true)
+    }
+
+    // keep a line in the window of context before the next selected one
+    fn remember(&mut self, before: usize, line_number: usize, byte_offset: usize, line: &str) {
+        self.window
+            .push_back((line_number, byte_offset, line.to_string()));
+        if self.window.len() > before {
+            self.window.pop_front();
+        }
+    }
+}
+
+// the literals to skip ahead to, when every selected line has to contain one
+fn skip_with<'a>(matcher: &'a Matcher, options: &SearchOptions) -> Option<Literals<'a>> {
+    if options.invert {
+        None
+    } else {
+        matcher.literals()
+    }
+}
+
+// how many bytes of `contents` can be skipped: up to the start of the line a
+// literal is next found in, or to the end of the last whole line if none is
+fn skip_to(literals: &Literals, contents: &[u8], terminator: char) -> usize {
+    let end = literals.find(contents).unwrap_or(contents.len());
+    memchr::memrchr(terminator as u8, &contents[..end]).map_or(0, |index| index + 1)
+}
+
+// how many of the first `count` lines of `bytes` there are, and how many
+// bytes they take up - all of them when there are fewer, the last one
+// perhaps cut short
+fn skip_lines(bytes: &[u8], terminator: char, count: usize) -> (usize, usize) {
+    if count == 0 {
+        return (0, 0);
+    }
+    match memchr::memchr_iter(terminator as u8, bytes).nth(count - 1) {
+        Some(index) => (count, index + 1),
+        None => (count_lines(bytes, terminator), bytes.len()),
+    }
+}
+
+fn count_lines(skipped: &[u8], terminator: char) -> usize {
+    memchr::memchr_iter(terminator as u8, skipped).count()
+}
+
+// the ranges of the last `count` lines of `skipped` (which ends with a
+// terminator, unless it's empty), first to last
+fn last_lines(skipped: &[u8], terminator: char, count: usize) -> Vec<Range<usize>> {
+    let mut lines = Vec::new();
+    let mut end = skipped.len();
+    while lines.len() < count && end > 0 {
+        let start =
+            memchr::memrchr(terminator as u8, &skipped[..end - 1]).map_or(0, |index| index + 1);
+        lines.push(start..end);
+        end = start;
+    }
+    lines.reverse();
+    lines
+}
+
+// like str::lines, but also yields the byte offset where each line starts
+// lines end with `terminator` - "\r\n" counts as well when it's '\n'
+pub(crate) fn lines_with_offsets(
+    contents: &str,
+    terminator: char,
+) -> impl Iterator<Item = (usize, &str)> {
+    let mut offset = 0;
+    contents.split_inclusive(terminator).map(move |line| {
+        let start = offset;
+        offset += line.len();
+        (start, strip_terminator(line, terminator))
+    })
+}
+
+fn strip_terminator(line: &str, terminator: char) -> &str {
+    let text = line.strip_suffix(terminator).unwrap_or(line);
+    if terminator == '\n' {
+        text.strip_suffix('\r').unwrap_or(text)
+    } else {
+        text
+    }
+}
+
+// match against the whole buffer, then split each match up by line
+// returns the spans (relative to the start of the line) keyed by line number
+fn multiline_spans(
+    matcher: &Matcher,
+    contents: &str,
+    terminator: char,
+) -> HashMap<usize, Vec<Range<usize>>> {
+    // byte offset where each line starts, and that line's length without its terminator
+    let lines: Vec<(usize, usize)> = lines_with_offsets(contents, terminator)
+        .map(|(offset, line)| (offset, line.len()))
+        .collect();
+
+    let mut matched: HashMap<usize, Vec<Range<usize>>> = HashMap::new();
+    for span in matcher.find_spans(contents) {
+        // an empty match after the last terminator (or in empty contents) is
+        // on no line at all
+        if lines.is_empty() || (span.start == contents.len() && contents.ends_with(terminator)) {
+            continue;
+        }
+        // index of the lines holding the first and last byte of the match
+        let first = lines.partition_point(|&(start, _)| start <= span.start) - 1;
+        let last_byte = span.end.saturating_sub(1).max(span.start);
+        let last = lines.partition_point(|&(start, _)| start <= last_byte) - 1;
+
+        for (index, &(start, len)) in lines.iter().enumerate().take(last + 1).skip(first) {
+            let from = span.start.max(start) - start;
+            let to = span.end.min(start + len).max(start) - start;
+            matched
+                .entry(index + 1)
+                .or_default()
+                .push(from..to.max(from));
+        }
+    }
+    matched
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MatcherOptions;
+
+    const POEM: &str = "\
+I'm nobody! Who are you?
+Are you nobody, too?
+Then there's a pair of us - don't tell!
+They'd banish us, you know.
+";
+
+    #[test]
+    fn regex_search() {
+        let matcher = Matcher::new(r"fn\s+\w+_test", false, true).unwrap();
+        let contents = "\
+fn build() {}
+fn build_test() {}
+fn  run_test() {}";
+
+        assert_eq!(
+            vec!["fn build_test() {}", "fn  run_test() {}"],
+            search_with(&matcher, contents)
+        );
+    }
+
+    #[test]
+    fn search_with_line_numbers() {
+        let matcher = Matcher::new("duct", false, false).unwrap();
+        let contents = "\
+Rust:
+safe, fast, productive.
+Pick three.
+Duct tape, productivity.";
+
+        assert_eq!(
+            vec![
+                (2, "safe, fast, productive."),
+                (4, "Duct tape, productivity.")
+            ],
+            search_lines(&matcher, contents)
+        );
+    }
+
+    fn context(before_context: usize, after_context: usize) -> SearchOptions {
+        SearchOptions {
+            before_context,
+            after_context,
+            ..Default::default()
+        }
+    }
+
+    // line numbers in each group, with matches marked by '*'
+    fn summarize(groups: &[Vec<Line>]) -> Vec<Vec<String>> {
+        groups
+            .iter()
+            .map(|group| {
+                group
+                    .iter()
+                    .map(|line| match line {
+                        Line::Matched(m) => format!("{}*", m.line_number),
+                        Line::Context(number, ..) => number.to_string(),
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn context_groups_are_separated() {
+        let matcher = Matcher::new("match", false, false).unwrap();
+        let contents = "1\nmatch\n3\n4\n5\n6\nmatch\n8";
+
+        assert_eq!(
+            vec![vec!["1", "2*", "3"], vec!["6", "7*", "8"]],
+            summarize(&search_context(&matcher, contents, &context(1, 1)))
+        );
+    }
+
+    #[test]
+    fn overlapping_context_is_merged() {
+        let matcher = Matcher::new("match", false, false).unwrap();
+        let contents = "1\nmatch\n3\n4\nmatch\n6";
+
+        assert_eq!(
+            vec![vec!["1", "2*", "3", "4", "5*", "6"]],
+            summarize(&search_context(&matcher, contents, &context(2, 2)))
+        );
+    }
+
+    #[test]
+    fn invert_selects_non_matching_lines() {
+        let matcher = Matcher::new("us", false, false).unwrap();
+
+        let lines: Vec<&str> = search_invert(&matcher, POEM)
+            .iter()
+            .map(|m| m.line)
+            .collect();
+        assert_eq!(
+            vec!["I'm nobody! Who are you?", "Are you nobody, too?"],
+            lines
+        );
+    }
+
+    #[test]
+    fn only_matches_within_the_ranges_count() {
+        let matcher = Matcher::new("TODO", false, false).unwrap();
+        let contents = "TODO: x\nx // TODO\ny\nTODO // TODO";
+        // where the comments are
+        let within = [10..17, 25..32];
+
+        let groups = search_context_within(&matcher, contents, &context(1, 0), &within);
+        assert_eq!(vec![vec!["1", "2*", "3", "4*"]], summarize(&groups));
+        let Line::Matched(m) = &groups[0][3] else {
+            panic!("line 4 should match")
+        };
+        assert_eq!(vec![8..12], m.spans);
+
+        let options = SearchOptions {
+            invert: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            vec![vec!["1*"], vec!["3*"]],
+            summarize(&search_context_within(
+                &matcher, contents, &options, &within
+            ))
+        );
+    }
+
+    #[test]
+    fn invert_with_context() {
+        let matcher = Matcher::new("match", false, false).unwrap();
+        let contents = "match\nmatch\n3\nmatch";
+        let options = SearchOptions {
+            invert: true,
+            before_context: 1,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            vec![vec!["2", "3*"]],
+            summarize(&search_context(&matcher, contents, &options))
+        );
+    }
+
+    #[test]
+    fn multiline_match_covers_each_line() {
+        let options = MatcherOptions {
+            regex: true,
+            multiline: true,
+            ..Default::default()
+        };
+        let matcher = Matcher::with_options(r"build\(\n\s+args", &options).unwrap();
+        let contents = "fn main() {\nlet c = build(\n    args);\n}";
+        let options = SearchOptions {
+            multiline: true,
+            ..Default::default()
+        };
+
+        let groups = search_context(&matcher, contents, &options);
+        let spans: Vec<(usize, Range<usize>)> = groups
+            .into_iter()
+            .flatten()
+            .flat_map(|line| match line {
+                Line::Matched(m) => m.spans.into_iter().map(move |span| (m.line_number, span)),
+                Line::Context(number, ..) => panic!("unexpected context line {number}"),
+            })
+            .collect();
+        assert_eq!(vec![(2, 8..14), (3, 0..8)], spans);
+    }
+
+    #[test]
+    fn empty_matches_with_no_line_to_be_on() {
+        let options = MatcherOptions {
+            regex: true,
+            multiline: true,
+            ..Default::default()
+        };
+        let search = SearchOptions {
+            multiline: true,
+            ..Default::default()
+        };
+        for pattern in ["x*", "^"] {
+            let matcher = Matcher::with_options(pattern, &options).unwrap();
+            assert!(search_context(&matcher, "", &search).is_empty());
+
+            // none after the last line's terminator either
+            let groups = search_context(&matcher, "a\n", &search);
+            assert_eq!(vec![vec!["1*"]], summarize(&groups));
+            match &groups[0][0] {
+                Line::Matched(m) => assert!(m.spans.iter().all(|span| span.end <= m.line.len())),
+                Line::Context(..) => unreachable!(),
+            }
+        }
+    }
+
+    #[test]
+    fn crlf_lets_dollar_match_before_carriage_returns() {
+        let options = MatcherOptions {
+            regex: true,
+            multiline: true,
+            crlf: true,
+            ..Default::default()
+        };
+        let matcher = Matcher::with_options(r"you\?$", &options).unwrap();
+        let contents = "Who are you?\r\nAre you nobody, too?\r\n";
+        let options = SearchOptions {
+            multiline: true,
+            ..Default::default()
+        };
+
+        let groups = search_context(&matcher, contents, &options);
+        assert_eq!(vec![vec!["1*"]], summarize(&groups));
+        match &groups[0][0] {
+            Line::Matched(m) => {
+                assert_eq!(("Who are you?", Some(&(8..12))), (m.line, m.spans.first()))
+            }
+            line => panic!("unexpected line {line:?}"),
+        }
+    }
+
+    #[test]
+    fn null_data_splits_records_on_nul() {
+        let matcher = Matcher::new("you", false, false).unwrap();
+        let contents = "I'm nobody!\nWho are you?\0Are we\0nobody too?\0";
+        let options = SearchOptions {
+            null_data: true,
+            ..Default::default()
+        };
+
+        let groups = search_context(&matcher, contents, &options);
+        assert_eq!(vec![vec!["1*"]], summarize(&groups));
+        match &groups[0][0] {
+            Line::Matched(m) => assert_eq!("I'm nobody!\nWho are you?", m.line),
+            line => panic!("unexpected line {line:?}"),
+        }
+    }
+
+    #[test]
+    fn max_count_stops_after_trailing_context() {
+        let matcher = Matcher::new("match", false, false).unwrap();
+        let contents = "match\n2\nmatch\n4\nmatch\nmatch";
+        let options = SearchOptions {
+            after_context: 1,
+            max_count: Some(2),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            vec![vec!["1*", "2", "3*", "4"]],
+            summarize(&search_context(&matcher, contents, &options))
+        );
+    }
+
+    #[test]
+    fn only_lines_in_the_range_are_searched() {
+        let matcher = Matcher::new("match", false, false).unwrap();
+        let contents = "match\n2\nmatch\n4\nmatch\nmatch";
+        let options = SearchOptions {
+            before_context: 1,
+            line_range: Some(3..=5),
+            ..Default::default()
+        };
+        // nor is context from outside it
+        assert_eq!(
+            vec![vec!["3*", "4", "5*"]],
+            summarize(&search_context(&matcher, contents, &options))
+        );
+
+        let options = SearchOptions {
+            line_range: Some(5..=usize::MAX),
+            ..Default::default()
+        };
+        assert_eq!(
+            vec![vec!["5*", "6*"]],
+            summarize(&search_context(&matcher, contents, &options))
+        );
+    }
+
+    #[test]
+    fn matches_know_their_byte_offset() {
+        let matcher = Matcher::new("too", false, false).unwrap();
+
+        let offsets: Vec<usize> = search_matches(&matcher, POEM)
+            .iter()
+            .map(|m| m.byte_offset)
+            .collect();
+        assert_eq!(vec![25], offsets);
+    }
+
+    #[test]
+    fn has_match_respects_invert() {
+        let matcher = Matcher::new("nobody", false, false).unwrap();
+        let invert = SearchOptions {
+            invert: true,
+            ..Default::default()
+        };
+
+        assert!(has_match(&matcher, POEM, &SearchOptions::default()));
+        assert!(has_match(&matcher, POEM, &invert));
+        assert!(!has_match(&matcher, "nobody\nnobody", &invert));
+    }
+
+    #[test]
+    fn passthru_keeps_every_line() {
+        let matcher = Matcher::new("match", false, false).unwrap();
+        let contents = "1\nmatch\n3\n4\nmatch";
+        let options = SearchOptions {
+            passthru: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            vec![vec!["1", "2*", "3", "4", "5*"]],
+            summarize(&search_context(&matcher, contents, &options))
+        );
+    }
+
+    #[test]
+    fn reading_as_it_goes_finds_the_same_groups() {
+        let matcher = Matcher::new("nobody|us", false, true).unwrap();
+        let options = [
+            context(0, 0),
+            context(1, 0),
+            context(0, 1),
+            SearchOptions {
+                invert: true,
+                max_count: Some(1),
+                after_context: 1,
+                ..Default::default()
+            },
+            SearchOptions {
+                before_context: 1,
+                line_range: Some(2..=3),
+                ..Default::default()
+            },
+        ];
+        for options in options {
+            let mut groups: Vec<Vec<String>> = Vec::new();
+            // a tiny buffer, so lines are split across reads
+            let reader = io::BufReader::with_capacity(4, POEM.as_bytes());
+            let lines = search_reader(&matcher, reader, &options, |new_group, line| {
+                if new_group {
+                    groups.push(Vec::new());
+                }
+                groups.last_mut().unwrap().push(match line {
+                    Line::Matched(m) => format!("{}*", m.line_number),
+                    Line::Context(number, ..) => number.to_string(),
+                });
+                Ok(())
+            })
+            .unwrap();
+            assert!(lines <= 4);
+            assert_eq!(summarize(&search_context(&matcher, POEM, &options)), groups);
+        }
+    }
+
+    #[test]
+    fn literals_skip_to_the_lines_they_are_in() {
+        // a regex is checked line by line, so it's what skipping should match
+        let patterns = ["us".to_string(), "nobody".to_string()];
+        let matchers = [
+            (
+                Matcher::new("us", false, false).unwrap(),
+                Matcher::new("u[s]", false, true).unwrap(),
+            ),
+            (
+                Matcher::with_patterns(&patterns, &crate::MatcherOptions::default()).unwrap(),
+                Matcher::new("u[s]|nobod[y]", false, true).unwrap(),
+            ),
+        ];
+        let contents = format!("{POEM}\r\n{}no match at the end", POEM.repeat(3));
+        let options = [
+            context(0, 0),
+            context(2, 0),
+            context(1, 1),
+            context(5, 0),
+            SearchOptions {
+                max_count: Some(3),
+                after_context: 1,
+                ..Default::default()
+            },
+        ];
+        for ((literal, regex), options) in matchers
+            .iter()
+            .flat_map(|matchers| options.iter().map(move |options| (matchers, options)))
+        {
+            let groups = search_context(literal, &contents, options);
+            assert_eq!(search_context(regex, &contents, options), groups);
+
+            let mut read: Vec<Vec<String>> = Vec::new();
+            let reader = io::BufReader::with_capacity(16, contents.as_bytes());
+            search_reader(literal, reader, options, |new_group, line| {
+                if new_group {
+                    read.push(Vec::new());
+                }
+                read.last_mut().unwrap().push(format!("{line:?}"));
+                Ok(())
+            })
+            .unwrap();
+            let groups: Vec<Vec<String>> = groups
+                .iter()
+                .map(|group| group.iter().map(|line| format!("{line:?}")).collect())
+                .collect();
+            assert_eq!(groups, read);
+        }
+    }
+
+    #[test]
+    fn the_total_limit_is_shared_between_searches() {
+        let matcher = Matcher::new("you", false, false).unwrap();
+        let options = SearchOptions {
+            after_context: 1,
+            total: Some(Arc::new(TotalLimit::new(3))),
+            ..Default::default()
+        };
+        // the first two lines take two of the three, leaving one for the next
+        // search - after which a match is only context
+        let first = search_context(&matcher, &POEM[..POEM.find("Then").unwrap()], &options);
+        let second = search_context(&matcher, POEM, &options);
+        assert_eq!(vec![vec!["1*", "2*"]], summarize(&first));
+        assert_eq!(vec![vec!["1*", "2"]], summarize(&second));
+        assert!(search_context(&matcher, POEM, &options).is_empty());
+    }
+
+    #[test]
+    fn searches_give_up_at_the_deadline() {
+        let matcher = Matcher::new("you", false, false).unwrap();
+        let options = |timeout| SearchOptions {
+            deadline: Some(Arc::new(Deadline::after(timeout))),
+            ..Default::default()
+        };
+
+        let plenty = options(Duration::from_secs(60));
+        assert_eq!(2, search_context(&matcher, POEM, &plenty).len());
+        assert!(!plenty.deadline.as_ref().unwrap().passed());
+
+        // past as soon as the first line is looked at
+        let none = options(Duration::ZERO);
+        assert!(search_context(&matcher, POEM, &none).is_empty());
+        assert!(none.deadline.as_ref().unwrap().passed());
+        let none = options(Duration::ZERO);
+        let mut lines = 0;
+        search_reader(&matcher, POEM.as_bytes(), &none, |_, _| {
+            lines += 1;
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(0, lines);
+        assert!(none.deadline.as_ref().unwrap().passed());
+    }
+
+    // finds "us", and is interrupted by the line it's found in that starts
+    // with "Then"
+    struct InterruptedAtThen(&'static AtomicBool);
+
+    impl crate::Engine for InterruptedAtThen {
+        fn find_at(&self, line: &str, start: usize) -> Option<Range<usize>> {
+            if line.starts_with("Then") {
+                self.0.store(true, Ordering::SeqCst);
+            }
+            let index = start + line[start..].find("us")?;
+            Some(index..index + 2)
+        }
+    }
+
+    #[test]
+    fn searches_stop_at_the_line_they_are_interrupted_in() {
+        static GROUPED: AtomicBool = AtomicBool::new(false);
+        static READ: AtomicBool = AtomicBool::new(false);
+        let options = |interrupt| SearchOptions {
+            interrupt: Some(interrupt),
+            ..Default::default()
+        };
+
+        // the last line has "us" too, but isn't looked at
+        let matcher = Matcher::with_engine(InterruptedAtThen(&GROUPED), &Default::default());
+        let groups = search_context(&matcher, POEM, &options(&GROUPED));
+        assert_eq!(vec![vec!["3*"]], summarize(&groups));
+
+        let matcher = Matcher::with_engine(InterruptedAtThen(&READ), &Default::default());
+        let mut lines = Vec::new();
+        search_reader(&matcher, POEM.as_bytes(), &options(&READ), |_, line| {
+            if let Line::Matched(m) = line {
+                lines.push(m.line_number);
+            }
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(vec![3], lines);
+    }
+}
@@ -0,0 +1,443 @@
+#[cfg(feature = "cli")]
+use std::fs::File;
+#[cfg(feature = "cli")]
+use std::io::BufReader;
+use std::io::{self, BufRead};
+use std::ops::RangeInclusive;
+#[cfg(feature = "cli")]
+use std::path::Path;
+
+use crate::search::{search_context, search_reader, Line, ReadState, SearchOptions};
+use crate::sink::Sink;
+use crate::{Matcher, MatcherOptions};
+
+// A Searcher for embedding minigrep: it's set up once with a SearcherBuilder
+// (how to treat case, whether to invert, how much context and so on) and can
+// then search any number of files, readers or strings the same way.
+//
+//     let searcher = SearcherBuilder::new()
+//         .case(CaseMode::Smart)
+//         .context(1, 1)
+//         .build(&["nobody".to_string()])?;
+//     let groups = searcher.search_slice(contents);
+
+// how the case of letters is matched
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CaseMode {
+    #[default]
+    Sensitive,
+    Insensitive,
+    // insensitive unless a pattern has a capital letter in it (like -S)
+    Smart,
+}
+
+#[derive(Debug, Clone)]
+pub struct SearcherBuilder {
+    case: CaseMode,
+    matcher: MatcherOptions,
+    search: SearchOptions,
+}
+
+impl SearcherBuilder {
+    // patterns are regexes, matched case sensitively, with no context
+    pub fn new() -> SearcherBuilder {
+        SearcherBuilder {
+            case: CaseMode::Sensitive,
+            matcher: MatcherOptions {
+                regex: true,
+                ..Default::default()
+            },
+            search: SearchOptions::default(),
+        }
+    }
+
+    pub fn case(&mut self, case: CaseMode) -> &mut SearcherBuilder {
+        self.case = case;
+        self
+    }
+
+    // treat the patterns as regular expressions (true) or literal strings
+    pub fn regex(&mut self, regex: bool) -> &mut SearcherBuilder {
+        self.matcher.regex = regex;
+        self
+    }
+
+    pub fn invert(&mut self, invert: bool) -> &mut SearcherBuilder {
+        self.search.invert = invert;
+        self
+    }
+
+    // lines of context before and after each selected line
+    pub fn context(&mut self, before: usize, after: usize) -> &mut SearcherBuilder {
+        self.search.before_context = before;
+        self.search.after_context = after;
+        self
+    }
+
+    pub fn max_count(&mut self, max_count: Option<usize>) -> &mut SearcherBuilder {
+        self.search.max_count = max_count;
+        self
+    }
+
+    // only search the lines numbered in `range` (from 1)
+    pub fn line_range(&mut self, range: Option<RangeInclusive<usize>>) -> &mut SearcherBuilder {
+        self.search.line_range = range;
+        self
+    }
+
+    // let matches span several lines - the contents are then searched whole,
+    // even the ones from a reader
+    pub fn multiline(&mut self, multiline: bool) -> &mut SearcherBuilder {
+        self.matcher.multiline = multiline;
+        self.search.multiline = multiline;
+        self
+    }
+
+    // a line is selected if any one of the patterns matches
+    pub fn build(&self, patterns: &[String]) -> Result<Searcher, regex::Error> {
+        let ignore_case = match self.case {
+            CaseMode::Sensitive => false,
+            CaseMode::Insensitive => true,
+            CaseMode::Smart => !patterns
+                .iter()
+                .any(|pattern| pattern.chars().any(char::is_uppercase)),
+        };
+        let options = MatcherOptions {
+            ignore_case,
+            ..self.matcher.clone()
+        };
+        Ok(Searcher {
+            matcher: Matcher::with_patterns(patterns, &options)?,
+            options: self.search.clone(),
+        })
+    }
+}
+
+impl Default for SearcherBuilder {
+    fn default() -> SearcherBuilder {
+        SearcherBuilder::new()
+    }
+}
+
+pub struct Searcher {
+    matcher: Matcher,
+    options: SearchOptions,
+}
+
+impl Searcher {
+    // the selected lines (and their context) in groups of consecutive lines
+    pub fn search_slice<'a>(&self, contents: &'a str) -> Vec<Vec<Line<'a>>> {
+        search_context(&self.matcher, contents, &self.options)
+    }
+
+    // hand each line to print to `each` as it's read, along with whether it
+    // starts a new group, returning how many lines were read
+    // (text that isn't UTF-8 is searched with U+FFFD in place of the bad bytes)
+    pub fn search_reader(
+        &self,
+        mut reader: impl BufRead,
+        mut each: impl FnMut(bool, &Line) -> io::Result<()>,
+    ) -> io::Result<usize> {
+        if !self.options.multiline {
+            return search_reader(&self.matcher, reader, &self.options, each);
+        }
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        let contents = String::from_utf8_lossy(&bytes);
+        for group in self.search_slice(&contents) {
+            for (index, line) in group.iter().enumerate() {
+                each(index == 0, line)?;
+            }
+        }
+        Ok(contents
+            .split_inclusive(if self.options.null_data { '\0' } else { '\n' })
+            .count())
+    }
+
+    #[cfg(feature = "cli")]
+    pub fn search_file(
+        &self,
+        path: impl AsRef<Path>,
+        each: impl FnMut(bool, &Line) -> io::Result<()>,
+    ) -> io::Result<usize> {
+        self.search_reader(BufReader::new(File::open(path)?), each)
+    }
+
+    // the same searches, sending what's found to `sink` between its
+    // begin_file and end_file
+    pub fn search_slice_to(
+        &self,
+        path: &str,
+        contents: &str,
+        sink: &mut dyn Sink,
+    ) -> io::Result<()> {
+        sink.begin_file(path)?;
+        for (index, group) in self.search_slice(contents).iter().enumerate() {
+            if index > 0 && self.has_context() {
+                sink.context_break()?;
+            }
+            for line in group {
+                send(sink, line)?;
+            }
+        }
+        sink.end_file(path)
+    }
+
+    pub fn search_reader_to(
+        &self,
+        path: &str,
+        reader: impl BufRead,
+        sink: &mut dyn Sink,
+    ) -> io::Result<usize> {
+        sink.begin_file(path)?;
+        let mut started = false;
+        let lines = self.search_reader(reader, |new_group, line| {
+            if new_group && started && self.has_context() {
+                sink.context_break()?;
+            }
+            started = true;
+            send(sink, line)
+        })?;
+        sink.end_file(path)?;
+        Ok(lines)
+    }
+
+    #[cfg(feature = "cli")]
+    pub fn search_file_to(&self, path: impl AsRef<Path>, sink: &mut dyn Sink) -> io::Result<usize> {
+        let path = path.as_ref();
+        let reader = BufReader::new(File::open(path)?);
+        self.search_reader_to(&path.to_string_lossy(), reader, sink)
+    }
+
+    // each of the files in turn, and then the sink's finish
+    #[cfg(feature = "cli")]
+    pub fn search_files_to(
+        &self,
+        paths: &[impl AsRef<Path>],
+        sink: &mut dyn Sink,
+    ) -> io::Result<()> {
+        for path in paths {
+            self.search_file_to(path, sink)?;
+        }
+        sink.finish()
+    }
+
+    // a search that's given its input a chunk at a time instead of reading
+    // it, for a caller doing its own reading - like an async service, which
+    // can hand over each chunk its runtime reads without a thread waiting
+    // in a BufRead for the next one
+    pub fn feed(&self) -> Feed<'_> {
+        Feed {
+            searcher: self,
+            state: ReadState::new(&self.options),
+            pending: Vec::new(),
+            done: false,
+        }
+    }
+
+    // groups are only told apart when there's context around them
+    fn has_context(&self) -> bool {
+        self.options.before_context > 0 || self.options.after_context > 0
+    }
+}
+
+// Lines are searched as soon as they're complete, giving `each` the same as
+// search_reader would, so only the last, unfinished line is held between
+// chunks. With multiline the input is searched whole when it's finished.
+pub struct Feed<'s> {
+    searcher: &'s Searcher,
+    state: ReadState,
+    // the start of a line the next chunk finishes
+    pending: Vec<u8>,
+    done: bool,
+}
+
+impl Feed<'_> {
+    pub fn push(
+        &mut self,
+        chunk: &[u8],
+        mut each: impl FnMut(bool, &Line) -> io::Result<()>,
+    ) -> io::Result<()> {
+        if self.done {
+            return Ok(());
+        }
+        self.pending.extend_from_slice(chunk);
+        if self.searcher.options.multiline {
+            return Ok(());
+        }
+        let terminator = self.searcher.options.terminator() as u8;
+        let mut start = 0;
+        while let Some(index) = memchr::memchr(terminator, &self.pending[start..]) {
+            let line = &self.pending[start..start + index + 1];
+            start += index + 1;
+            if !self.state.line(
+                &self.searcher.matcher,
+                &self.searcher.options,
+                line,
+                &mut each,
+            )? {
+                self.done = true;
+                self.pending.clear();
+                return Ok(());
+            }
+        }
+        self.pending.drain(..start);
+        Ok(())
+    }
+
+    // whether the search has all it needs (it's reached the max count, say),
+    // so the rest of the input needn't be read
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    // the end of the input: search what's left, and return how many lines
+    // there were
+    pub fn finish(
+        mut self,
+        mut each: impl FnMut(bool, &Line) -> io::Result<()>,
+    ) -> io::Result<usize> {
+        if self.searcher.options.multiline {
+            return self.searcher.search_reader(&self.pending[..], each);
+        }
+        if !self.done && !self.pending.is_empty() {
+            let line = std::mem::take(&mut self.pending);
+            self.state.line(
+                &self.searcher.matcher,
+                &self.searcher.options,
+                &line,
+                &mut each,
+            )?;
+        }
+        Ok(self.state.line_number)
+    }
+}
+
+fn send(sink: &mut dyn Sink, line: &Line) -> io::Result<()> {
+    match line {
+        Line::Matched(m) => sink.matched(m),
+        Line::Context(line_number, byte_offset, text) => {
+            sink.context(*line_number, *byte_offset, text)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POEM: &str = "\
+I'm nobody! Who are you?
+Are you nobody, too?
+Then there's a pair of us - don't tell!
+They'd banish us, you know.
+";
+
+    // "*" marks the selected lines, and groups start with a "|"
+    fn summarize(searcher: &Searcher, reader: impl BufRead) -> Vec<String> {
+        let mut lines = Vec::new();
+        searcher
+            .search_reader(reader, |new_group, line| {
+                let mark = if new_group { "|" } else { "" };
+                lines.push(match line {
+                    Line::Matched(m) => format!("{mark}{}*", m.line_number),
+                    Line::Context(number, ..) => format!("{mark}{number}"),
+                });
+                Ok(())
+            })
+            .unwrap();
+        lines
+    }
+
+    #[test]
+    fn a_searcher_is_set_up_once() {
+        let searcher = SearcherBuilder::new()
+            .case(CaseMode::Smart)
+            .context(0, 1)
+            .build(&["THEN".to_string()])
+            .unwrap();
+        assert!(searcher.search_slice(POEM).is_empty());
+
+        let searcher = SearcherBuilder::new()
+            .case(CaseMode::Smart)
+            .context(0, 1)
+            .max_count(Some(1))
+            .build(&["you".to_string()])
+            .unwrap();
+        assert_eq!(vec!["|1*", "2"], summarize(&searcher, POEM.as_bytes()));
+        assert_eq!(1, searcher.search_slice(POEM).len());
+    }
+
+    #[test]
+    fn selected_lines_say_where_each_match_is() {
+        let searcher = SearcherBuilder::new()
+            .context(1, 0)
+            .build(&["you|us".to_string()])
+            .unwrap();
+        let groups = searcher.search_slice(POEM);
+
+        let spans: Vec<_> = groups
+            .iter()
+            .flatten()
+            .map(|line| match line {
+                Line::Matched(m) => {
+                    let spans = m.spans.iter().map(|span| (span.start, span.end));
+                    (m.line_number, spans.collect())
+                }
+                Line::Context(number, ..) => (*number, Vec::new()),
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                (1, vec![(20, 23)]),
+                (2, vec![(4, 7)]),
+                (3, vec![(23, 25)]),
+                (4, vec![(14, 16), (18, 21)])
+            ],
+            spans
+        );
+    }
+
+    #[test]
+    fn fed_chunks_are_searched_like_a_reader() {
+        let mut builder = SearcherBuilder::new();
+        builder.context(1, 1);
+        for max_count in [None, Some(2)] {
+            let searcher = builder
+                .max_count(max_count)
+                .build(&["nobody|banish".to_string()])
+                .unwrap();
+            let contents = format!("{POEM}no break at the end");
+            for size in [1, 7, contents.len()] {
+                let mut feed = searcher.feed();
+                let mut lines = Vec::new();
+                let mut each = |new_group: bool, line: &Line| {
+                    let mark = if new_group { "|" } else { "" };
+                    lines.push(match line {
+                        Line::Matched(m) => format!("{mark}{}*", m.line_number),
+                        Line::Context(number, ..) => format!("{mark}{number}"),
+                    });
+                    Ok(())
+                };
+                for chunk in contents.as_bytes().chunks(size) {
+                    feed.push(chunk, &mut each).unwrap();
+                }
+                let done = feed.is_done();
+                let read = feed.finish(&mut each).unwrap();
+                assert_eq!(summarize(&searcher, contents.as_bytes()), lines);
+                assert_eq!(max_count.is_some(), done);
+                assert_eq!(if done { 4 } else { 5 }, read);
+            }
+        }
+    }
+
+    #[test]
+    fn multiline_readers_are_searched_whole() {
+        let searcher = SearcherBuilder::new()
+            .multiline(true)
+            .build(&[r"too\?\nThen".to_string()])
+            .unwrap();
+
+        assert_eq!(vec!["|2*", "3*"], summarize(&searcher, POEM.as_bytes()));
+    }
+}
@@ -0,0 +1,59 @@
+#[cfg(unix)]
+use std::os::raw::c_int;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Ctrl-C while searching stops at the next line instead of killing minigrep
+// mid-write, so the lines found so far (and --stats) still come out whole. A
+// second Ctrl-C exits straight away.
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+// the exit code after an interrupt, the one a shell gives a command SIGINT killed
+pub const EXIT_CODE: i32 = 130;
+
+#[cfg(unix)]
+mod sys {
+    use std::os::raw::c_int;
+
+    pub const SIGINT: c_int = 2;
+    pub const SIG_IGN: usize = 1;
+
+    extern "C" {
+        // from the C library std already links against
+        pub fn signal(signum: c_int, handler: usize) -> usize;
+        pub fn _exit(status: c_int) -> !;
+    }
+}
+
+#[cfg(unix)]
+extern "C" fn on_interrupt(_signum: c_int) {
+    // only async-signal-safe things in here: an atomic and _exit
+    if INTERRUPTED.swap(true, Ordering::SeqCst) {
+        unsafe { sys::_exit(EXIT_CODE) }
+    }
+}
+
+// from now on Ctrl-C sets interrupted() instead of killing the process -
+// unless it was being ignored, like in a background job, which it still is
+#[cfg(unix)]
+pub fn catch_interrupt() {
+    let handler: extern "C" fn(c_int) = on_interrupt;
+    unsafe {
+        if sys::signal(sys::SIGINT, handler as usize) == sys::SIG_IGN {
+            sys::signal(sys::SIGINT, sys::SIG_IGN);
+        }
+    }
+}
+
+// elsewhere Ctrl-C still ends the process the usual way
+#[cfg(not(unix))]
+pub fn catch_interrupt() {}
+
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+// what interrupted() looks at, for searches to stop at the next line
+pub fn flag() -> &'static AtomicBool {
+    &INTERRUPTED
+}
@@ -0,0 +1,296 @@
+use std::io::{self, Write};
+
+use crate::format::json_string;
+use crate::{Match, Printer};
+
+// A Sink is where a Searcher sends what it finds, so what's done with the
+// results is up to the caller: printed the way minigrep prints them
+// (Standard), as JSON (Json), just counted (Count) or collected into
+// anything else by implementing it. Everything but `matched` can be ignored.
+pub trait Sink {
+    // a file is about to be searched
+    fn begin_file(&mut self, _path: &str) -> io::Result<()> {
+        Ok(())
+    }
+
+    // a selected line, with where the matches are in it
+    fn matched(&mut self, m: &Match) -> io::Result<()>;
+
+    // a line of context around the selected ones
+    fn context(&mut self, _line_number: usize, _byte_offset: usize, _line: &str) -> io::Result<()> {
+        Ok(())
+    }
+
+    // the lines that follow aren't next to the ones before, with context on
+    // (where minigrep prints "--")
+    fn context_break(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn end_file(&mut self, _path: &str) -> io::Result<()> {
+        Ok(())
+    }
+
+    // every file has been searched
+    fn finish(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// the lines printed the way minigrep prints them, as the printer says
+pub struct Standard<W: Write> {
+    printer: Printer,
+    out: W,
+    path: String,
+    // the heading goes above a file's first line, so files without any
+    // don't get one
+    heading_due: bool,
+}
+
+impl<W: Write> Standard<W> {
+    pub fn new(printer: Printer, out: W) -> Standard<W> {
+        Standard {
+            printer,
+            out,
+            path: String::new(),
+            heading_due: false,
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    fn heading(&mut self) -> io::Result<()> {
+        if std::mem::take(&mut self.heading_due) {
+            self.printer.print_heading(&mut self.out, &self.path)?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Sink for Standard<W> {
+    fn begin_file(&mut self, path: &str) -> io::Result<()> {
+        self.path = path.to_string();
+        self.heading_due = self.printer.heading && self.printer.show_filename;
+        Ok(())
+    }
+
+    fn matched(&mut self, m: &Match) -> io::Result<()> {
+        self.heading()?;
+        self.printer.print_match(&mut self.out, &self.path, m)
+    }
+
+    fn context(&mut self, line_number: usize, byte_offset: usize, line: &str) -> io::Result<()> {
+        self.heading()?;
+        self.printer
+            .print_context(&mut self.out, &self.path, line_number, byte_offset, line)
+    }
+
+    fn context_break(&mut self) -> io::Result<()> {
+        self.printer.print_context_break(&mut self.out)
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+// a JSON object on its own line for each line found, like
+// {"type":"match","path":"poem.txt","line_number":1,"text":"...","spans":[{"start":4,"end":10}]}
+// ("context" lines have no spans)
+pub struct Json<W: Write> {
+    out: W,
+    path: String,
+}
+
+impl<W: Write> Json<W> {
+    pub fn new(out: W) -> Json<W> {
+        Json {
+            out,
+            path: String::new(),
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    fn line(
+        &mut self,
+        kind: &str,
+        line_number: usize,
+        text: &str,
+        spans: &[String],
+    ) -> io::Result<()> {
+        writeln!(
+            self.out,
+            "{{\"type\":\"{kind}\",\"path\":{},\"line_number\":{line_number},\"text\":{},\"spans\":[{}]}}",
+            json_string(&self.path),
+            json_string(text),
+            spans.join(",")
+        )
+    }
+}
+
+impl<W: Write> Sink for Json<W> {
+    fn begin_file(&mut self, path: &str) -> io::Result<()> {
+        self.path = path.to_string();
+        Ok(())
+    }
+
+    fn matched(&mut self, m: &Match) -> io::Result<()> {
+        let spans: Vec<String> = m
+            .spans
+            .iter()
+            .map(|span| format!("{{\"start\":{},\"end\":{}}}", span.start, span.end))
+            .collect();
+        self.line("match", m.line_number, m.line, &spans)
+    }
+
+    fn context(&mut self, line_number: usize, _byte_offset: usize, line: &str) -> io::Result<()> {
+        self.line("context", line_number, line, &[])
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+// how many lines were selected in each file, printed as "path:count" once
+// it's been searched (like grep -c), and in all
+pub struct Count<W: Write> {
+    out: W,
+    count: usize,
+    total: usize,
+}
+
+impl<W: Write> Count<W> {
+    pub fn new(out: W) -> Count<W> {
+        Count {
+            out,
+            count: 0,
+            total: 0,
+        }
+    }
+
+    // the selected lines in every file so far
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+impl<W: Write> Sink for Count<W> {
+    fn begin_file(&mut self, _path: &str) -> io::Result<()> {
+        self.count = 0;
+        Ok(())
+    }
+
+    fn matched(&mut self, _m: &Match) -> io::Result<()> {
+        self.count += 1;
+        self.total += 1;
+        Ok(())
+    }
+
+    fn end_file(&mut self, path: &str) -> io::Result<()> {
+        writeln!(self.out, "{path}:{}", self.count)
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Colors, Delimiters, SearcherBuilder};
+
+    const POEM: &str = "\
+I'm nobody! Who are you?
+Are you nobody, too?
+Then there's a pair of us - don't tell!
+They'd banish us, you know.
+";
+
+    fn printer() -> Printer {
+        Printer {
+            show_filename: true,
+            line_number: true,
+            byte_offset: false,
+            column: false,
+            color: false,
+            colors: Colors::default(),
+            show_score: false,
+            only_matching: false,
+            null: false,
+            null_data: false,
+            heading: false,
+            max_columns: None,
+            max_columns_preview: false,
+            trim: false,
+            delimiters: Delimiters::default(),
+        }
+    }
+
+    #[test]
+    fn sinks_get_what_the_searcher_finds() {
+        let searcher = SearcherBuilder::new()
+            .context(0, 1)
+            .build(&["Who|Then".to_string()])
+            .unwrap();
+
+        let mut standard = Standard::new(printer(), Vec::new());
+        searcher
+            .search_reader_to("poem.txt", POEM.as_bytes(), &mut standard)
+            .unwrap();
+        assert_eq!(
+            "poem.txt:1: I'm nobody! Who are you?\n\
+             poem.txt-2- Are you nobody, too?\n\
+             poem.txt:3: Then there's a pair of us - don't tell!\n\
+             poem.txt-4- They'd banish us, you know.\n",
+            String::from_utf8(standard.into_inner()).unwrap()
+        );
+
+        let mut json = Json::new(Vec::new());
+        searcher
+            .search_slice_to("poem.txt", "Who\nwhat\n", &mut json)
+            .unwrap();
+        assert_eq!(
+            "{\"type\":\"match\",\"path\":\"poem.txt\",\"line_number\":1,\"text\":\"Who\",\"spans\":[{\"start\":0,\"end\":3}]}\n\
+             {\"type\":\"context\",\"path\":\"poem.txt\",\"line_number\":2,\"text\":\"what\",\"spans\":[]}\n",
+            String::from_utf8(json.into_inner()).unwrap()
+        );
+    }
+
+    #[test]
+    fn groups_apart_are_broken_up() {
+        let searcher = SearcherBuilder::new()
+            .context(0, 1)
+            .build(&["nobody!|banish".to_string()])
+            .unwrap();
+        let mut standard = Standard::new(printer(), Vec::new());
+        searcher
+            .search_slice_to("poem.txt", POEM, &mut standard)
+            .unwrap();
+        assert_eq!(
+            "poem.txt:1: I'm nobody! Who are you?\n\
+             poem.txt-2- Are you nobody, too?\n\
+             --\n\
+             poem.txt:4: They'd banish us, you know.\n",
+            String::from_utf8(standard.into_inner()).unwrap()
+        );
+
+        let mut count = Count::new(Vec::new());
+        searcher.search_slice_to("a", POEM, &mut count).unwrap();
+        searcher
+            .search_slice_to("b", "nobody!", &mut count)
+            .unwrap();
+        assert_eq!(3, count.total());
+        assert_eq!("a:2\nb:1\n", String::from_utf8(count.into_inner()).unwrap());
+    }
+}
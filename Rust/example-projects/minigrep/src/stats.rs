@@ -0,0 +1,267 @@
+use std::fmt;
+use std::time::Duration;
+
+// why a file wasn't searched
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SkipReason {
+    // a binary file found in a directory
+    Binary,
+    // it couldn't be read (or fetched, or run through its preprocessor)
+    Unreadable,
+    // --write or --diff left it alone, since it couldn't be written back as it was
+    NotReplaced,
+    // --only-in doesn't know its language
+    UnknownLanguage,
+    // the rest are left out by the directory walk, without being read:
+    // a dotfile or dot-directory (see --hidden)
+    Hidden,
+    // by an ignore file or git's excludes
+    Ignored,
+    // by --glob or --type
+    Filtered,
+    // a symlink that isn't followed (see --follow)
+    Symlink,
+    // bigger than --max-filesize
+    TooBig,
+    // a directory at --max-depth, or on another filesystem with --one-file-system
+    NotDescended,
+}
+
+impl SkipReason {
+    fn describe(self) -> &'static str {
+        match self {
+            SkipReason::Binary => "binary",
+            SkipReason::Unreadable => "unreadable",
+            SkipReason::NotReplaced => "not replaced",
+            SkipReason::UnknownLanguage => "unknown language",
+            SkipReason::Hidden => "hidden",
+            SkipReason::Ignored => "ignored",
+            SkipReason::Filtered => "filtered out",
+            SkipReason::Symlink => "symlink",
+            SkipReason::TooBig => "over --max-filesize",
+            SkipReason::NotDescended => "not descended into",
+        }
+    }
+}
+
+// what a search did - run returns these, and --stats prints them at the end
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Stats {
+    // whether anything was selected (or replaced), for the exit code
+    pub matched: bool,
+    pub files_searched: usize,
+    pub files_matched: usize,
+    pub skipped: Vec<(String, SkipReason)>,
+    // files and directories that couldn't be read along the way
+    pub errors: usize,
+    // Ctrl-C stopped the search before the end
+    pub interrupted: bool,
+    // lines in the files that were searched
+    pub lines_searched: usize,
+    pub matched_lines: usize,
+    // the matches within those lines - a line can have several
+    pub matches: usize,
+    // how many of the matched lines each pattern matches, with --pattern-counts
+    pub pattern_lines: Vec<usize>,
+    pub elapsed: Duration,
+}
+
+impl Stats {
+    pub fn skip(&mut self, path: &str, reason: SkipReason) {
+        self.skipped.push((path.to_string(), reason));
+    }
+
+    // add in what searching another file came to (apart from the time taken)
+    pub fn add(&mut self, other: Stats) {
+        self.matched |= other.matched;
+        self.files_searched += other.files_searched;
+        self.files_matched += other.files_matched;
+        self.skipped.extend(other.skipped);
+        self.errors += other.errors;
+        self.interrupted |= other.interrupted;
+        self.lines_searched += other.lines_searched;
+        self.matched_lines += other.matched_lines;
+        self.matches += other.matches;
+        if self.pattern_lines.len() < other.pattern_lines.len() {
+            self.pattern_lines.resize(other.pattern_lines.len(), 0);
+        }
+        for (lines, other) in self.pattern_lines.iter_mut().zip(other.pattern_lines) {
+            *lines += other;
+        }
+    }
+
+    // the --pattern-counts summary: the matched lines of each pattern, in the
+    // order they were given, then the ones that never matched
+    pub fn pattern_counts(&self, patterns: &[String]) -> String {
+        let mut summary = String::new();
+        let mut unmatched = Vec::new();
+        for (index, pattern) in patterns.iter().enumerate() {
+            let lines = self.pattern_lines.get(index).copied().unwrap_or(0);
+            if lines == 0 {
+                unmatched.push(pattern.as_str());
+            }
+            summary += &format!("{}: {pattern}\n", plural(lines, "line", "lines"));
+        }
+        if unmatched.is_empty() {
+            summary += "every pattern matched";
+        } else {
+            summary += &format!(
+                "{} never matched: {}",
+                plural(unmatched.len(), "pattern", "patterns"),
+                unmatched.join(", ")
+            );
+        }
+        summary
+    }
+
+    // grep's exit code: 0 if anything matched, 1 if nothing did and 2 if
+    // something couldn't be read - though with -q a match is all that counts,
+    // and after Ctrl-C it's 130 whatever was found
+    pub fn exit_code(&self, quiet: bool) -> i32 {
+        if self.interrupted {
+            return crate::signal::EXIT_CODE;
+        }
+        match (self.matched, self.errors > 0) {
+            (true, false) => 0,
+            (true, true) if quiet => 0,
+            (_, true) => 2,
+            (false, false) => 1,
+        }
+    }
+}
+
+// the --stats summary, one number per line like ripgrep's
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", plural(self.matches, "match", "matches"))?;
+        writeln!(
+            f,
+            "{}",
+            plural(self.matched_lines, "matched line", "matched lines")
+        )?;
+        writeln!(
+            f,
+            "{} contained matches",
+            plural(self.files_matched, "file", "files")
+        )?;
+        writeln!(
+            f,
+            "{} searched",
+            plural(self.files_searched, "file", "files")
+        )?;
+        if !self.skipped.is_empty() {
+            // the reasons in the order they first came up
+            let mut reasons: Vec<(SkipReason, usize)> = Vec::new();
+            for (_, reason) in &self.skipped {
+                match reasons.iter_mut().find(|(seen, _)| seen == reason) {
+                    Some((_, count)) => *count += 1,
+                    None => reasons.push((*reason, 1)),
+                }
+            }
+            let reasons: Vec<String> = reasons
+                .iter()
+                .map(|(reason, count)| format!("{count} {}", reason.describe()))
+                .collect();
+            writeln!(
+                f,
+                "{} skipped ({})",
+                plural(self.skipped.len(), "file", "files"),
+                reasons.join(", ")
+            )?;
+        }
+        writeln!(
+            f,
+            "{} searched",
+            plural(self.lines_searched, "line", "lines")
+        )?;
+        write!(f, "{:.3} seconds", self.elapsed.as_secs_f64())
+    }
+}
+
+pub(crate) fn plural(count: usize, one: &str, many: &str) -> String {
+    if count == 1 {
+        format!("{count} {one}")
+    } else {
+        format!("{count} {many}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summary_counts_skipped_files_by_reason() {
+        let mut stats = Stats {
+            matches: 3,
+            matched_lines: 2,
+            files_matched: 1,
+            files_searched: 4,
+            lines_searched: 120,
+            elapsed: Duration::from_millis(15),
+            ..Stats::default()
+        };
+        stats.skip("logo.png", SkipReason::Binary);
+        stats.skip("secret.txt", SkipReason::Unreadable);
+        stats.skip("app.bin", SkipReason::Binary);
+
+        assert_eq!(
+            "\
+3 matches
+2 matched lines
+1 file contained matches
+4 files searched
+3 files skipped (2 binary, 1 unreadable)
+120 lines searched
+0.015 seconds",
+            stats.to_string()
+        );
+    }
+
+    #[test]
+    fn pattern_counts_say_which_never_matched() {
+        let mut stats = Stats {
+            pattern_lines: vec![2, 0],
+            ..Stats::default()
+        };
+        stats.add(Stats {
+            pattern_lines: vec![1, 0, 0],
+            ..Stats::default()
+        });
+        let patterns = ["old_api", "try!", "mem::uninitialized"].map(String::from);
+
+        assert_eq!(
+            "\
+3 lines: old_api
+0 lines: try!
+0 lines: mem::uninitialized
+2 patterns never matched: try!, mem::uninitialized",
+            stats.pattern_counts(&patterns)
+        );
+        assert_eq!(
+            "3 lines: old_api\nevery pattern matched",
+            stats.pattern_counts(&patterns[..1])
+        );
+    }
+
+    #[test]
+    fn exit_codes_are_grep_s() {
+        let stats = |matched, errors| Stats {
+            matched,
+            errors,
+            ..Stats::default()
+        };
+        assert_eq!(0, stats(true, 0).exit_code(false));
+        assert_eq!(1, stats(false, 0).exit_code(false));
+        assert_eq!(2, stats(true, 1).exit_code(false));
+        assert_eq!(2, stats(false, 1).exit_code(true));
+        assert_eq!(0, stats(true, 1).exit_code(true));
+
+        let interrupted = Stats {
+            matched: true,
+            interrupted: true,
+            ..Stats::default()
+        };
+        assert_eq!(130, interrupted.exit_code(false));
+    }
+}
@@ -0,0 +1,236 @@
+use std::collections::VecDeque;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use futures_core::Stream;
+use tokio::io::{AsyncRead, ReadBuf};
+
+use crate::searcher::{Feed, Searcher};
+use crate::{Line, Match};
+
+// The search for async code (with the tokio feature): the input is read with
+// tokio's AsyncRead, so no executor thread blocks waiting on it, and what's
+// found comes out of a Stream as each chunk is searched. With futures'
+// StreamExt:
+//
+//     let mut lines = searcher.search_file_stream("notes.txt").await?;
+//     while let Some(found) = lines.next().await {
+//         let (new_group, line) = found?;
+//         ...
+//     }
+//
+// Each line is owned, as the chunk it was found in is gone by the time it's
+// looked at.
+
+// how much is read at a time
+const CHUNK: usize = 8 * 1024;
+
+// a Match with its line copied out of what was read
+#[derive(Debug, Clone, PartialEq)]
+pub struct OwnedMatch {
+    pub line_number: usize,
+    pub byte_offset: usize,
+    pub line: String,
+    pub spans: Vec<Range<usize>>,
+    pub score: Option<usize>,
+}
+
+// a Line the same way: a selected line, or a (line number, byte offset,
+// text) context line
+#[derive(Debug, Clone, PartialEq)]
+pub enum OwnedLine {
+    Matched(OwnedMatch),
+    Context(usize, usize, String),
+}
+
+impl From<&Line<'_>> for OwnedLine {
+    fn from(line: &Line) -> OwnedLine {
+        match line {
+            Line::Matched(Match {
+                line_number,
+                byte_offset,
+                line,
+                spans,
+                score,
+            }) => OwnedLine::Matched(OwnedMatch {
+                line_number: *line_number,
+                byte_offset: *byte_offset,
+                line: line.to_string(),
+                spans: spans.clone(),
+                score: *score,
+            }),
+            Line::Context(line_number, byte_offset, text) => {
+                OwnedLine::Context(*line_number, *byte_offset, text.to_string())
+            }
+        }
+    }
+}
+
+impl Searcher {
+    // the lines to print from `reader`, each with whether it starts a new
+    // group - what search_reader hands its callback
+    pub fn search_stream<R: AsyncRead + Unpin>(&self, reader: R) -> MatchStream<'_, R> {
+        MatchStream {
+            reader,
+            feed: Some(self.feed()),
+            buffer: vec![0; CHUNK],
+            found: VecDeque::new(),
+        }
+    }
+
+    pub async fn search_file_stream(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> io::Result<MatchStream<'_, tokio::fs::File>> {
+        Ok(self.search_stream(tokio::fs::File::open(path).await?))
+    }
+}
+
+// Reading stops once the search has all it needs (at the max count), or at
+// the first error, which is the stream's last item.
+pub struct MatchStream<'s, R> {
+    reader: R,
+    // None once the input's finished
+    feed: Option<Feed<'s>>,
+    buffer: Vec<u8>,
+    // found in the last chunk, and not yet taken
+    found: VecDeque<(bool, OwnedLine)>,
+}
+
+impl<R: AsyncRead + Unpin> Stream for MatchStream<'_, R> {
+    type Item = io::Result<(bool, OwnedLine)>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let stream = self.get_mut();
+        loop {
+            if let Some(found) = stream.found.pop_front() {
+                return Poll::Ready(Some(Ok(found)));
+            }
+            let Some(feed) = &mut stream.feed else {
+                return Poll::Ready(None);
+            };
+            let mut buffer = ReadBuf::new(&mut stream.buffer);
+            let read = ready!(Pin::new(&mut stream.reader).poll_read(cx, &mut buffer));
+            let found = &mut stream.found;
+            let each = |new_group: bool, line: &Line| {
+                found.push_back((new_group, OwnedLine::from(line)));
+                Ok(())
+            };
+            let searched = match read {
+                // nothing read is the end of the input
+                Ok(()) if buffer.filled().is_empty() => stream
+                    .feed
+                    .take()
+                    .map_or(Ok(()), |feed| feed.finish(each).map(drop)),
+                Ok(()) => {
+                    let pushed = feed.push(buffer.filled(), each);
+                    if feed.is_done() {
+                        stream.feed = None;
+                    }
+                    pushed
+                }
+                Err(err) => Err(err),
+            };
+            if let Err(err) = searched {
+                stream.feed = None;
+                return Poll::Ready(Some(Err(err)));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SearcherBuilder;
+    use std::future::poll_fn;
+
+    const POEM: &str = "\
+I'm nobody! Who are you?
+Are you nobody, too?
+Then there's a pair of us - don't tell!
+They'd banish us, you know.
+";
+
+    // a reader that only gives out a few bytes at a time, and is pending
+    // before each of them
+    struct Trickle<'a> {
+        left: &'a [u8],
+        ready: bool,
+    }
+
+    impl AsyncRead for Trickle<'_> {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context,
+            buffer: &mut ReadBuf,
+        ) -> Poll<io::Result<()>> {
+            if !self.ready {
+                self.ready = true;
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            let size = self.left.len().min(5);
+            buffer.put_slice(&self.left[..size]);
+            self.left = &self.left[size..];
+            self.ready = false;
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    async fn collect(
+        mut stream: impl Stream<Item = io::Result<(bool, OwnedLine)>> + Unpin,
+    ) -> Vec<(bool, OwnedLine)> {
+        let mut lines = Vec::new();
+        while let Some(found) = poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await {
+            lines.push(found.unwrap());
+        }
+        lines
+    }
+
+    #[tokio::test]
+    async fn streams_what_search_reader_finds() {
+        let mut builder = SearcherBuilder::new();
+        builder.context(0, 1);
+        for max_count in [None, Some(1)] {
+            let searcher = builder
+                .max_count(max_count)
+                .build(&["nobody|banish".to_string()])
+                .unwrap();
+            let mut expected = Vec::new();
+            searcher
+                .search_reader(POEM.as_bytes(), |new_group, line| {
+                    expected.push((new_group, OwnedLine::from(line)));
+                    Ok(())
+                })
+                .unwrap();
+
+            let reader = Trickle {
+                left: POEM.as_bytes(),
+                ready: false,
+            };
+            assert_eq!(expected, collect(searcher.search_stream(reader)).await);
+        }
+    }
+
+    #[tokio::test]
+    async fn files_are_read_with_tokio() {
+        let searcher = SearcherBuilder::new()
+            .build(&["nobody".to_string()])
+            .unwrap();
+        let lines = collect(searcher.search_file_stream("poem.txt").await.unwrap()).await;
+        let numbers: Vec<_> = lines
+            .iter()
+            .map(|(_, line)| match line {
+                OwnedLine::Matched(m) => m.line_number,
+                OwnedLine::Context(number, ..) => *number,
+            })
+            .collect();
+        assert_eq!(vec![1, 2], numbers);
+
+        assert!(searcher.search_file_stream("no/such/file").await.is_err());
+    }
+}
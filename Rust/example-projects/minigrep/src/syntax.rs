@@ -0,0 +1,345 @@
+use std::ops::Range;
+use std::path::Path;
+
+// Where the comments and string literals are in a source file, for --only-in,
+// so a search can be kept to them (or to the code around them). It's a
+// lexer rather than a parser: each language is known by its file extension
+// and described by how its comments and strings are written, which is all it
+// takes to tell the three apart - there's no telling a function name from any
+// other identifier. With the tree-sitter feature the languages it has a
+// grammar for are parsed instead (see grammar.rs), which can.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Comment,
+    String,
+    // everything that's neither
+    Code,
+    // the name a function is declared with, which only a grammar can find
+    Function,
+}
+
+impl Kind {
+    pub fn from_name(name: &str) -> Option<Kind> {
+        match name {
+            "comment" | "comments" => Some(Kind::Comment),
+            "string" | "strings" => Some(Kind::String),
+            "code" => Some(Kind::Code),
+            "function" | "functions" => Some(Kind::Function),
+            _ => None,
+        }
+    }
+}
+
+// how a language writes its comments and strings
+struct Language {
+    line_comments: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+    // block comments can be nested, like /* /* */ */ in Rust
+    nested: bool,
+    // the characters that quote a string, which a backslash escapes within
+    quotes: &'static [char],
+    // quotes that run to the same three again, like Python's """
+    triple_quotes: bool,
+    // Rust's r"..." and r#"..."#, and 'x' as a character rather than a quote
+    rust: bool,
+}
+
+const C_LIKE: Language = Language {
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    nested: false,
+    quotes: &['"', '\''],
+    triple_quotes: false,
+    rust: false,
+};
+
+const RUST: Language = Language {
+    nested: true,
+    quotes: &['"'],
+    rust: true,
+    ..C_LIKE
+};
+
+// JavaScript's template literals and Go's raw strings are quoted with `
+const BACKTICKS: Language = Language {
+    quotes: &['"', '\'', '`'],
+    ..C_LIKE
+};
+
+const HASH: Language = Language {
+    line_comments: &["#"],
+    block_comment: None,
+    nested: false,
+    quotes: &['"', '\''],
+    triple_quotes: false,
+    rust: false,
+};
+
+const PYTHON: Language = Language {
+    triple_quotes: true,
+    ..HASH
+};
+
+const SQL: Language = Language {
+    line_comments: &["--"],
+    quotes: &['\''],
+    ..C_LIKE
+};
+
+const MARKUP: Language = Language {
+    line_comments: &[],
+    block_comment: Some(("<!--", "-->")),
+    nested: false,
+    quotes: &[],
+    triple_quotes: false,
+    rust: false,
+};
+
+fn language(path: &Path) -> Option<&'static Language> {
+    let name = path.file_name()?.to_str()?;
+    if matches!(name, "Makefile" | "makefile" | "GNUmakefile" | "Dockerfile") {
+        return Some(&HASH);
+    }
+    let language = match name.rsplit_once('.')?.1 {
+        "rs" => &RUST,
+        "c" | "h" | "cc" | "cpp" | "cxx" | "hh" | "hpp" | "hxx" | "java" | "cs" | "kt"
+        | "swift" | "scala" | "css" | "scss" | "less" => &C_LIKE,
+        "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" | "go" => &BACKTICKS,
+        "py" | "pyi" => &PYTHON,
+        "rb" | "sh" | "bash" | "zsh" | "pl" | "toml" | "yaml" | "yml" | "mk" | "r" => &HASH,
+        "sql" => &SQL,
+        "html" | "htm" | "xml" | "svg" | "md" | "markdown" => &MARKUP,
+        _ => return None,
+    };
+    Some(language)
+}
+
+// whether the file's language is one `kinds` can be found in
+pub fn knows(path: &Path, kinds: &[Kind]) -> bool {
+    #[cfg(feature = "tree-sitter")]
+    if crate::grammar::for_path(path).is_some() {
+        return true;
+    }
+    !kinds.contains(&Kind::Function) && language(path).is_some()
+}
+
+// the byte ranges of `contents` that are of one of `kinds`, in order - or
+// None when the file's language isn't one they can be found in
+pub fn regions(path: &Path, contents: &str, kinds: &[Kind]) -> Option<Vec<Range<usize>>> {
+    #[cfg(feature = "tree-sitter")]
+    if let Some(grammar) = crate::grammar::for_path(path) {
+        return Some(crate::grammar::regions(grammar, contents, kinds));
+    }
+    if kinds.contains(&Kind::Function) {
+        return None;
+    }
+    Some(lex(language(path)?, contents, kinds))
+}
+
+fn lex(language: &Language, contents: &str, kinds: &[Kind]) -> Vec<Range<usize>> {
+    let mut regions: Vec<Range<usize>> = Vec::new();
+    let mut add = |kind: Kind, range: Range<usize>| {
+        if !kinds.contains(&kind) || range.is_empty() {
+            return;
+        }
+        match regions.last_mut() {
+            Some(last) if last.end == range.start => last.end = range.end,
+            _ => regions.push(range),
+        }
+    };
+
+    let mut code_start = 0;
+    let mut at = 0;
+    while at < contents.len() {
+        let Some((kind, end)) = token_at(language, contents, at) else {
+            at += contents[at..].chars().next().map_or(1, char::len_utf8);
+            continue;
+        };
+        if kind == Kind::Code {
+            at = end;
+            continue;
+        }
+        add(Kind::Code, code_start..at);
+        add(kind, at..end);
+        code_start = end;
+        at = end;
+    }
+    add(Kind::Code, code_start..contents.len());
+    regions
+}
+
+// the comment or string starting at `at`, and where it ends - Code for
+// something to step over whole, like a Rust lifetime or character
+fn token_at(language: &Language, contents: &str, at: usize) -> Option<(Kind, usize)> {
+    let rest = &contents[at..];
+    if let Some((open, close)) = language.block_comment {
+        if rest.starts_with(open) {
+            return Some((
+                Kind::Comment,
+                at + block_end(rest, open, close, language.nested),
+            ));
+        }
+    }
+    if language
+        .line_comments
+        .iter()
+        .any(|prefix| rest.starts_with(prefix))
+    {
+        let end = rest.find('\n').unwrap_or(rest.len());
+        return Some((Kind::Comment, at + end));
+    }
+    if language.rust {
+        if let Some(end) = rust_raw_string(rest) {
+            return Some((Kind::String, at + end));
+        }
+        if rest.starts_with('\'') {
+            return Some((Kind::Code, at + rust_quote(rest)));
+        }
+    }
+    let quote = rest
+        .chars()
+        .next()
+        .filter(|c| language.quotes.contains(c))?;
+    if language.triple_quotes {
+        let triple: String = [quote; 3].iter().collect();
+        if rest.starts_with(&triple) {
+            let end = rest[3..].find(&triple).map_or(rest.len(), |end| end + 6);
+            return Some((Kind::String, at + end));
+        }
+    }
+    Some((Kind::String, at + string_end(rest, quote)))
+}
+
+// the length of the block comment `rest` starts with, to the end of the
+// contents if it isn't closed
+fn block_end(rest: &str, open: &str, close: &str, nested: bool) -> usize {
+    let mut depth = 0;
+    let mut at = 0;
+    while at < rest.len() {
+        if rest[at..].starts_with(open) && (nested || depth == 0) {
+            depth += 1;
+            at += open.len();
+        } else if rest[at..].starts_with(close) {
+            depth -= 1;
+            at += close.len();
+            if depth == 0 {
+                return at;
+            }
+        } else {
+            at += rest[at..].chars().next().map_or(1, char::len_utf8);
+        }
+    }
+    rest.len()
+}
+
+// the length of the string `rest` starts with, up to the same quote again
+// (one after a backslash doesn't count)
+fn string_end(rest: &str, quote: char) -> usize {
+    let mut escaped = false;
+    for (index, c) in rest.char_indices().skip(1) {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == quote {
+            return index + c.len_utf8();
+        }
+    }
+    rest.len()
+}
+
+// the length of a Rust raw string like r#"..."# (or br"...") at the start
+fn rust_raw_string(rest: &str) -> Option<usize> {
+    let after_r = rest.strip_prefix("br").or_else(|| rest.strip_prefix('r'))?;
+    let hashes = after_r.len() - after_r.trim_start_matches('#').len();
+    let body = after_r[hashes..].strip_prefix('"')?;
+    let close = format!("\"{}", "#".repeat(hashes));
+    let start = rest.len() - body.len();
+    Some(
+        body.find(&close)
+            .map_or(rest.len(), |end| start + end + close.len()),
+    )
+}
+
+// a ' in Rust: a character like 'x' or '\n', or else a lifetime's quote
+fn rust_quote(rest: &str) -> usize {
+    let mut chars = rest.char_indices().skip(1);
+    match chars.next() {
+        Some((_, '\\')) => string_end(rest, '\''),
+        Some((_, c)) => match chars.next() {
+            Some((index, '\'')) => index + 1,
+            _ => 1 + c.len_utf8(),
+        },
+        None => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the lexer's, whether or not there's a grammar for the language
+    fn texts<'a>(path: &str, contents: &'a str, kind: Kind) -> Vec<&'a str> {
+        lex(language(Path::new(path)).unwrap(), contents, &[kind])
+            .into_iter()
+            .map(|range| &contents[range])
+            .collect()
+    }
+
+    #[test]
+    fn comments_and_strings_are_told_from_code() {
+        let rust = "\
+// TODO: tidy
+fn main<'a>(x: &'a str) {
+    let c = '\"'; /* a /* nested */ quote */
+    println!(\"TODO {}\", r#\"say \"hi\"\"#);
+}
+";
+        assert_eq!(
+            vec!["// TODO: tidy", "/* a /* nested */ quote */"],
+            texts("main.rs", rust, Kind::Comment)
+        );
+        assert_eq!(
+            vec!["\"TODO {}\"", "r#\"say \"hi\"\"#"],
+            texts("main.rs", rust, Kind::String)
+        );
+        let code = texts("main.rs", rust, Kind::Code).concat();
+        assert!(code.contains("fn main<'a>(x: &'a str) {"));
+        assert!(code.contains("let c = '\"'; "));
+        assert!(!code.contains("TODO"));
+    }
+
+    #[test]
+    fn each_language_has_its_own_syntax() {
+        let python = "x = \"\"\"a # not a comment\n\"\"\"  # but this is\n";
+        assert_eq!(vec!["# but this is"], texts("a.py", python, Kind::Comment));
+        assert_eq!(
+            vec!["\"\"\"a # not a comment\n\"\"\""],
+            texts("a.py", python, Kind::String)
+        );
+
+        let js = "let s = `it's // fine`; // done";
+        assert_eq!(vec!["// done"], texts("app.js", js, Kind::Comment));
+
+        let sql = "select 'a--b' -- why\nfrom t";
+        assert_eq!(vec!["-- why"], texts("q.sql", sql, Kind::Comment));
+
+        assert!(regions(Path::new("notes.txt"), "# hi", &[Kind::Comment]).is_none());
+        assert!(!knows(Path::new("notes.txt"), &[Kind::Comment]));
+        assert!(!knows(Path::new("q.sql"), &[Kind::Comment, Kind::Function]));
+    }
+
+    #[test]
+    fn unclosed_tokens_run_to_the_end() {
+        let c = "int x; /* never closed\n\"nor this";
+        assert_eq!(
+            vec!["/* never closed\n\"nor this"],
+            texts("x.c", c, Kind::Comment)
+        );
+        assert_eq!(
+            vec!["\"nor this"],
+            texts("x.c", "a = \"nor this", Kind::String)
+        );
+    }
+}
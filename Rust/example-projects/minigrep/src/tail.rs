@@ -0,0 +1,153 @@
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+// how long --follow-appends sleeps between looking for new data
+pub const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+// Tail follows a file as it grows, like tail -f. It keeps the file open and
+// reads whatever was appended since last time, starting over from the top if
+// the file is truncated or replaced by a new one (log rotation).
+pub struct Tail {
+    path: PathBuf,
+    file: File,
+    // where the next read starts
+    offset: u64,
+    // number of the last complete line read
+    line_number: usize,
+    // the start of a line whose newline hasn't been written yet
+    partial: Vec<u8>,
+}
+
+// a complete line appended to the file
+#[derive(Debug, PartialEq)]
+pub struct Appended {
+    pub line_number: usize,
+    pub byte_offset: usize,
+    pub text: String,
+}
+
+impl Tail {
+    // follow the file from `offset`, where line `line_number` + 1 starts
+    pub fn new(path: &Path, offset: u64, line_number: usize) -> io::Result<Tail> {
+        Ok(Tail {
+            path: path.to_path_buf(),
+            file: File::open(path)?,
+            offset,
+            line_number,
+            partial: Vec::new(),
+        })
+    }
+
+    // follow the file from the start of its last (possibly incomplete) line,
+    // given the contents that have already been searched
+    pub fn after(path: &Path, searched: &[u8]) -> io::Result<Tail> {
+        let offset = searched
+            .iter()
+            .rposition(|&byte| byte == b'\n')
+            .map_or(0, |index| index + 1);
+        let line_number = searched[..offset]
+            .iter()
+            .filter(|&&byte| byte == b'\n')
+            .count();
+        Tail::new(path, offset as u64, line_number)
+    }
+
+    // the complete lines appended since the last call
+    pub fn poll(&mut self) -> io::Result<Vec<Appended>> {
+        if self.rotated()? {
+            self.file = File::open(&self.path)?;
+            self.offset = 0;
+            self.line_number = 0;
+            self.partial.clear();
+        }
+
+        self.file.seek(SeekFrom::Start(self.offset))?;
+        let mut data = std::mem::take(&mut self.partial);
+        let start = self.offset as usize - data.len();
+        self.offset += self.file.read_to_end(&mut data)? as u64;
+
+        // hold on to a trailing incomplete line until the rest of it arrives
+        let complete = data
+            .iter()
+            .rposition(|&byte| byte == b'\n')
+            .map_or(0, |index| index + 1);
+        self.partial = data.split_off(complete);
+
+        let mut appended = Vec::new();
+        let mut byte_offset = start;
+        for line in data.split_inclusive(|&byte| byte == b'\n') {
+            let text = line.strip_suffix(b"\n").unwrap_or(line);
+            let text = text.strip_suffix(b"\r").unwrap_or(text);
+            self.line_number += 1;
+            appended.push(Appended {
+                line_number: self.line_number,
+                byte_offset,
+                text: String::from_utf8_lossy(text).into_owned(),
+            });
+            byte_offset += line.len();
+        }
+        Ok(appended)
+    }
+
+    // whether the file was truncated, or the path now names a different file
+    fn rotated(&self) -> io::Result<bool> {
+        let current = match fs::metadata(&self.path) {
+            Ok(metadata) => metadata,
+            // moved away and not recreated yet - keep reading the old one
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(err) => return Err(err),
+        };
+        if current.len() < self.offset {
+            return Ok(true);
+        }
+        Ok(!same_file(&current, &self.file.metadata()?))
+    }
+}
+
+#[cfg(unix)]
+fn same_file(a: &fs::Metadata, b: &fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    (a.dev(), a.ino()) == (b.dev(), b.ino())
+}
+
+// without inodes only truncation can be noticed
+#[cfg(not(unix))]
+fn same_file(_a: &fs::Metadata, _b: &fs::Metadata) -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TempDir;
+    use std::fs::OpenOptions;
+    use std::io::Write;
+
+    fn texts(appended: Vec<Appended>) -> Vec<(usize, String)> {
+        appended
+            .into_iter()
+            .map(|line| (line.line_number, line.text))
+            .collect()
+    }
+
+    #[test]
+    fn reads_appended_lines_and_starts_over_after_truncation() {
+        let dir = TempDir::new("tail");
+        let path = dir.join("app.log");
+        fs::write(&path, "one\ntwo\nthr").unwrap();
+
+        let mut tail = Tail::after(&path, &fs::read(&path).unwrap()).unwrap();
+        let mut log = OpenOptions::new().append(true).open(&path).unwrap();
+        write!(log, "ee\nfour\nfi").unwrap();
+        assert_eq!(
+            vec![(3, "three".to_string()), (4, "four".to_string())],
+            texts(tail.poll().unwrap())
+        );
+        assert!(tail.poll().unwrap().is_empty());
+
+        fs::write(&path, "new\n").unwrap();
+        assert_eq!(vec![(1, "new".to_string())], texts(tail.poll().unwrap()));
+    }
+}
@@ -0,0 +1,533 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::ops::Range;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::{encoding, search_context, Line, Matcher, MatcherOptions, SearchOptions};
+
+// The --tui browser: a query line that can be edited, the list of matching
+// lines below it and a preview of the selected match in its file. The terminal
+// is driven with stty and ANSI escapes, so there's no terminal library to link.
+// Files are searched a slice at a time between key presses, so the list fills
+// in as the search goes and typing never waits for a whole tree to be searched.
+
+// how long one slice of searching runs before looking for key presses
+const SEARCH_SLICE: Duration = Duration::from_millis(30);
+// how long to sleep when there's nothing to search and no key was pressed
+const IDLE_POLL: Duration = Duration::from_millis(20);
+// the list stops here, so a one-letter query on a big tree stays responsive
+const MAX_HITS: usize = 10_000;
+
+const MATCH_COLOR: &str = "\x1b[1;31m";
+// ends a highlight without undoing the reverse video of the selected line
+const MATCH_END: &str = "\x1b[22;39m";
+const REVERSE: &str = "\x1b[7m";
+const RESET: &str = "\x1b[0m";
+
+// a key press, after decoding escape sequences
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Key {
+    Char(char),
+    Backspace,
+    // Ctrl-U - clear the query
+    ClearQuery,
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Enter,
+    // Esc, Ctrl-C or Ctrl-Q
+    Quit,
+}
+
+// a matching line in the list
+#[derive(Debug, PartialEq)]
+struct Hit {
+    file: usize,
+    line_number: usize,
+    line: String,
+    spans: Vec<Range<usize>>,
+}
+
+// what the browser shows, apart from the terminal itself
+struct Browser {
+    files: Vec<String>,
+    // each file's text, read the first time it's searched
+    contents: Vec<Option<String>>,
+    matcher_options: MatcherOptions,
+    options: SearchOptions,
+    query: String,
+    // the query's matcher, or why it isn't a valid pattern
+    matcher: Result<Matcher, String>,
+    hits: Vec<Hit>,
+    // how many of the files have been searched for the current query
+    searched: usize,
+    selected: usize,
+    // the first hit shown in the list
+    scroll: usize,
+}
+
+impl Browser {
+    fn new(files: Vec<String>, matcher_options: MatcherOptions, options: SearchOptions) -> Browser {
+        let contents = vec![None; files.len()];
+        Browser {
+            files,
+            contents,
+            matcher_options,
+            options,
+            query: String::new(),
+            matcher: Err(String::new()),
+            hits: Vec::new(),
+            searched: 0,
+            selected: 0,
+            scroll: 0,
+        }
+    }
+
+    // start the search over for a new query
+    fn set_query(&mut self, query: String) {
+        self.matcher = Matcher::with_patterns(std::slice::from_ref(&query), &self.matcher_options)
+            .map_err(|err| err.to_string());
+        self.query = query;
+        self.hits.clear();
+        self.searched = 0;
+        self.selected = 0;
+        self.scroll = 0;
+    }
+
+    fn searching(&self) -> bool {
+        !self.query.is_empty()
+            && self.matcher.is_ok()
+            && self.searched < self.files.len()
+            && self.hits.len() < MAX_HITS
+    }
+
+    // search more of the files, for about `budget`
+    fn search_for(&mut self, budget: Duration) {
+        let start = Instant::now();
+        while self.searching() && start.elapsed() < budget {
+            let Ok(matcher) = &self.matcher else {
+                return;
+            };
+            let file = self.searched;
+            self.searched += 1;
+            let contents = self.contents[file].get_or_insert_with(|| read_text(&self.files[file]));
+            for group in search_context(matcher, contents, &self.options) {
+                for line in group {
+                    if let Line::Matched(m) = line {
+                        self.hits.push(Hit {
+                            file,
+                            line_number: m.line_number,
+                            line: m.line.to_string(),
+                            spans: m.spans,
+                        });
+                    }
+                }
+            }
+        }
+        self.hits.truncate(MAX_HITS);
+    }
+
+    // handle a key press, returning false to quit
+    fn press(&mut self, key: Key, page: usize) -> bool {
+        match key {
+            Key::Char(c) => {
+                let mut query = self.query.clone();
+                query.push(c);
+                self.set_query(query);
+            }
+            Key::Backspace => {
+                let mut query = self.query.clone();
+                if query.pop().is_some() {
+                    self.set_query(query);
+                }
+            }
+            Key::ClearQuery => self.set_query(String::new()),
+            Key::Up => self.selected = self.selected.saturating_sub(1),
+            Key::Down => self.selected += 1,
+            Key::PageUp => self.selected = self.selected.saturating_sub(page),
+            Key::PageDown => self.selected += page,
+            // opening the editor needs the terminal, so the caller does it
+            Key::Enter => {}
+            Key::Quit => return false,
+        }
+        self.selected = self.selected.min(self.hits.len().saturating_sub(1));
+        true
+    }
+
+    // the status shown at the end of the query line
+    fn status(&self) -> String {
+        match &self.matcher {
+            _ if self.query.is_empty() => "type to search".to_string(),
+            Err(err) => err.lines().last().unwrap_or_default().to_string(),
+            Ok(_) if self.hits.len() >= MAX_HITS => format!("{MAX_HITS}+ matches"),
+            Ok(_) if self.searching() => format!(
+                "{} matches, searched {}/{} files",
+                self.hits.len(),
+                self.searched,
+                self.files.len()
+            ),
+            Ok(_) => format!("{} matches in {} files", self.hits.len(), self.files.len()),
+        }
+    }
+
+    // the whole screen, for a terminal of `rows` x `columns`
+    fn render(&mut self, rows: usize, columns: usize) -> String {
+        let list_rows = (rows.saturating_sub(2) / 2).max(1);
+        let preview_rows = rows.saturating_sub(list_rows + 3);
+
+        // keep the selected hit in view
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + list_rows {
+            self.scroll = self.selected + 1 - list_rows;
+        }
+
+        let mut screen = String::new();
+        let prompt = format!("> {}", self.query);
+        let status = self.status();
+        let gap = columns.saturating_sub(prompt.chars().count() + status.chars().count());
+        screen.push_str(&row(1, &format!("{prompt}{:gap$}{status}", "")));
+
+        for (index, hit) in self
+            .hits
+            .iter()
+            .enumerate()
+            .skip(self.scroll)
+            .take(list_rows)
+        {
+            let prefix = format!("{}:{}: ", self.files[hit.file], hit.line_number);
+            let width = columns.saturating_sub(prefix.chars().count());
+            let text = fit(&prefix, columns) + &highlighted(&hit.line, &hit.spans, width);
+            let text = if index == self.selected {
+                // fill the rest of the row, so the whole line stands out
+                let used =
+                    prefix.chars().count().min(columns) + hit.line.chars().count().min(width);
+                let gap = columns - used;
+                format!("{REVERSE}{text}{:gap$}{RESET}", "")
+            } else {
+                text
+            };
+            screen.push_str(&row(index - self.scroll + 2, &text));
+        }
+        for blank in self.hits.len().saturating_sub(self.scroll)..list_rows {
+            screen.push_str(&row(blank + 2, ""));
+        }
+
+        let hit = self.hits.get(self.selected);
+        let title = hit.map_or(String::new(), |hit| format!(" {} ", self.files[hit.file]));
+        let rule = format!("--{title}{}", "-".repeat(columns));
+        screen.push_str(&row(list_rows + 2, &fit(&rule, columns)));
+        let preview = match hit {
+            Some(hit) => self.preview(hit, preview_rows, columns),
+            None => Vec::new(),
+        };
+        for index in 0..preview_rows {
+            let line = preview.get(index).map_or("", String::as_str);
+            screen.push_str(&row(list_rows + 3 + index, line));
+        }
+
+        let help = "up/down: move  enter: open in $EDITOR  ctrl-u: clear  esc: quit";
+        screen.push_str(&row(rows, &fit(help, columns)));
+        // leave the cursor at the end of the query
+        let column = (prompt.chars().count() + 1).min(columns);
+        screen.push_str(&format!("\x1b[1;{column}H"));
+        screen
+    }
+
+    // the lines around a hit in its file, with their line numbers
+    fn preview(&self, hit: &Hit, rows: usize, columns: usize) -> Vec<String> {
+        let Some(contents) = &self.contents[hit.file] else {
+            return Vec::new();
+        };
+        let first = hit.line_number.saturating_sub(rows / 2).max(1);
+        contents
+            .lines()
+            .enumerate()
+            .skip(first - 1)
+            .take(rows)
+            .map(|(index, line)| {
+                let number = format!("{:>6} ", index + 1);
+                let width = columns.saturating_sub(number.len());
+                let spans = match &self.matcher {
+                    _ if index + 1 == hit.line_number => hit.spans.clone(),
+                    Ok(matcher) if !self.options.invert => matcher.find_spans(line),
+                    _ => Vec::new(),
+                };
+                fit(&number, columns) + &highlighted(line, &spans, width)
+            })
+            .collect()
+    }
+}
+
+// draw a row of the screen, clearing whatever was there before
+fn row(number: usize, text: &str) -> String {
+    format!("\x1b[{number};1H\x1b[2K{text}")
+}
+
+// the start of the text that fits in `width` columns, with tabs and other
+// control characters shown as spaces so they can't move the cursor
+fn fit(text: &str, width: usize) -> String {
+    text.chars()
+        .take(width)
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect()
+}
+
+// like fit, but with the spans colored
+fn highlighted(text: &str, spans: &[Range<usize>], width: usize) -> String {
+    let mut fitted = String::new();
+    let mut in_span = false;
+    for (index, c) in text.char_indices().take(width) {
+        let inside = spans.iter().any(|span| span.contains(&index));
+        if inside != in_span {
+            fitted.push_str(if inside { MATCH_COLOR } else { MATCH_END });
+            in_span = inside;
+        }
+        fitted.push(if c.is_control() { ' ' } else { c });
+    }
+    if in_span {
+        fitted.push_str(MATCH_END);
+    }
+    fitted
+}
+
+// turn the bytes read from the terminal into key presses
+// an Esc on its own is a key of its own, otherwise it starts a sequence
+fn parse_keys(bytes: &[u8]) -> Vec<Key> {
+    let text = String::from_utf8_lossy(bytes);
+    let mut chars = text.chars().peekable();
+    let mut keys = Vec::new();
+    while let Some(c) = chars.next() {
+        let key = match c {
+            '\x1b' => match chars.next() {
+                None => Key::Quit,
+                Some('[' | 'O') => match chars.next() {
+                    Some('A') => Key::Up,
+                    Some('B') => Key::Down,
+                    Some(digit @ ('5' | '6')) if chars.next_if_eq(&'~').is_some() => {
+                        if digit == '5' {
+                            Key::PageUp
+                        } else {
+                            Key::PageDown
+                        }
+                    }
+                    // some other sequence (like the right arrow) does nothing
+                    _ => continue,
+                },
+                _ => continue,
+            },
+            '\r' | '\n' => Key::Enter,
+            '\x7f' | '\x08' => Key::Backspace,
+            '\x03' | '\x11' => Key::Quit,
+            '\x15' => Key::ClearQuery,
+            '\x10' => Key::Up,
+            '\x0e' => Key::Down,
+            c if c.is_control() => continue,
+            c => Key::Char(c),
+        };
+        keys.push(key);
+    }
+    keys
+}
+
+// a file's text for the browser - binary and unreadable files have none
+fn read_text(path: &str) -> String {
+    let Ok(bytes) = fs::read(path) else {
+        return String::new();
+    };
+    match encoding::sniff(&bytes) {
+        Some(encoding) => encoding::decode(&bytes, encoding),
+        None if crate::is_binary(&bytes) => String::new(),
+        None => String::from_utf8_lossy(&bytes).into_owned(),
+    }
+}
+
+// the controlling terminal, in raw mode on the alternate screen until dropped
+struct Terminal {
+    tty: File,
+    // the stty settings to go back to
+    saved: String,
+}
+
+impl Terminal {
+    fn open() -> io::Result<Terminal> {
+        let tty = OpenOptions::new().read(true).write(true).open("/dev/tty")?;
+        let saved = stty(&tty, &["-g"])?.trim().to_string();
+        let mut terminal = Terminal { tty, saved };
+        terminal.enter()?;
+        Ok(terminal)
+    }
+
+    // raw mode, where reads return straight away even when no key was pressed
+    fn enter(&mut self) -> io::Result<()> {
+        stty(&self.tty, &["raw", "-echo", "min", "0", "time", "0"])?;
+        write!(self.tty, "\x1b[?1049h")?;
+        self.tty.flush()
+    }
+
+    fn leave(&mut self) -> io::Result<()> {
+        write!(self.tty, "\x1b[2J\x1b[?1049l")?;
+        self.tty.flush()?;
+        stty(&self.tty, &[self.saved.as_str()]).map(|_| ())
+    }
+
+    // rows and columns, from stty (80x24 if it can't tell)
+    fn size(&self) -> (usize, usize) {
+        let size = stty(&self.tty, &["size"]).unwrap_or_default();
+        let mut numbers = size.split_whitespace().map(str::parse::<usize>);
+        match (numbers.next(), numbers.next()) {
+            (Some(Ok(rows)), Some(Ok(columns))) if rows > 0 && columns > 0 => (rows, columns),
+            _ => (24, 80),
+        }
+    }
+
+    fn keys(&mut self) -> io::Result<Vec<Key>> {
+        let mut buffer = [0; 256];
+        let read = self.tty.read(&mut buffer)?;
+        Ok(parse_keys(&buffer[..read]))
+    }
+
+    // edit the file in $VISUAL or $EDITOR (vi if neither is set), starting at
+    // the line - "+<line>" is understood by vi, nano, emacs and friends
+    fn edit(&mut self, path: &str, line_number: usize) -> io::Result<()> {
+        let editor = std::env::var("VISUAL")
+            .or_else(|_| std::env::var("EDITOR"))
+            .unwrap_or_else(|_| "vi".to_string());
+        let mut words = editor.split_whitespace();
+        let program = words.next().unwrap_or("vi");
+
+        self.leave()?;
+        let status = Command::new(program)
+            .args(words)
+            .arg(format!("+{line_number}"))
+            .arg(Path::new(path))
+            .stdin(Stdio::from(self.tty.try_clone()?))
+            .stdout(Stdio::from(self.tty.try_clone()?))
+            .status();
+        self.enter()?;
+        status.map(|_| ())
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = self.leave();
+    }
+}
+
+pub(crate) fn stty(tty: &File, args: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::from(tty.try_clone()?))
+        .output()
+        .map_err(|err| io::Error::new(err.kind(), format!("unable to run stty: {err}")))?;
+    if !output.status.success() {
+        let errors = String::from_utf8_lossy(&output.stderr);
+        return Err(io::Error::other(format!("stty failed: {}", errors.trim())));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+// browse the matches in the files, starting from `query`
+// returns whether the query matched anything when the browser was closed
+pub fn run(
+    files: Vec<String>,
+    query: &str,
+    matcher_options: &MatcherOptions,
+    options: &SearchOptions,
+) -> io::Result<bool> {
+    let mut browser = Browser::new(files, matcher_options.clone(), options.clone());
+    browser.set_query(query.to_string());
+    let mut terminal = Terminal::open()?;
+
+    let mut redraw = true;
+    loop {
+        let searching = browser.searching();
+        if searching {
+            browser.search_for(SEARCH_SLICE);
+        }
+        let (rows, columns) = terminal.size();
+        if redraw || searching {
+            let screen = browser.render(rows, columns);
+            write!(terminal.tty, "{screen}")?;
+            terminal.tty.flush()?;
+        }
+
+        let keys = terminal.keys()?;
+        redraw = !keys.is_empty();
+        if keys.is_empty() && !searching {
+            thread::sleep(IDLE_POLL);
+        }
+        for key in keys {
+            if key == Key::Enter {
+                if let Some(hit) = browser.hits.get(browser.selected) {
+                    terminal.edit(&browser.files[hit.file], hit.line_number)?;
+                }
+            }
+            // the page is the part of the screen the list gets
+            if !browser.press(key, (rows.saturating_sub(2) / 2).max(1)) {
+                return Ok(!browser.hits.is_empty());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TempDir;
+
+    #[test]
+    fn keys_are_decoded_from_escape_sequences() {
+        assert_eq!(
+            vec![
+                Key::Char('f'),
+                Key::Char('é'),
+                Key::Up,
+                Key::Down,
+                Key::PageDown,
+                Key::Enter,
+                Key::Backspace,
+                Key::Quit,
+            ],
+            parse_keys("fé\x1b[A\x1bOB\x1b[C\x1b[6~\r\x7f\x1b".as_bytes())
+        );
+    }
+
+    #[test]
+    fn browser_searches_again_when_the_query_changes() {
+        let dir = TempDir::new("tui");
+        let path = dir.join("poem.txt");
+        fs::write(&path, "I'm nobody! Who are you?\nAre you nobody, too?\n").unwrap();
+        let files = vec![path.to_string_lossy().into_owned()];
+
+        let mut browser = Browser::new(files, MatcherOptions::default(), SearchOptions::default());
+        browser.set_query("nobody".to_string());
+        browser.search_for(Duration::from_secs(10));
+        assert!(!browser.searching());
+        assert_eq!(2, browser.hits.len());
+        assert_eq!(4..10, browser.hits[0].spans[0]);
+
+        browser.press(Key::Down, 1);
+        browser.press(Key::Down, 1);
+        assert_eq!(1, browser.selected);
+
+        for c in ", too".chars() {
+            browser.press(Key::Char(c), 1);
+        }
+        browser.search_for(Duration::from_secs(10));
+        assert_eq!(0, browser.selected);
+        assert_eq!(
+            vec![2],
+            browser
+                .hits
+                .iter()
+                .map(|hit| hit.line_number)
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::glob::Glob;
+
+// the built-in file types, as globs matched against file names
+const DEFAULT_TYPES: &[(&str, &[&str])] = &[
+    ("c", &["*.c", "*.h"]),
+    (
+        "cpp",
+        &["*.cpp", "*.cc", "*.cxx", "*.hpp", "*.hh", "*.hxx", "*.h"],
+    ),
+    ("css", &["*.css", "*.scss", "*.sass", "*.less"]),
+    ("go", &["*.go"]),
+    ("html", &["*.html", "*.htm"]),
+    ("java", &["*.java"]),
+    ("js", &["*.js", "*.jsx", "*.mjs", "*.cjs"]),
+    ("json", &["*.json"]),
+    ("make", &["Makefile", "makefile", "GNUmakefile", "*.mk"]),
+    ("markdown", &["*.md", "*.markdown"]),
+    ("md", &["*.md", "*.markdown"]),
+    ("py", &["*.py", "*.pyi"]),
+    ("ruby", &["*.rb", "Gemfile", "Rakefile"]),
+    ("rust", &["*.rs"]),
+    ("sh", &["*.sh", "*.bash", "*.zsh"]),
+    ("sql", &["*.sql"]),
+    ("toml", &["*.toml", "Cargo.lock"]),
+    ("ts", &["*.ts", "*.tsx"]),
+    ("txt", &["*.txt"]),
+    ("xml", &["*.xml"]),
+    ("yaml", &["*.yaml", "*.yml"]),
+];
+
+// --type and --type-not filters: a file is searched if its name matches one
+// of the selected types (when any are given) and none of the rejected ones
+// like --glob filters, they only apply to files found by walking a directory
+#[derive(Debug, Clone, Default)]
+pub struct Types {
+    select: Vec<Glob>,
+    reject: Vec<Glob>,
+}
+
+impl Types {
+    // `added` are --type-add definitions like "web:*.html", which extend the
+    // built-in types (or define new ones)
+    pub fn new(select: &[String], reject: &[String], added: &[String]) -> Result<Types, String> {
+        let mut definitions: HashMap<String, Vec<String>> = DEFAULT_TYPES
+            .iter()
+            .map(|(name, globs)| {
+                (
+                    name.to_string(),
+                    globs.iter().map(|glob| glob.to_string()).collect(),
+                )
+            })
+            .collect();
+        for definition in added {
+            match definition.split_once(':') {
+                Some((name, glob)) if !name.is_empty() && !glob.is_empty() => definitions
+                    .entry(name.to_string())
+                    .or_default()
+                    .push(glob.to_string()),
+                _ => return Err(format!("--type-add expects name:glob, got '{definition}'")),
+            }
+        }
+
+        let globs_of = |names: &[String]| -> Result<Vec<Glob>, String> {
+            let mut globs = Vec::new();
+            for name in names {
+                let definition = definitions
+                    .get(name)
+                    .ok_or_else(|| format!("unknown file type '{name}'"))?;
+                for glob in definition {
+                    globs.push(
+                        Glob::new(glob).map_err(|err| format!("invalid glob '{glob}': {err}"))?,
+                    );
+                }
+            }
+            Ok(globs)
+        };
+
+        Ok(Types {
+            select: globs_of(select)?,
+            reject: globs_of(reject)?,
+        })
+    }
+
+    pub fn selects(&self, path: &Path) -> bool {
+        let name = match path.file_name() {
+            Some(name) => name.to_string_lossy(),
+            None => return true,
+        };
+        let matches = |globs: &[Glob]| globs.iter().any(|glob| glob.is_match(&name));
+        !matches(&self.reject) && (self.select.is_empty() || matches(&self.select))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn types(select: &[&str], reject: &[&str], added: &[&str]) -> Result<Types, String> {
+        let strings = |names: &[&str]| {
+            names
+                .iter()
+                .map(|name| name.to_string())
+                .collect::<Vec<_>>()
+        };
+        Types::new(&strings(select), &strings(reject), &strings(added))
+    }
+
+    #[test]
+    fn selects_and_rejects_built_in_types() {
+        let rust = types(&["rust"], &[], &[]).unwrap();
+        assert!(rust.selects(Path::new("src/main.rs")));
+        assert!(!rust.selects(Path::new("README.md")));
+
+        let not_md = types(&[], &["md"], &[]).unwrap();
+        assert!(not_md.selects(Path::new("src/main.rs")));
+        assert!(!not_md.selects(Path::new("README.md")));
+    }
+
+    #[test]
+    fn type_add_defines_new_types() {
+        let web = types(&["web"], &[], &["web:*.html", "web:*.css"]).unwrap();
+        assert!(web.selects(Path::new("index.html")));
+        assert!(web.selects(Path::new("site.css")));
+        assert!(!web.selects(Path::new("app.js")));
+    }
+
+    #[test]
+    fn unknown_types_are_errors() {
+        assert!(types(&["cobol"], &[], &[]).is_err());
+        assert!(types(&[], &[], &["nocolon"]).is_err());
+    }
+}
@@ -0,0 +1,548 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::ignore::{Globs, Ignore};
+use crate::log::{debug, trace};
+use crate::stats::SkipReason;
+use crate::types::Types;
+
+// options controlling which files a directory walk yields
+#[derive(Debug, Default, Clone)]
+pub struct WalkOptions {
+    // how many directories deep to descend (0 = only the root itself)
+    pub max_depth: Option<usize>,
+    // also yield dotfiles and descend into dot-directories
+    pub hidden: bool,
+    // descend into symlinked directories and search symlinked files
+    pub follow: bool,
+    // don't skip the paths listed in .gitignore, .ignore and .minigrepignore files
+    // (and git's excludes)
+    pub no_ignore: bool,
+    // leave the user's global git excludes file out of those
+    pub no_ignore_global: bool,
+    // --glob filters, applied in order: "*.rs" searches only matching files,
+    // "!target/**" leaves matching paths out
+    pub globs: Vec<String>,
+    // --type and --type-not filters on file names
+    pub types: Types,
+    // skip files bigger than this many bytes
+    pub max_filesize: Option<u64>,
+    // stay on the root's filesystem, not descending into anything mounted below it
+    pub one_file_system: bool,
+}
+
+// Walk recursively yields every file under a root path, depth first,
+// with the entries of each directory in name order.
+// A root that is a file is yielded as is.
+pub struct Walk {
+    options: WalkOptions,
+    // paths still to visit, along with their depth below the root
+    stack: Vec<(PathBuf, usize)>,
+    // the directories on the way down to the current one, with --follow
+    ancestors: Vec<DirId>,
+    // the ignore rules of each directory on the way down, like ancestors
+    ignores: Vec<Option<Ignore>>,
+    // and whether each is in a git repository, where .gitignore applies
+    in_repo: Vec<bool>,
+    // git's excludes, when the root is inside a repository rather than one
+    excludes: Option<Ignore>,
+    globs: Globs,
+    // the filesystem the root is on, with --one-file-system
+    root_device: Option<u64>,
+    // what was left out since take_skipped was last called, and why
+    skipped: Vec<(PathBuf, SkipReason)>,
+}
+
+impl Walk {
+    pub fn new(root: &Path, options: &WalkOptions) -> Walk {
+        Walk {
+            options: options.clone(),
+            stack: vec![(root.to_path_buf(), 0)],
+            ancestors: Vec::new(),
+            ignores: Vec::new(),
+            in_repo: Vec::new(),
+            excludes: None,
+            globs: Globs::new(root, &options.globs),
+            root_device: None,
+            skipped: Vec::new(),
+        }
+    }
+
+    // the paths left out of the walk so far (and not taken already), for --stats
+    pub fn take_skipped(&mut self) -> Vec<(PathBuf, SkipReason)> {
+        std::mem::take(&mut self.skipped)
+    }
+
+    // push the entries of a directory so they come off the stack in name order
+    fn push_entries(&mut self, dir: &Path, depth: usize) -> io::Result<()> {
+        trace!("reading directory {}", dir.display());
+        let mut entries = fs::read_dir(dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<io::Result<Vec<_>>>()?;
+        let mut skipped = Vec::new();
+        if !self.options.hidden {
+            entries.retain(|path| {
+                let hidden = is_hidden(path);
+                if hidden {
+                    debug!("{}: skipped, hidden (see --hidden)", path.display());
+                    skipped.push((path.clone(), SkipReason::Hidden));
+                }
+                !hidden
+            });
+        }
+        if !self.options.no_ignore {
+            let global = !self.options.no_ignore_global;
+            // looked for once there's a directory to walk at all
+            if depth == 0 {
+                self.excludes = Ignore::above(dir, global);
+            }
+            // a repository lasts all the way down from the first .git
+            let in_repo = match self.in_repo.last() {
+                Some(&parent) => parent || dir.join(".git").exists(),
+                None => Ignore::in_repo(dir),
+            };
+            self.in_repo.push(in_repo);
+            self.ignores.push(Ignore::load(dir, global, in_repo));
+        }
+        // the globs override the ignore files
+        entries.retain(|path| {
+            let is_dir = path.is_dir();
+            let skip = match self.globs.selects(path, is_dir) {
+                Some(selected) => {
+                    (!selected).then_some((SkipReason::Filtered, "not selected by --glob"))
+                }
+                None if is_ignored(&self.ignores, self.excludes.as_ref(), path, is_dir) => Some((
+                    SkipReason::Ignored,
+                    "ignored by an ignore file (see --no-ignore)",
+                )),
+                None if !is_dir && !self.options.types.selects(path) => {
+                    Some((SkipReason::Filtered, "not a --type being searched"))
+                }
+                None => None,
+            };
+            if let Some((reason, why)) = skip {
+                debug!("{}: skipped, {why}", path.display());
+                skipped.push((path.clone(), reason));
+            }
+            skip.is_none()
+        });
+        self.skipped.extend(skipped);
+        entries.sort();
+        for path in entries.into_iter().rev() {
+            self.stack.push((path, depth + 1));
+        }
+        Ok(())
+    }
+}
+
+impl Iterator for Walk {
+    type Item = io::Result<PathBuf>;
+
+    fn next(&mut self) -> Option<io::Result<PathBuf>> {
+        while let Some((path, depth)) = self.stack.pop() {
+            // walking depth first, the ancestors of an entry are the
+            // directories before it at depths 0..depth
+            self.ancestors.truncate(depth);
+            self.ignores.truncate(depth);
+            self.in_repo.truncate(depth);
+
+            // a symlink given as the root is always followed
+            let metadata = if self.options.follow || depth == 0 {
+                fs::metadata(&path)
+            } else {
+                fs::symlink_metadata(&path)
+            };
+            let metadata = match metadata {
+                Ok(metadata) => metadata,
+                Err(err) => return Some(Err(annotate(&path, err))),
+            };
+
+            if metadata.file_type().is_symlink() {
+                debug!("{}: skipped, a symlink (see --follow)", path.display());
+                self.skipped.push((path, SkipReason::Symlink));
+                continue;
+            }
+            if !metadata.is_dir() {
+                // checked here, before anything reads the file
+                if self
+                    .options
+                    .max_filesize
+                    .is_some_and(|max| metadata.len() > max)
+                {
+                    debug!(
+                        "{}: skipped, {} bytes is over --max-filesize",
+                        path.display(),
+                        metadata.len()
+                    );
+                    self.skipped.push((path, SkipReason::TooBig));
+                    continue;
+                }
+                return Some(Ok(path));
+            }
+
+            // the depth limit is checked before reading the directory at all
+            if self.options.max_depth.is_some_and(|max| depth >= max) {
+                debug!("{}: not descended into, at --max-depth", path.display());
+                self.skipped.push((path, SkipReason::NotDescended));
+                continue;
+            }
+            if self.options.one_file_system {
+                if depth == 0 {
+                    self.root_device = device(&metadata);
+                } else if device(&metadata) != self.root_device {
+                    debug!(
+                        "{}: not descended into, another filesystem (see --one-file-system)",
+                        path.display()
+                    );
+                    self.skipped.push((path, SkipReason::NotDescended));
+                    continue;
+                }
+            }
+            // a symlink back up to an ancestor would recurse forever
+            if self.options.follow {
+                let id = match dir_id(&path, &metadata) {
+                    Ok(id) => id,
+                    Err(err) => return Some(Err(annotate(&path, err))),
+                };
+                if self.ancestors.contains(&id) {
+                    let err = io::Error::other("symlink loop, not followed");
+                    return Some(Err(annotate(&path, err)));
+                }
+                self.ancestors.push(id);
+            }
+            if let Err(err) = self.push_entries(&path, depth) {
+                return Some(Err(annotate(&path, err)));
+            }
+        }
+        None
+    }
+}
+
+// what identifies a directory however it was reached: its device and inode,
+// or where the platform has neither, its canonical path
+#[cfg(unix)]
+type DirId = (u64, u64);
+#[cfg(not(unix))]
+type DirId = PathBuf;
+
+#[cfg(unix)]
+fn dir_id(_path: &Path, metadata: &fs::Metadata) -> io::Result<DirId> {
+    use std::os::unix::fs::MetadataExt;
+    Ok((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn dir_id(path: &Path, _metadata: &fs::Metadata) -> io::Result<DirId> {
+    fs::canonicalize(path)
+}
+
+// which filesystem the entry is on - where the platform can't say, every
+// entry is taken to be on the root's
+#[cfg(unix)]
+fn device(metadata: &fs::Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.dev())
+}
+
+#[cfg(not(unix))]
+fn device(_metadata: &fs::Metadata) -> Option<u64> {
+    None
+}
+
+// dotfiles like .git or .env - only entries found while walking are
+// skipped, a hidden path given as the root is still searched
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+}
+
+// the deepest directory with a rule for the path decides, then the
+// repository's excludes
+fn is_ignored(
+    ignores: &[Option<Ignore>],
+    excludes: Option<&Ignore>,
+    path: &Path,
+    is_dir: bool,
+) -> bool {
+    ignores
+        .iter()
+        .rev()
+        .flatten()
+        .chain(excludes)
+        .find_map(|ignore| ignore.matched(path, is_dir))
+        .unwrap_or(false)
+}
+
+// add the path to an io error so the message says which file it was about
+fn annotate(path: &Path, err: io::Error) -> io::Error {
+    io::Error::new(err.kind(), format!("{}: {err}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TempDir;
+
+    // a small tree in a directory of the test's own
+    struct TempTree(TempDir);
+
+    impl TempTree {
+        fn new(name: &str, files: &[&str]) -> TempTree {
+            let root = TempDir::new(name);
+            for file in files {
+                let path = root.join(file);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, "contents").unwrap();
+            }
+            TempTree(root)
+        }
+
+        // the walked paths, relative to the root
+        fn walk(&self, options: &WalkOptions) -> Vec<String> {
+            Walk::new(&self.0, options)
+                .map(|path| {
+                    let path = path.unwrap();
+                    let relative = path.strip_prefix(&self.0).unwrap();
+                    relative.to_string_lossy().replace('\\', "/")
+                })
+                .collect()
+        }
+    }
+
+    #[test]
+    fn walks_every_file_in_order() {
+        let tree = TempTree::new("walk", &["b.txt", "a/z.txt", "a/deep/y.txt"]);
+
+        assert_eq!(
+            vec!["a/deep/y.txt", "a/z.txt", "b.txt"],
+            tree.walk(&WalkOptions::default())
+        );
+    }
+
+    #[test]
+    fn max_depth_limits_descent() {
+        let tree = TempTree::new("depth", &["b.txt", "a/z.txt", "a/deep/y.txt"]);
+        let options = WalkOptions {
+            max_depth: Some(2),
+            ..WalkOptions::default()
+        };
+
+        assert_eq!(vec!["a/z.txt", "b.txt"], tree.walk(&options));
+    }
+
+    #[test]
+    fn hidden_files_are_skipped_unless_asked_for() {
+        let tree = TempTree::new("hidden", &[".env", ".git/config", "a/.b.txt", "a/c.txt"]);
+
+        assert_eq!(vec!["a/c.txt"], tree.walk(&WalkOptions::default()));
+
+        let options = WalkOptions {
+            hidden: true,
+            ..WalkOptions::default()
+        };
+        assert_eq!(
+            vec![".env", ".git/config", "a/.b.txt", "a/c.txt"],
+            tree.walk(&options)
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn follow_breaks_symlink_loops() {
+        let tree = TempTree::new("follow", &["a/b.txt", "c.txt"]);
+        std::os::unix::fs::symlink("..", tree.0.join("a/up")).unwrap();
+        std::os::unix::fs::symlink("c.txt", tree.0.join("link.txt")).unwrap();
+
+        assert_eq!(vec!["a/b.txt", "c.txt"], tree.walk(&WalkOptions::default()));
+
+        let options = WalkOptions {
+            follow: true,
+            ..WalkOptions::default()
+        };
+        let (found, loops): (Vec<_>, Vec<_>) =
+            Walk::new(&tree.0, &options).partition(Result::is_ok);
+        let found: Vec<_> = found
+            .into_iter()
+            .map(|path| path.unwrap().strip_prefix(&tree.0).unwrap().to_owned())
+            .collect();
+        assert_eq!(
+            vec![
+                Path::new("a/b.txt"),
+                Path::new("c.txt"),
+                Path::new("link.txt")
+            ],
+            found
+        );
+        assert_eq!(1, loops.len());
+    }
+
+    #[test]
+    fn gitignore_rules_apply_below_their_directory() {
+        let tree = TempTree::new(
+            "gitignore",
+            &[
+                "a.log",
+                "keep.log",
+                "src/main.rs",
+                "src/b.log",
+                "target/out.rs",
+                "web/gen.js",
+                "web/app.js",
+            ],
+        );
+        fs::create_dir(tree.0.join(".git")).unwrap();
+        fs::write(tree.0.join(".gitignore"), "/target\n*.log\n").unwrap();
+        // a nested file can bring back what a parent ignored
+        fs::write(tree.0.join("src/.gitignore"), "!b.log\n").unwrap();
+        fs::write(tree.0.join("web/.gitignore"), "gen.js\n").unwrap();
+
+        assert_eq!(
+            vec!["src/b.log", "src/main.rs", "web/app.js"],
+            tree.walk(&WalkOptions::default())
+        );
+
+        let options = WalkOptions {
+            no_ignore: true,
+            ..WalkOptions::default()
+        };
+        assert_eq!(7, tree.walk(&options).len());
+    }
+
+    #[test]
+    fn minigrepignore_overrides_ignore_and_gitignore() {
+        let tree = TempTree::new("precedence", &["a.txt", "b.txt", "c.txt"]);
+        fs::create_dir(tree.0.join(".git")).unwrap();
+        fs::write(tree.0.join(".gitignore"), "*.txt\n").unwrap();
+        fs::write(tree.0.join(".ignore"), "!b.txt\n!c.txt\n").unwrap();
+        fs::write(tree.0.join(".minigrepignore"), "c.txt\n").unwrap();
+
+        assert_eq!(vec!["b.txt"], tree.walk(&WalkOptions::default()));
+    }
+
+    #[test]
+    fn gitignore_only_applies_in_a_repository() {
+        let tree = TempTree::new("no-repo", &["a.log", "b.txt", "repo/c.log", "repo/d.txt"]);
+        fs::write(tree.0.join(".gitignore"), "*.log\n").unwrap();
+        fs::write(tree.0.join(".ignore"), "b.txt\n").unwrap();
+        // the repository's own .gitignore applies, not the one outside it
+        fs::create_dir(tree.0.join("repo/.git")).unwrap();
+        fs::write(tree.0.join("repo/.gitignore"), "*.txt\n").unwrap();
+
+        assert_eq!(
+            vec!["a.log", "repo/c.log"],
+            tree.walk(&WalkOptions::default())
+        );
+    }
+
+    #[test]
+    fn git_excludes_apply_in_the_repository() {
+        let tree = TempTree::new(
+            "excludes",
+            &["a.tmp", "b.log", "src/gen.rs", "src/main.rs", "src/c.tmp"],
+        );
+        fs::create_dir_all(tree.0.join(".git/info")).unwrap();
+        fs::write(tree.0.join(".git/info/exclude"), "*.tmp\n/src/gen.rs\n").unwrap();
+        // below the excludes, like the rest of git
+        fs::write(tree.0.join(".gitignore"), "!a.tmp\n").unwrap();
+        let options = WalkOptions {
+            no_ignore_global: true,
+            ..WalkOptions::default()
+        };
+
+        assert_eq!(vec!["a.tmp", "b.log", "src/main.rs"], tree.walk(&options));
+
+        // with the walk starting inside the repository
+        let src: Vec<_> = Walk::new(&tree.0.join("src"), &options)
+            .map(|path| path.unwrap().file_name().unwrap().to_owned())
+            .collect();
+        assert_eq!(vec!["main.rs"], src);
+    }
+
+    #[test]
+    fn globs_filter_during_traversal() {
+        let tree = TempTree::new(
+            "globs",
+            &["build.rs", "src/main.rs", "src/notes.md", "target/gen.rs"],
+        );
+        let options = WalkOptions {
+            globs: ["*.rs", "!target/**", "!build.rs"]
+                .map(String::from)
+                .to_vec(),
+            ..WalkOptions::default()
+        };
+
+        assert_eq!(vec!["src/main.rs"], tree.walk(&options));
+    }
+
+    #[test]
+    fn max_filesize_skips_big_files() {
+        let tree = TempTree::new("filesize", &["small.txt"]);
+        fs::write(tree.0.join("big.txt"), "x".repeat(100)).unwrap();
+        let options = WalkOptions {
+            max_filesize: Some(10),
+            ..WalkOptions::default()
+        };
+
+        assert_eq!(vec!["small.txt"], tree.walk(&options));
+    }
+
+    #[test]
+    fn skipped_paths_say_why() {
+        let tree = TempTree::new(
+            "skipped",
+            &["small.txt", ".env", "notes.md", "a/b/deep.txt"],
+        );
+        fs::write(tree.0.join("big.txt"), "x".repeat(100)).unwrap();
+        let options = WalkOptions {
+            max_filesize: Some(10),
+            max_depth: Some(1),
+            globs: vec!["!*.md".to_string()],
+            ..WalkOptions::default()
+        };
+
+        let mut walk = Walk::new(&tree.0, &options);
+        walk.by_ref().for_each(drop);
+        let mut skipped: Vec<_> = walk
+            .take_skipped()
+            .into_iter()
+            .map(|(path, reason)| (path.strip_prefix(&tree.0).unwrap().to_path_buf(), reason))
+            .collect();
+        skipped.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            vec![
+                (PathBuf::from(".env"), SkipReason::Hidden),
+                (PathBuf::from("a"), SkipReason::NotDescended),
+                (PathBuf::from("big.txt"), SkipReason::TooBig),
+                (PathBuf::from("notes.md"), SkipReason::Filtered),
+            ],
+            skipped
+        );
+        assert!(walk.take_skipped().is_empty());
+    }
+
+    #[test]
+    fn one_file_system_still_walks_the_roots() {
+        let tree = TempTree::new("one-fs", &["b.txt", "a/z.txt", "a/deep/y.txt"]);
+        let options = WalkOptions {
+            one_file_system: true,
+            ..WalkOptions::default()
+        };
+
+        assert_eq!(
+            vec!["a/deep/y.txt", "a/z.txt", "b.txt"],
+            tree.walk(&options)
+        );
+    }
+
+    #[test]
+    fn types_filter_files_but_not_directories() {
+        let tree = TempTree::new("types", &["README.md", "src/main.rs", "src/lib.rs"]);
+        let options = WalkOptions {
+            types: Types::new(&["rust".to_string()], &[], &[]).unwrap(),
+            ..WalkOptions::default()
+        };
+
+        assert_eq!(vec!["src/lib.rs", "src/main.rs"], tree.walk(&options));
+    }
+}
@@ -0,0 +1,133 @@
+use notify::event::EventKind;
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+// how long --watch waits for a notification before seeing whether it was
+// interrupted - and, without a notifier, between looking at the files again
+pub const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// a save tends to come as a handful of notifications, which are waited out
+// so the files are only looked at once
+const SETTLE: Duration = Duration::from_millis(50);
+
+// Notifier waits for the filesystem to say something under the paths given to
+// --watch changed. Where the platform's notifier can't watch them (too many
+// watches, a filesystem it doesn't cover) it falls back to polling.
+pub struct Notifier {
+    events: Option<(RecommendedWatcher, Receiver<notify::Result<Event>>)>,
+}
+
+impl Notifier {
+    pub fn new(paths: &[String]) -> Notifier {
+        let (sender, receiver) = mpsc::channel();
+        let watched = notify::recommended_watcher(sender).and_then(|mut watcher| {
+            for path in paths {
+                let path = Path::new(path);
+                if path.is_dir() {
+                    watcher.watch(path, RecursiveMode::Recursive)?;
+                } else {
+                    // a file's directory, as editors save by replacing the file
+                    let dir = match path.parent() {
+                        Some(dir) if !dir.as_os_str().is_empty() => dir,
+                        _ => Path::new("."),
+                    };
+                    watcher.watch(dir, RecursiveMode::NonRecursive)?;
+                }
+            }
+            Ok(watcher)
+        });
+        Notifier {
+            events: watched.ok().map(|watcher| (watcher, receiver)),
+        }
+    }
+
+    // waits up to POLL_INTERVAL, and says whether the files may have changed
+    pub fn wait(&self) -> bool {
+        let Some((_, events)) = &self.events else {
+            thread::sleep(POLL_INTERVAL);
+            return true;
+        };
+        match events.recv_timeout(POLL_INTERVAL) {
+            // minigrep's own reads are notified too, and never change anything
+            Ok(Ok(event)) if matches!(event.kind, EventKind::Access(_)) => false,
+            // an error may mean notifications were lost, so look anyway
+            Ok(_) => {
+                thread::sleep(SETTLE);
+                events.try_iter().for_each(drop);
+                true
+            }
+            Err(RecvTimeoutError::Timeout) => false,
+            Err(RecvTimeoutError::Disconnected) => {
+                thread::sleep(POLL_INTERVAL);
+                true
+            }
+        }
+    }
+}
+
+// Stamps remembers the size and modification time of each searched file, so
+// --watch can tell which files are new or have changed since it last looked -
+// a notification only says something somewhere under the paths did.
+#[derive(Default)]
+pub struct Stamps {
+    stamps: HashMap<String, (Option<SystemTime>, u64)>,
+}
+
+impl Stamps {
+    // the files that are new or changed since the last call, keeping their
+    // order - files that have gone away are forgotten
+    pub fn changed(&mut self, files: &[(String, bool)]) -> Vec<(String, bool)> {
+        let mut stamps = HashMap::new();
+        let mut changed = Vec::new();
+        for (path, walked) in files {
+            // something that isn't a file on disk (stdin, a URL) never changes
+            let Ok(metadata) = fs::metadata(path) else {
+                continue;
+            };
+            let stamp = (metadata.modified().ok(), metadata.len());
+            if self.stamps.get(path) != Some(&stamp) {
+                changed.push((path.clone(), *walked));
+            }
+            stamps.insert(path.clone(), stamp);
+        }
+        self.stamps = stamps;
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TempDir;
+
+    #[test]
+    fn reports_new_and_changed_files() {
+        let dir = TempDir::new("watch");
+        let path = dir.join("log.txt");
+        fs::write(&path, "one\n").unwrap();
+        let files = vec![(path.to_string_lossy().into_owned(), true)];
+
+        let mut stamps = Stamps::default();
+        assert_eq!(files, stamps.changed(&files));
+        assert!(stamps.changed(&files).is_empty());
+
+        fs::write(&path, "one\ntwo\n").unwrap();
+        assert_eq!(files, stamps.changed(&files));
+    }
+
+    #[test]
+    fn notifies_when_a_file_is_written() {
+        let dir = TempDir::new("notify");
+        let notifier = Notifier::new(&[dir.to_string_lossy().into_owned()]);
+        assert!(notifier.events.is_some());
+        assert!(!notifier.wait());
+
+        fs::write(dir.join("log.txt"), "one\n").unwrap();
+        assert!(notifier.wait());
+    }
+}